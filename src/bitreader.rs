@@ -0,0 +1,197 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+
+use crate::dat_decompress::DecompressError;
+
+const U32_IN_BITS: u8 = 32;
+
+/// Bit reader state shared by the DAT and texture decompressors.
+///
+/// The input must not contain the archive's chunk CRCs; `DatFile` strips them before inflating.
+///
+/// `head_data` holds the next 32 bits to decode, most significant bit first, and
+/// `buffer_data` the bits pulled after them.
+#[derive(Debug, Default)]
+pub(crate) struct StateData<'a> {
+    pub(crate) input_buffer: Cursor<&'a [u8]>,
+    pub(crate) buffer_position_bytes: u64,
+    /// Length of the input buffer in bytes, computed once up front.
+    pub(crate) total_len: u64,
+    pub(crate) bytes_available: u32,
+    pub(crate) head_data: u32,
+    pub(crate) buffer_data: u32,
+    pub(crate) bytes_available_data: u8,
+}
+
+impl<'a> StateData<'a> {
+    /// Wrap `input_data` and load its first 32 bits, ready for `read_bits`.
+    pub(crate) fn new(input_data: &'a [u8]) -> std::io::Result<StateData<'a>> {
+        let mut state_data = StateData {
+            bytes_available: input_data.len() as u32,
+            total_len: input_data.len() as u64,
+            input_buffer: Cursor::new(input_data),
+            ..Default::default()
+        };
+        let mut head_data: u32 = 0;
+        let mut bytes_available_data: u8 = 0;
+
+        pull_byte(&mut state_data, &mut head_data, &mut bytes_available_data)?;
+
+        state_data.head_data = head_data;
+        state_data.bytes_available_data = bytes_available_data;
+        Ok(state_data)
+    }
+}
+
+pub(crate) fn pull_byte(
+    state_data: &mut StateData,
+    head_data: &mut u32,
+    bytes_available_data: &mut u8,
+) -> std::io::Result<()> {
+    if state_data.bytes_available >= std::mem::size_of::<u32>() as u32 {
+        *head_data = state_data.input_buffer.read_u32::<LittleEndian>()?;
+        state_data.bytes_available -= std::mem::size_of::<u32>() as u32;
+        state_data.buffer_position_bytes = state_data.input_buffer.position();
+        *bytes_available_data = (std::mem::size_of::<u32>() as u32 * 8) as u8;
+    } else {
+        *head_data = 0;
+        *bytes_available_data = 0;
+    }
+    Ok(())
+}
+
+/// Whether every input byte has been pulled and no bits are left to decode.
+pub(crate) fn is_input_exhausted(state_data: &StateData) -> bool {
+    state_data.buffer_position_bytes >= state_data.total_len && state_data.bytes_available_data == 0
+}
+
+/// Reject bit counts wider than the 32-bit head; widths can come from untrusted symbols.
+fn check_bits_number(bits_number: u8) -> std::io::Result<()> {
+    if bits_number > U32_IN_BITS {
+        return Err(DecompressError::InvalidBitRequest { bits: bits_number }.into());
+    }
+    Ok(())
+}
+
+/// Peek at the next `bits_number` bits without consuming them.
+///
+/// Past the end of the input the head is zero-filled, so a short read yields the remaining bits followed by zeros.
+pub(crate) fn read_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<u32> {
+    check_bits_number(bits_number)?;
+    if bits_number == 0 {
+        return Ok(0);
+    }
+    Ok(state_data.head_data >> (U32_IN_BITS - bits_number))
+}
+
+pub(crate) fn drop_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<()> {
+    check_bits_number(bits_number)?;
+    if bits_number == 0 {
+        return Ok(());
+    }
+    // Past the end of the input only zero padding is left, so drop it all; callers that need
+    // more data see `is_input_exhausted` and report the truncation themselves
+    if state_data.bytes_available_data < bits_number {
+        state_data.head_data = 0;
+        state_data.buffer_data = 0;
+        state_data.bytes_available_data = 0;
+        return Ok(());
+    }
+    #[allow(unused_assignments)]
+    let mut new_bits_available: u8 = 0;
+    new_bits_available = state_data.bytes_available_data.wrapping_sub(bits_number);
+    if new_bits_available >= std::mem::size_of::<u32>() as u8 * 8 {
+        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
+            state_data.head_data = state_data.buffer_data;
+            state_data.buffer_data = 0;
+        } else {
+            state_data.head_data = (state_data.head_data << bits_number)
+                | (state_data.buffer_data
+                    >> ((std::mem::size_of::<u32>() as u8 * 8) - bits_number));
+            state_data.buffer_data <<= bits_number;
+        }
+        state_data.bytes_available_data = new_bits_available;
+    } else {
+        let mut new_value: u32 = 0;
+        let mut pulled_bits: u8 = 0;
+        pull_byte(state_data, &mut new_value, &mut pulled_bits)?;
+
+        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
+            state_data.head_data = 0;
+        } else {
+            state_data.head_data <<= bits_number;
+        }
+        state_data.head_data |= (state_data.buffer_data
+            >> ((std::mem::size_of::<u32>() as u8 * 8) - bits_number))
+            | (new_value >> (new_bits_available));
+        if new_bits_available > 0 {
+            state_data.buffer_data =
+                new_value << ((std::mem::size_of::<u32>() as u8 * 8) - new_bits_available);
+        }
+        state_data.bytes_available_data = new_bits_available + pulled_bits;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_bit_counts_are_rejected() {
+        let input_data = [0xFFu8; 8];
+        let mut state_data = StateData::new(&input_data).unwrap();
+
+        for bits_number in [33, 64, u8::MAX] {
+            let err = read_bits(&mut state_data, bits_number).unwrap_err();
+            assert_eq!(
+                DecompressError::from_io(&err),
+                Some(DecompressError::InvalidBitRequest { bits: bits_number })
+            );
+            let err = drop_bits(&mut state_data, bits_number).unwrap_err();
+            assert_eq!(
+                DecompressError::from_io(&err),
+                Some(DecompressError::InvalidBitRequest { bits: bits_number })
+            );
+        }
+
+        assert_eq!(read_bits(&mut state_data, 32).unwrap(), u32::MAX);
+        assert_eq!(state_data.bytes_available_data, 32);
+    }
+
+    #[test]
+    fn bits_cross_words_and_pad_with_zeros_at_the_end() {
+        let input_data: Vec<u8> = [0x1234_5678u32, 0x9ABC_DEF0]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let mut state_data = StateData::new(&input_data).unwrap();
+        assert_eq!(read_bits(&mut state_data, 8).unwrap(), 0x12);
+
+        drop_bits(&mut state_data, 8).unwrap();
+        assert_eq!(read_bits(&mut state_data, 32).unwrap(), 0x3456_789A);
+        assert_eq!(state_data.bytes_available_data, 56);
+
+        drop_bits(&mut state_data, 32).unwrap();
+        assert_eq!(read_bits(&mut state_data, 24).unwrap(), 0xBC_DEF0);
+        assert_eq!(read_bits(&mut state_data, 32).unwrap(), 0xBCDE_F000);
+        assert!(!is_input_exhausted(&state_data));
+
+        drop_bits(&mut state_data, 24).unwrap();
+        assert_eq!(read_bits(&mut state_data, 32).unwrap(), 0);
+        assert!(is_input_exhausted(&state_data));
+    }
+
+    #[test]
+    fn dropping_past_the_end_clamps_to_exhausted() {
+        let input_data = 0x8000_0001u32.to_le_bytes();
+        let mut state_data = StateData::new(&input_data).unwrap();
+
+        drop_bits(&mut state_data, 31).unwrap();
+        assert_eq!(read_bits(&mut state_data, 1).unwrap(), 1);
+        drop_bits(&mut state_data, 8).unwrap();
+        assert_eq!(state_data.bytes_available_data, 0);
+        assert_eq!(read_bits(&mut state_data, 32).unwrap(), 0);
+        assert!(is_input_exhausted(&state_data));
+    }
+}
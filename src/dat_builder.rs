@@ -0,0 +1,336 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::dat_decompress::static_symbol_table;
+use crate::dat_parser::{
+    DAT_BLOCK_SIZE, DAT_MAGIC, MFT_ENTRY_BYTES, MFT_INDEX_ENTRY_BYTES, MFT_MAGIC,
+    crc_chunk_size_for_header, crc32c,
+};
+
+/// Size of the DAT header written by `DatBuilder`.
+const DAT_HEADER_SIZE: u32 = 40;
+/// Version byte written into the DAT header.
+const DAT_VERSION: u8 = 151;
+/// MFT rows written before the first entry: the DAT header, the index table and the MFT itself.
+const RESERVED_MFT_ROWS: u32 = 3;
+/// Symbol `compress_entry_data` uses for a copy of the previous byte; literals are 0x00..=0xFF.
+const REPEAT_SYMBOL: u16 = 0x100;
+/// Code length of every symbol in the literal tree written by `compress_entry_data`.
+const LITERAL_CODE_BITS: u8 = 9;
+/// Codes read per Huffman tree pair when the stream's count field is 0.
+const CODES_PER_BLOCK: usize = 0x1000;
+/// Static dictionary symbol describing 8 symbols with 9-bit codes: `(8 - 1) << 5 | 9`.
+const EIGHT_9_BIT_SYMBOLS: u16 = 0xE9;
+/// Static dictionary symbol describing 1 symbol with a 9-bit code.
+const ONE_9_BIT_SYMBOL: u16 = 0x09;
+/// Static dictionary symbol describing 1 symbol with a 1-bit code.
+const ONE_1_BIT_SYMBOL: u16 = 0x01;
+
+struct BuilderEntry {
+    data: Vec<u8>,
+    compression_flag: u16,
+}
+
+/// Builds a small, valid DAT archive in memory, for tests and fixtures.
+///
+/// Entries are stored after three reserved MFT rows, so the first entry gets base ID 4.
+/// Compressed entries must already hold a compressed stream; the builder only adds the
+/// chunk CRCs that `DatFile` strips when extracting them.
+#[derive(Default)]
+pub struct DatBuilder {
+    entries: Vec<BuilderEntry>,
+    index_data: Vec<(u32, u32)>,
+    /// Written into `DatHeader::chunk_size`; `None` writes the 512-byte block size.
+    chunk_size: Option<u32>,
+}
+
+impl DatBuilder {
+    pub fn new() -> DatBuilder {
+        DatBuilder::default()
+    }
+
+    /// Add an entry and return its base ID. No file ID points at it until `add_file_id` is called.
+    pub fn add_entry(&mut self, data: Vec<u8>, compression_flag: u16) -> u32 {
+        self.entries.push(BuilderEntry {
+            data,
+            compression_flag,
+        });
+        RESERVED_MFT_ROWS + self.entries.len() as u32
+    }
+
+    /// Map `file_id` to the entry with `base_id` in the index table.
+    pub fn add_file_id(&mut self, file_id: u32, base_id: u32) -> &mut DatBuilder {
+        self.index_data.push((file_id, base_id));
+        self
+    }
+
+    /// Write `chunk_size` into the DAT header instead of the block size, so it becomes the CRC chunk stride.
+    pub fn chunk_size(&mut self, chunk_size: u32) -> &mut DatBuilder {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Lay out the header, entry data, index table and MFT, and return the archive bytes.
+    pub fn build(&self) -> Vec<u8> {
+        let header_chunk_size = self.chunk_size.unwrap_or(DAT_BLOCK_SIZE);
+        let crc_chunk_size = crc_chunk_size_for_header(header_chunk_size);
+        let mut output = vec![0; DAT_HEADER_SIZE as usize];
+        // (offset, size, compression_flag) for every MFT row after the header row.
+        let mut mft_rows: Vec<(u64, u32, u16)> = Vec::new();
+        mft_rows.push((0, DAT_HEADER_SIZE, 0));
+
+        let mut entry_rows = Vec::new();
+        for entry in &self.entries {
+            let stored_data = if entry.compression_flag != 0 {
+                add_chunk_crcs(&entry.data, crc_chunk_size)
+            } else {
+                entry.data.clone()
+            };
+            entry_rows.push((
+                output.len() as u64,
+                stored_data.len() as u32,
+                entry.compression_flag,
+            ));
+            output.extend_from_slice(&stored_data);
+        }
+
+        let index_offset = output.len() as u64;
+        for &(file_id, base_id) in &self.index_data {
+            output.write_u32::<LittleEndian>(file_id).unwrap();
+            output.write_u32::<LittleEndian>(base_id).unwrap();
+        }
+        mft_rows.push((
+            index_offset,
+            self.index_data.len() as u32 * MFT_INDEX_ENTRY_BYTES,
+            0,
+        ));
+
+        // The MFT header row is counted in the entry count.
+        let mft_entry_count = RESERVED_MFT_ROWS + self.entries.len() as u32 + 1;
+        let mft_offset = output.len() as u64;
+        let mft_size = mft_entry_count * MFT_ENTRY_BYTES;
+        mft_rows.push((mft_offset, mft_size, 0));
+        mft_rows.extend(entry_rows);
+
+        output.extend_from_slice(&MFT_MAGIC);
+        output.write_u64::<LittleEndian>(0).unwrap();
+        output.write_u32::<LittleEndian>(mft_entry_count).unwrap();
+        output.write_u32::<LittleEndian>(0).unwrap();
+        output.write_u32::<LittleEndian>(0).unwrap();
+        for (offset, size, compression_flag) in mft_rows {
+            output.write_u64::<LittleEndian>(offset).unwrap();
+            output.write_u32::<LittleEndian>(size).unwrap();
+            output.write_u16::<LittleEndian>(compression_flag).unwrap();
+            output.write_u16::<LittleEndian>(0).unwrap();
+            output.write_u32::<LittleEndian>(0).unwrap();
+            output.write_u32::<LittleEndian>(0).unwrap();
+        }
+
+        let mut header = Vec::with_capacity(DAT_HEADER_SIZE as usize);
+        header.write_u8(DAT_VERSION).unwrap();
+        header.extend_from_slice(&DAT_MAGIC);
+        header.write_u32::<LittleEndian>(DAT_HEADER_SIZE).unwrap();
+        header.write_u32::<LittleEndian>(0).unwrap();
+        header.write_u32::<LittleEndian>(header_chunk_size).unwrap();
+        let header_crc = crc32c(&header);
+        header.write_u32::<LittleEndian>(header_crc).unwrap();
+        header.write_u32::<LittleEndian>(0).unwrap();
+        header.write_u64::<LittleEndian>(mft_offset).unwrap();
+        header.write_u32::<LittleEndian>(mft_size).unwrap();
+        header.write_u32::<LittleEndian>(0).unwrap();
+        output[..DAT_HEADER_SIZE as usize].copy_from_slice(&header);
+
+        output
+    }
+}
+
+/// Follow every `chunk_size - 4` bytes of `data`, and its end, with the CRC-32C of that piece.
+fn add_chunk_crcs(data: &[u8], chunk_size: usize) -> Vec<u8> {
+    let mut stored_data = Vec::with_capacity(data.len() + data.len() / (chunk_size - 4) * 4 + 4);
+    for piece in data.chunks(chunk_size - 4) {
+        stored_data.extend_from_slice(piece);
+        stored_data
+            .write_u32::<LittleEndian>(crc32c(piece))
+            .unwrap();
+    }
+    stored_data
+}
+
+/// Encode `data` as a compressed entry stream, the input `inflate_dat_file_buffer` expects.
+///
+/// Only literals and copies of the previous byte are written, so the output is larger than the
+/// streams in real archives. It is meant for fixtures that need compressed entries.
+pub fn compress_entry_data(data: &[u8]) -> Vec<u8> {
+    let (dictionary_bits, dictionary_symbols) = static_symbol_table();
+    let dictionary: Vec<(u16, u8)> = dictionary_symbols
+        .iter()
+        .copied()
+        .zip(dictionary_bits.iter().copied())
+        .collect();
+    let dictionary_codes = canonical_codes(&dictionary);
+    // Symbols are added from the highest down, like parse_huffmantree does
+    let literal_tree: Vec<(u16, u8)> = (0..=REPEAT_SYMBOL)
+        .rev()
+        .map(|symbol| (symbol, LITERAL_CODE_BITS))
+        .collect();
+    let literal_codes = canonical_codes(&literal_tree);
+    let copy_codes = canonical_codes(&[(0, 1)]);
+
+    let mut bit_writer = BitWriter::default();
+    // An unused half byte, then 0 so that every copy length gets 1 added
+    bit_writer.write(0, 4);
+    bit_writer.write(0, 4);
+
+    for (block_index, block) in data.chunks(CODES_PER_BLOCK).enumerate() {
+        // Literal tree: 257 symbols of 9 bits each
+        bit_writer.write(literal_tree.len() as u32, 16);
+        for _ in 0..(REPEAT_SYMBOL / 8) {
+            bit_writer.write_code(dictionary_codes[EIGHT_9_BIT_SYMBOLS as usize]);
+        }
+        bit_writer.write_code(dictionary_codes[ONE_9_BIT_SYMBOL as usize]);
+        // Copy tree: distance symbol 0, a distance of 1, with a 1-bit code
+        bit_writer.write(1, 16);
+        bit_writer.write_code(dictionary_codes[ONE_1_BIT_SYMBOL as usize]);
+        // 0 selects CODES_PER_BLOCK codes before the next tree pair
+        bit_writer.write(0, 4);
+
+        for (position, &byte) in block.iter().enumerate() {
+            let data_position = block_index * CODES_PER_BLOCK + position;
+            if data_position > 0 && data[data_position - 1] == byte {
+                bit_writer.write_code(literal_codes[REPEAT_SYMBOL as usize]);
+                bit_writer.write_code(copy_codes[0]);
+            } else {
+                bit_writer.write_code(literal_codes[byte as usize]);
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    output.write_u32::<LittleEndian>(0).unwrap();
+    output.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+    for word in bit_writer.finish() {
+        output.write_u32::<LittleEndian>(word).unwrap();
+    }
+    output
+}
+
+/// Assign the canonical codes that `build_huffmantree` derives from symbols added in this order.
+///
+/// Returns `(code, bits)` indexed by symbol; symbols that were not added get `(0, 0)`.
+fn canonical_codes(symbols: &[(u16, u8)]) -> Vec<(u32, u8)> {
+    let mut symbols_by_bits: Vec<Vec<u16>> = vec![Vec::new(); 32];
+    for &(symbol, bits) in symbols {
+        symbols_by_bits[bits as usize].push(symbol);
+    }
+    let symbol_count = symbols.iter().map(|&(symbol, _)| symbol as usize + 1).max();
+    let mut codes = vec![(0, 0); symbol_count.unwrap_or(0)];
+
+    // The builder keeps each length's symbols as a list headed by the last one added
+    let mut code: u32 = 0;
+    for (bits, bits_symbols) in symbols_by_bits.iter().enumerate() {
+        for &symbol in bits_symbols.iter().rev() {
+            codes[symbol as usize] = (code, bits as u8);
+            code = code.wrapping_sub(1);
+        }
+        code = (code << 1) + 1;
+    }
+    codes
+}
+
+/// Packs bits most significant first into the 32-bit words the bit reader consumes.
+#[derive(Default)]
+struct BitWriter {
+    words: Vec<u32>,
+    current_word: u32,
+    current_bits: u8,
+}
+
+impl BitWriter {
+    /// Append the low `bits` bits of `value`.
+    fn write(&mut self, value: u32, bits: u8) {
+        for bit_index in (0..bits).rev() {
+            self.current_word = (self.current_word << 1) | ((value >> bit_index) & 1);
+            self.current_bits += 1;
+            if self.current_bits == 32 {
+                self.words.push(self.current_word);
+                self.current_word = 0;
+                self.current_bits = 0;
+            }
+        }
+    }
+
+    fn write_code(&mut self, (code, bits): (u32, u8)) {
+        self.write(code, bits);
+    }
+
+    /// Pad the last word with zeros and return the words, followed by two words of padding
+    /// so the reader never runs out of input while decoding the final codes.
+    fn finish(mut self) -> Vec<u32> {
+        if self.current_bits > 0 {
+            self.write(0, 32 - self.current_bits);
+        }
+        self.words.extend([0, 0]);
+        self.words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dat_decompress::inflate_dat_file_buffer;
+    use crate::dat_parser::{ArchiveId, CHUNK_SIZE, DatFile};
+    use std::io::Cursor;
+
+    fn load(bytes: Vec<u8>) -> DatFile {
+        let len = bytes.len() as u64;
+        DatFile::load_from_reader(Cursor::new(bytes), len).unwrap()
+    }
+
+    #[test]
+    fn compress_entry_data_round_trips_through_inflate() {
+        let data: Vec<u8> = (0..20000u32).map(|i| ((i / 7) % 251) as u8).collect();
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        inflate_dat_file_buffer(
+            compress_entry_data(&data),
+            &mut output_data_size,
+            &mut output_data,
+        )
+        .unwrap();
+        assert_eq!(output_data_size, data.len() as u32);
+        assert_eq!(output_data, data);
+    }
+
+    #[test]
+    fn built_archive_round_trips() {
+        let stored = b"stored as is".to_vec();
+        let compressed: Vec<u8> = (0..100_000u32).map(|i| (i % 97) as u8).collect();
+
+        let mut builder = DatBuilder::new();
+        let stored_base_id = builder.add_entry(stored.clone(), 0);
+        let compressed_base_id = builder.add_entry(compress_entry_data(&compressed), 8);
+        builder
+            .add_file_id(10, stored_base_id)
+            .add_file_id(11, compressed_base_id);
+        let mut dat_file = load(builder.build());
+
+        assert_eq!(stored_base_id, 4);
+        assert_eq!(dat_file.mft_data.len(), 5);
+        assert_eq!(dat_file.mft_index_data.len(), 2);
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 10).unwrap();
+        assert_eq!(raw_data, stored);
+        assert_eq!(decompressed_data, stored);
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 11).unwrap();
+        // Two CRC chunks: one full, one partial
+        assert!(raw_data.len() > CHUNK_SIZE);
+        assert_eq!(decompressed_data, compressed);
+
+        let (_, decompressed_data) = dat_file
+            .extract_mft_data(ArchiveId::BaseId, compressed_base_id as usize)
+            .unwrap();
+        assert_eq!(decompressed_data, compressed);
+    }
+}
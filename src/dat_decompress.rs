@@ -1,329 +1,388 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+use crate::bitreader::{StateData, drop_bits, is_input_exhausted, read_bits};
+use crate::huffman::{
+    HuffmanTree, HuffmanTreeBuilder, MAX_SYMBOL_VALUE, add_symbol, build_huffmantree, read_code,
+};
+use log::{error, warn};
+use std::sync::OnceLock;
+use std::time::Instant;
 
-const MAX_BITS_HASH: usize = 8;
-const MAX_CODE_BITS_LENGTH: usize = 32;
-const MAX_SYMBOL_VALUE: usize = 285;
 const HALF_BYTE: u8 = 4;
-const U8_IN_BITS: u8 = 8;
 const U16_IN_BITS: u8 = 16;
 const U32_IN_BITS: u8 = 32;
-
-#[derive(Debug, Default)]
-struct StateData {
-    input_buffer: Cursor<Vec<u8>>,
-    buffer_position_bytes: u64,
-    bytes_available: u32,
-    head_data: u32,
-    buffer_data: u32,
-    bytes_available_data: u8,
+/// Number of codes decoded between checks of the deadline passed to `inflate_dat_file_buffer_deadline`.
+const DEADLINE_CHECK_INTERVAL: u32 = 4096;
+
+/// Why a DAT or texture stream could not be decoded.
+///
+/// Returning `std::io::Error` with one of these inside is the intended public design: the
+/// decoding entry points in this crate also fail on file reads, short headers and
+/// unsupported formats, which have no variant here, so they keep a single `io::Result`.
+/// Callers match on a decoding failure with `DecompressError::from_io` instead of reading
+/// the message; it returns `None` for the other errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecompressError {
+    /// A read or drop of more bits than the 32-bit reader head holds.
+    InvalidBitRequest { bits: u8 },
+    /// A code, symbol or bit width in the stream that the Huffman trees cannot decode.
+    InvalidCode { context: &'static str, value: u32 },
+    /// The declared output size is larger than the caller's limit.
+    OutputTooLarge { declared: u32, max: u32 },
+    /// Decoding ran past its deadline.
+    Timeout { written: u32, expected: u32 },
+    /// A Huffman tree declares more symbols than it can hold.
+    TooManySymbols { count: u16 },
+    /// The input ended before the declared output size was reached.
+    UnexpectedEof { written: u32, expected: u32 },
 }
 
-#[derive(Debug)]
-struct HuffmanTree {
-    code_comparison: [u32; MAX_CODE_BITS_LENGTH],
-    symbol_value_offset: [u16; MAX_CODE_BITS_LENGTH],
-    code_bits: [u8; MAX_CODE_BITS_LENGTH],
-    symbol_value: [u16; MAX_SYMBOL_VALUE],
-    symbol_value_hash_exist: [bool; 1 << MAX_BITS_HASH],
-    symbol_value_hash: [u16; 1 << MAX_BITS_HASH],
-    code_bits_hash: [u8; 1 << MAX_BITS_HASH],
-}
+impl DecompressError {
+    /// The `DecompressError` carried by `err`, if a decoder produced it.
+    pub fn from_io(err: &std::io::Error) -> Option<DecompressError> {
+        err.get_ref()?.downcast_ref::<DecompressError>().copied()
+    }
 
-impl Default for HuffmanTree {
-    fn default() -> Self {
-        HuffmanTree {
-            code_comparison: [0; MAX_CODE_BITS_LENGTH],
-            symbol_value_offset: [0; MAX_CODE_BITS_LENGTH],
-            code_bits: [0; MAX_CODE_BITS_LENGTH],
-            symbol_value: [0; MAX_SYMBOL_VALUE],
-            symbol_value_hash_exist: [false; 1 << MAX_BITS_HASH],
-            symbol_value_hash: [0; 1 << MAX_BITS_HASH],
-            code_bits_hash: [0; 1 << MAX_BITS_HASH],
+    /// The `std::io::ErrorKind` this error is reported with.
+    pub fn kind(&self) -> std::io::ErrorKind {
+        match self {
+            DecompressError::InvalidBitRequest { .. }
+            | DecompressError::InvalidCode { .. }
+            | DecompressError::OutputTooLarge { .. }
+            | DecompressError::TooManySymbols { .. } => std::io::ErrorKind::InvalidData,
+            DecompressError::Timeout { .. } => std::io::ErrorKind::TimedOut,
+            DecompressError::UnexpectedEof { .. } => std::io::ErrorKind::UnexpectedEof,
         }
     }
 }
 
-#[derive(Debug)]
-struct HuffmanTreeBuilder {
-    bits_head_exist: [bool; MAX_CODE_BITS_LENGTH],
-    bits_head: [u16; MAX_CODE_BITS_LENGTH],
-    bits_body_exist: [bool; MAX_SYMBOL_VALUE],
-    bits_body: [u16; MAX_SYMBOL_VALUE],
-}
-
-impl Default for HuffmanTreeBuilder {
-    fn default() -> Self {
-        HuffmanTreeBuilder {
-            bits_head_exist: [false; MAX_CODE_BITS_LENGTH],
-            bits_head: [0; MAX_CODE_BITS_LENGTH],
-            bits_body_exist: [false; MAX_SYMBOL_VALUE],
-            bits_body: [0; MAX_SYMBOL_VALUE],
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::InvalidBitRequest { bits } => write!(
+                f,
+                "Invalid bit request: {} bits (maximum {}).",
+                bits, U32_IN_BITS
+            ),
+            DecompressError::InvalidCode { context, value } => {
+                write!(f, "Invalid {}: {}.", context, value)
+            }
+            DecompressError::OutputTooLarge { declared, max } => write!(
+                f,
+                "Declared output size {} exceeds the limit of {} bytes.",
+                declared, max
+            ),
+            DecompressError::Timeout { written, expected } => write!(
+                f,
+                "Decompression timed out after {} of {} output bytes.",
+                written, expected
+            ),
+            DecompressError::TooManySymbols { count } => write!(
+                f,
+                "Too many symbols to decode: {} (maximum {}).",
+                count, MAX_SYMBOL_VALUE
+            ),
+            DecompressError::UnexpectedEof { written, expected } => write!(
+                f,
+                "Compressed data ended after {} of {} output bytes.",
+                written, expected
+            ),
         }
     }
 }
 
-fn pull_byte(
-    state_data: &mut StateData,
-    head_data: &mut u32,
-    bytes_available_data: &mut u8,
-) -> std::io::Result<()> {
-    if state_data.bytes_available >= std::mem::size_of::<u32>() as u32 {
-        *head_data = state_data.input_buffer.read_u32::<LittleEndian>()?;
-        state_data.bytes_available -= std::mem::size_of::<u32>() as u32;
-        state_data.buffer_position_bytes = state_data.input_buffer.position();
-        *bytes_available_data = (std::mem::size_of::<u32>() as u32 * 8) as u8;
-    } else {
-        *head_data = 0;
-        *bytes_available_data = 0;
+impl std::error::Error for DecompressError {}
+
+impl From<DecompressError> for std::io::Error {
+    fn from(err: DecompressError) -> std::io::Error {
+        std::io::Error::new(err.kind(), err)
     }
-    Ok(())
 }
 
-fn read_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<u32> {
-    // Extract the available bits
-    let mut value = state_data.head_data >> (std::mem::size_of::<u32>() as u8 * 8 - bits_number);
+/// Counters collected by `inflate_dat_file_buffer_stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressStats {
+    /// Length of the compressed input in bytes.
+    pub input_len: u64,
+    /// Declared length of the decompressed output in bytes.
+    pub output_len: u64,
+    /// Number of symbol/copy Huffman tree pairs read from the stream.
+    pub huffman_tree_rebuilds: u32,
+    /// Number of back-references copied from earlier output.
+    pub copy_ops: u64,
+    /// Number of literal bytes written.
+    pub literal_ops: u64,
+}
 
-    if state_data.bytes_available_data < bits_number {
-        // If the number of bits is less than 32, pad with zeros
-        if bits_number < 32 {
-            let padding_bits = 32 - bits_number;
-            value <<= padding_bits; // Shift the value to the left, adding zeros
-        }
-    }
+pub fn inflate_dat_file_buffer(
+    input_data: Vec<u8>,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    inflate_dat_file_buffer_with_limit(&input_data, None, None, output_data_size, output_data)?;
+    Ok(())
+}
 
-    Ok(value)
+/// Like `inflate_dat_file_buffer`, but decode straight from borrowed bytes such as a memory-mapped archive.
+pub fn inflate_dat_file_slice(
+    input_data: &[u8],
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    inflate_dat_file_buffer_with_limit(input_data, None, None, output_data_size, output_data)?;
+    Ok(())
 }
 
-fn drop_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<()> {
-    if state_data.bytes_available_data < bits_number {
-        println!("Too much bits were asked to be dropped.");
-    }
-    #[allow(unused_assignments)]
-    let mut new_bits_available: u8 = 0;
-    new_bits_available = state_data.bytes_available_data.wrapping_sub(bits_number);
-    if new_bits_available >= std::mem::size_of::<u32>() as u8 * 8 {
-        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
-            state_data.head_data = state_data.buffer_data;
-            state_data.buffer_data = 0;
-        } else {
-            state_data.head_data = (state_data.head_data << bits_number)
-                | (state_data.buffer_data >> (std::mem::size_of::<u32>() as u8 * 8) - bits_number);
-            state_data.buffer_data = state_data.buffer_data << bits_number;
-        }
-        state_data.bytes_available_data = new_bits_available;
-    } else {
-        let mut new_value: u32 = 0;
-        let mut pulled_bits: u8 = 0;
-        pull_byte(state_data, &mut new_value, &mut pulled_bits)?;
+/// Like `inflate_dat_file_buffer`, but also return counters describing how the stream was decoded.
+pub fn inflate_dat_file_buffer_stats(
+    input_data: Vec<u8>,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<DecompressStats> {
+    inflate_dat_file_buffer_with_limit(&input_data, None, None, output_data_size, output_data)
+}
 
-        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
-            state_data.head_data = 0;
-        } else {
-            state_data.head_data = state_data.head_data << bits_number;
-        }
-        state_data.head_data |= (state_data.buffer_data
-            >> ((std::mem::size_of::<u32>() as u8 * 8) - bits_number))
-            | (new_value >> (new_bits_available));
-        if new_bits_available > 0 {
-            state_data.buffer_data =
-                new_value << (std::mem::size_of::<u32>() as u8 * 8) - new_bits_available;
-        }
-        state_data.bytes_available_data = new_bits_available + pulled_bits;
-    }
+/// Like `inflate_dat_file_buffer`, but fail before allocating if the declared output size exceeds `max_output` bytes.
+pub fn inflate_dat_file_buffer_bounded(
+    input_data: Vec<u8>,
+    max_output: u32,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    inflate_dat_file_buffer_with_limit(
+        &input_data,
+        Some(max_output),
+        None,
+        output_data_size,
+        output_data,
+    )?;
     Ok(())
 }
 
-fn read_code(
-    huffmantree_data: &mut HuffmanTree,
-    state_data: &mut StateData,
-    symbol_data: &mut u16,
+/// Like `inflate_dat_file_buffer`, but fail with `ErrorKind::TimedOut` once decoding runs past `deadline`.
+///
+/// The deadline is only checked every few thousand codes, so decoding may overrun it slightly.
+pub fn inflate_dat_file_buffer_deadline(
+    input_data: Vec<u8>,
+    deadline: Instant,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
 ) -> std::io::Result<()> {
-    let index_num = read_bits(state_data, U8_IN_BITS as u8)? as usize;
-
-    let exist = huffmantree_data.symbol_value_hash_exist[index_num];
+    inflate_dat_file_buffer_with_limit(
+        &input_data,
+        None,
+        Some(deadline),
+        output_data_size,
+        output_data,
+    )?;
+    Ok(())
+}
 
-    if exist {
-        *symbol_data =
-            huffmantree_data.symbol_value_hash[read_bits(state_data, U8_IN_BITS as u8)? as usize];
+fn inflate_dat_file_buffer_with_limit(
+    input_data: &[u8],
+    max_output: Option<u32>,
+    deadline: Option<Instant>,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<DecompressStats> {
+    Decompressor::new().inflate(
+        input_data,
+        max_output,
+        deadline,
+        output_data_size,
+        output_data,
+    )
+}
 
-        let code_bits_hash =
-            huffmantree_data.code_bits_hash[read_bits(state_data, U8_IN_BITS as u8)? as usize];
+/// Reusable decoding state for decompressing many DAT entries in a row.
+///
+/// Every decompressor reads the one shared static dictionary, and the Huffman trees and builder
+/// are overwritten by each call instead of being set up again.
+#[derive(Debug)]
+pub struct Decompressor {
+    huffmantree_symbol: HuffmanTree,
+    huffmantree_copy: HuffmanTree,
+    huffmantree_builder: HuffmanTreeBuilder,
+}
 
-        drop_bits(state_data, code_bits_hash)?;
-    } else {
-        let mut index_data: u16 = 0;
-        while read_bits(state_data, U32_IN_BITS)?
-            < huffmantree_data.code_comparison[index_data as usize]
-        {
-            index_data = index_data.wrapping_add(1);
+impl Decompressor {
+    pub fn new() -> Decompressor {
+        Decompressor {
+            huffmantree_symbol: HuffmanTree::default(),
+            huffmantree_copy: HuffmanTree::default(),
+            huffmantree_builder: HuffmanTreeBuilder::default(),
         }
+    }
 
-        let temp_bits: u8 = huffmantree_data.code_bits[index_data as usize];
-
-        // Step 1: Read 32 bits from state_data
-        let read_bits_value = read_bits(state_data, U32_IN_BITS)?;
-
-        // Step 2: Subtract code_comparison from read_bits_value (with wrapping)
-        let adjusted_bits = read_bits_value
-            .wrapping_sub(huffmantree_data.code_comparison[index_data as usize] as u32);
+    /// Decompress `input_data` into `output_data`, replacing its contents but keeping its allocation.
+    pub fn decompress_into(
+        &mut self,
+        input_data: &[u8],
+        output_data: &mut Vec<u8>,
+    ) -> std::io::Result<()> {
+        output_data.clear();
+        let mut output_data_size: u32 = 0;
+        self.inflate(input_data, None, None, &mut output_data_size, output_data)?;
+        Ok(())
+    }
 
-        // Step 3: Perform the right shift operation (with wrapping)
-        let shifted_bits = adjusted_bits.wrapping_shr((32 - temp_bits as u16) as u32);
+    fn inflate(
+        &mut self,
+        input_data: &[u8],
+        max_output: Option<u32>,
+        deadline: Option<Instant>,
+        output_data_size: &mut u32,
+        output_data: &mut Vec<u8>,
+    ) -> std::io::Result<DecompressStats> {
+        let mut decompress_stats = DecompressStats {
+            input_len: input_data.len() as u64,
+            ..Default::default()
+        };
+        let mut state_data = StateData::new(input_data)?;
+        *output_data_size = read_output_size(&mut state_data)?;
+
+        if let Some(max_output) = max_output
+            && *output_data_size > max_output
+        {
+            return Err(DecompressError::OutputTooLarge {
+                declared: *output_data_size,
+                max: max_output,
+            }
+            .into());
+        }
 
-        // Step 4: Subtract the shifted value from the symbol_value_offset (with wrapping)
-        let symbol_index = huffmantree_data.symbol_value_offset[index_data as usize]
-            .wrapping_sub(shifted_bits as u16) as usize;
+        output_data.resize(*output_data_size as usize, 0);
 
-        // Step 5: Retrieve the symbol_data using the calculated index
-        *symbol_data = huffmantree_data.symbol_value[symbol_index];
+        inflate_data(
+            &mut state_data,
+            output_data_size,
+            output_data,
+            deadline,
+            &mut decompress_stats,
+            self,
+        )?;
+        decompress_stats.output_len = *output_data_size as u64;
+        Ok(decompress_stats)
+    }
+}
 
-        drop_bits(state_data, temp_bits)?;
+impl Default for Decompressor {
+    fn default() -> Self {
+        Decompressor::new()
     }
-    Ok(())
 }
-pub fn inflate_dat_file_buffer(
+
+/// Inflate only the first `max_bytes` of output, for reading headers without decoding whole entries.
+///
+/// The output is shorter than `max_bytes` when the stream declares a smaller size.
+/// Decoding stops as soon as the prefix is complete, so the rest of the input is never read.
+pub fn inflate_dat_file_buffer_prefix(
     input_data: Vec<u8>,
-    output_data_size: &mut u32,
     output_data: &mut Vec<u8>,
+    max_bytes: u32,
 ) -> std::io::Result<()> {
-    let mut state_data = StateData::default();
-    state_data.bytes_available = input_data.len() as u32;
-    state_data.input_buffer = Cursor::new(input_data);
-    let mut head_data: u32 = 0;
-    let mut bytes_available_data: u8 = 0;
-
-    pull_byte(&mut state_data, &mut head_data, &mut bytes_available_data)?;
-
-    state_data.head_data = head_data;
-    state_data.bytes_available_data = bytes_available_data;
-
-    drop_bits(&mut state_data, 32)?;
-
-    *output_data_size = read_bits(&mut state_data, 32)?;
-
-    drop_bits(&mut state_data, 32)?;
-
-    output_data.resize(*output_data_size as usize, 0);
+    let mut state_data = StateData::new(&input_data)?;
+    let mut prefix_size = read_output_size(&mut state_data)?.min(max_bytes);
+
+    output_data.resize(prefix_size as usize, 0);
+
+    inflate_data(
+        &mut state_data,
+        &mut prefix_size,
+        output_data,
+        None,
+        &mut DecompressStats::default(),
+        &mut Decompressor::new(),
+    )
+}
 
-    inflate_data(&mut state_data, output_data_size, output_data)?;
-    Ok(())
+/// Skip the leading word of a compressed stream and read the declared decompressed size.
+fn read_output_size(state_data: &mut StateData) -> std::io::Result<u32> {
+    drop_bits(state_data, 32)?;
+    let output_data_size = read_bits(state_data, 32)?;
+    drop_bits(state_data, 32)?;
+    Ok(output_data_size)
 }
 
 fn inflate_data(
     state_data: &mut StateData,
     output_data_size: &mut u32,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
+    deadline: Option<Instant>,
+    decompress_stats: &mut DecompressStats,
+    decompressor: &mut Decompressor,
 ) -> std::io::Result<()> {
     let mut output_position: u32 = 0;
     #[allow(unused_assignments)]
     let mut write_size_const_addition: u16 = 0;
-    let mut max_size_count: u32 = 0;
     drop_bits(state_data, HALF_BYTE)?;
     write_size_const_addition = read_bits(state_data, HALF_BYTE)? as u16;
     write_size_const_addition += 1;
     drop_bits(state_data, HALF_BYTE)?;
 
-    let mut dat_file_huffmantree_dict = HuffmanTree::default();
-    let mut huffmantree_copy = HuffmanTree::default();
-    let mut huffmantree_symbol = HuffmanTree::default();
-    if !initialize_huffmantree_dict(&mut dat_file_huffmantree_dict)? {
-        println!("Failed to initialize huffmantree dict!");
-    }
-
-    let mut huffmantree_builder = HuffmanTreeBuilder::default();
+    let dat_file_huffmantree_dict = static_huffmantree_dict();
+    let Decompressor {
+        huffmantree_symbol,
+        huffmantree_copy,
+        huffmantree_builder,
+    } = decompressor;
 
     while output_position < *output_data_size {
         if !parse_huffmantree(
             state_data,
-            &mut huffmantree_symbol,
-            &mut dat_file_huffmantree_dict,
-            &mut huffmantree_builder,
+            huffmantree_symbol,
+            dat_file_huffmantree_dict,
+            huffmantree_builder,
         )? || !parse_huffmantree(
             state_data,
-            &mut huffmantree_copy,
-            &mut dat_file_huffmantree_dict,
-            &mut huffmantree_builder,
+            huffmantree_copy,
+            dat_file_huffmantree_dict,
+            huffmantree_builder,
         )? {
-            println!("Failed to parse huffmantree.");
+            warn!("Failed to parse huffmantree.");
             break;
         }
+        decompress_stats.huffman_tree_rebuilds += 1;
 
         #[allow(unused_assignments)]
         let mut max_count: u32 = 0;
         max_count = read_bits(state_data, HALF_BYTE)?;
         max_count = (max_count + 1) << 12;
-        max_size_count = max_size_count + 1;
         drop_bits(state_data, HALF_BYTE)?;
 
         let mut current_code_read_count: u32 = 0;
         while (current_code_read_count < max_count) && (output_position < *output_data_size) {
+            if is_input_exhausted(state_data) {
+                return Err(DecompressError::UnexpectedEof {
+                    written: output_position,
+                    expected: *output_data_size,
+                }
+                .into());
+            }
             current_code_read_count = current_code_read_count.wrapping_add(1);
+            if current_code_read_count.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(DecompressError::Timeout {
+                    written: output_position,
+                    expected: *output_data_size,
+                }
+                .into());
+            }
             let mut symbol_data = 0;
-            read_code(&mut huffmantree_symbol, state_data, &mut symbol_data)?;
+            read_code(huffmantree_symbol, state_data, &mut symbol_data)?;
 
             if symbol_data < 0x100 {
                 let index_num = output_position as usize;
 
                 output_data[index_num] = symbol_data as u8;
+                decompress_stats.literal_ops += 1;
 
                 output_position = output_position.wrapping_add(1);
                 continue;
             }
-            symbol_data = symbol_data.wrapping_sub(0x100);
-
-            let temp_code_div4_quot = symbol_data / 4;
-            let temp_code_div4_rem = symbol_data % 4;
-
-            let mut write_size: u32 = 0;
-
-            if temp_code_div4_quot == 0 {
-                write_size = symbol_data as u32
-            } else if temp_code_div4_quot < 7 {
-                write_size =
-                    (1 << (temp_code_div4_quot.wrapping_sub(1))) * (4 + temp_code_div4_rem) as u32
-            } else if symbol_data == 28 {
-                write_size = 0xFF
-            } else {
-                println!("Invalid value for write_size code.");
-            }
-
-            if temp_code_div4_quot > 1 && symbol_data != 28 {
-                let write_size_add_bits: u8 = temp_code_div4_quot.wrapping_sub(1) as u8;
-                #[allow(unused_assignments)]
-                let mut write_size_add: u32 = 0;
-                write_size_add = read_bits(state_data, write_size_add_bits)?;
-                write_size |= write_size_add;
-                drop_bits(state_data, write_size_add_bits)?;
-            }
-
-            write_size = write_size.wrapping_add(write_size_const_addition as u32);
-
-            read_code(&mut huffmantree_copy, state_data, &mut symbol_data)?;
-            let temp_code_div2_quot = symbol_data / 2;
-            let temp_code_div2_rem = symbol_data % 2;
-
-            let mut write_offset: u32 = 0;
-
-            if temp_code_div2_quot == 0 {
-                write_offset = symbol_data as u32
-            } else if temp_code_div2_quot < 17 {
-                write_offset =
-                    (1 << (temp_code_div2_quot.wrapping_sub(1))) * (2 + temp_code_div2_rem) as u32
-            } else {
-                println!("Invalid value for writeOffset code.");
-            }
-
-            if temp_code_div2_quot > 1 {
-                let write_offset_add_bits: u8 = temp_code_div2_quot.wrapping_sub(1) as u8;
-                #[allow(unused_assignments)]
-                let mut write_offset_add: u32 = 0;
-                write_offset_add = read_bits(state_data, write_offset_add_bits)?;
-                write_offset |= write_offset_add;
-                drop_bits(state_data, write_offset_add_bits)?;
-            }
-
-            write_offset = write_offset.wrapping_add(1);
+            let write_size = read_write_size(state_data, symbol_data, write_size_const_addition)?;
+            decompress_stats.copy_ops += 1;
+            let write_offset = read_write_offset(state_data, huffmantree_copy)?;
+            check_write_offset(write_offset, output_position)?;
 
             let mut already_written: u32 = 0;
             while (already_written < write_size) && (output_position < *output_data_size) {
@@ -334,179 +393,186 @@ fn inflate_data(
             }
         }
     }
-    Ok(())
-}
-
-fn initialize_huffmantree_dict(huffmantree_data: &mut HuffmanTree) -> std::io::Result<bool> {
-    let mut huffmantree_builder = HuffmanTreeBuilder::default();
 
-    let bits_data: [u8; 256] = [
-        3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7, 7, 7, 7, 7, 7, 7, 8, 8, 8, 8,
-        8, 8, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10,
-        10, 10, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 12, 12, 12, 13,
-        13, 13, 13, 13, 13, 14, 14, 14, 14, 15, 15, 15, 15, 15, 15, 15, 15, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-        16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
-    ];
-
-    let symbols_data: [u16; 256] = [
-        0x0A, 0x09, 0x08, 0x0C, 0x0B, 0x07, 0x00, 0xE0, 0x2A, 0x29, 0x06, 0x4A, 0x40, 0x2C, 0x2B,
-        0x28, 0x20, 0x05, 0x04, 0x49, 0x48, 0x27, 0x26, 0x25, 0x0D, 0x03, 0x6A, 0x69, 0x4C, 0x4B,
-        0x47, 0x24, 0xE8, 0xA0, 0x89, 0x88, 0x68, 0x67, 0x63, 0x60, 0x46, 0x23, 0xE9, 0xC9, 0xC0,
-        0xA9, 0xA8, 0x8A, 0x87, 0x80, 0x66, 0x65, 0x45, 0x44, 0x43, 0x2D, 0x02, 0x01, 0xE5, 0xC8,
-        0xAA, 0xA5, 0xA4, 0x8B, 0x85, 0x84, 0x6C, 0x6B, 0x64, 0x4D, 0x0E, 0xE7, 0xCA, 0xC7, 0xA7,
-        0xA6, 0x86, 0x83, 0xE6, 0xE4, 0xC4, 0x8C, 0x2E, 0x22, 0xEC, 0xC6, 0x6D, 0x4E, 0xEA, 0xCC,
-        0xAC, 0xAB, 0x8D, 0x11, 0x10, 0x0F, 0xFF, 0xFE, 0xFD, 0xFC, 0xFB, 0xFA, 0xF9, 0xF8, 0xF7,
-        0xF6, 0xF5, 0xF4, 0xF3, 0xF2, 0xF1, 0xF0, 0xEF, 0xEE, 0xED, 0xEB, 0xE3, 0xE2, 0xE1, 0xDF,
-        0xDE, 0xDD, 0xDC, 0xDB, 0xDA, 0xD9, 0xD8, 0xD7, 0xD6, 0xD5, 0xD4, 0xD3, 0xD2, 0xD1, 0xD0,
-        0xCF, 0xCE, 0xCD, 0xCB, 0xC5, 0xC3, 0xC2, 0xC1, 0xBF, 0xBE, 0xBD, 0xBC, 0xBB, 0xBA, 0xB9,
-        0xB8, 0xB7, 0xB6, 0xB5, 0xB4, 0xB3, 0xB2, 0xB1, 0xB0, 0xAF, 0xAE, 0xAD, 0xA3, 0xA2, 0xA1,
-        0x9F, 0x9E, 0x9D, 0x9C, 0x9B, 0x9A, 0x99, 0x98, 0x97, 0x96, 0x95, 0x94, 0x93, 0x92, 0x91,
-        0x90, 0x8F, 0x8E, 0x82, 0x81, 0x7F, 0x7E, 0x7D, 0x7C, 0x7B, 0x7A, 0x79, 0x78, 0x77, 0x76,
-        0x75, 0x74, 0x73, 0x72, 0x71, 0x70, 0x6F, 0x6E, 0x62, 0x61, 0x5F, 0x5E, 0x5D, 0x5C, 0x5B,
-        0x5A, 0x59, 0x58, 0x57, 0x56, 0x55, 0x54, 0x53, 0x52, 0x51, 0x50, 0x4F, 0x42, 0x41, 0x3F,
-        0x3E, 0x3D, 0x3C, 0x3B, 0x3A, 0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30,
-        0x2F, 0x21, 0x1F, 0x1E, 0x1D, 0x1C, 0x1B, 0x1A, 0x19, 0x18, 0x17, 0x16, 0x15, 0x14, 0x13,
-        0x12,
-    ];
-
-    for index in 0..256 {
-        add_symbol(
-            &mut huffmantree_builder,
-            symbols_data[index],
-            bits_data[index],
-        )?;
-    }
-
-    if !build_huffmantree(huffmantree_data, &mut huffmantree_builder)? {
-        return Ok(false);
-    } else {
-        Ok(true)
+    // The loop only ends early when no further Huffman trees could be read
+    if output_position < *output_data_size {
+        return Err(DecompressError::UnexpectedEof {
+            written: output_position,
+            expected: *output_data_size,
+        }
+        .into());
     }
+    Ok(())
 }
 
-fn add_symbol(
-    huffmantree_builder: &mut HuffmanTreeBuilder,
-    symbol_data: u16,
-    bit_data: u8,
-) -> std::io::Result<()> {
-    if huffmantree_builder.bits_head_exist[bit_data as usize] {
-        huffmantree_builder.bits_body[symbol_data as usize] =
-            huffmantree_builder.bits_head[bit_data as usize];
-
-        huffmantree_builder.bits_body_exist[symbol_data as usize] = true;
-
-        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
-    } else {
-        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
-
-        huffmantree_builder.bits_head_exist[bit_data as usize] = true;
+/// A copy must start inside the bytes already written, at least one byte back.
+fn check_write_offset(write_offset: u32, output_position: u32) -> std::io::Result<()> {
+    if write_offset == 0 || write_offset > output_position {
+        return Err(DecompressError::InvalidCode {
+            context: "write_offset",
+            value: write_offset,
+        }
+        .into());
     }
     Ok(())
 }
 
-fn check_bits_head(huffmantree_builder: &mut HuffmanTreeBuilder) -> std::io::Result<bool> {
-    for head in huffmantree_builder.bits_head_exist {
-        if head == true {
-            return Ok(false);
+/// Number of extra bits that follow a length or offset code, which must fit in [0, 31].
+fn extra_bits_width(code_quot: u16) -> std::io::Result<u8> {
+    let bits_width = code_quot.wrapping_sub(1);
+    if bits_width >= U32_IN_BITS as u16 {
+        return Err(DecompressError::InvalidCode {
+            context: "extra bit width",
+            value: bits_width as u32,
         }
+        .into());
     }
-
-    Ok(true)
+    Ok(bits_width as u8)
 }
 
-fn build_huffmantree(
-    huffmantree_data: &mut HuffmanTree,
-    huffmantree_builder: &mut HuffmanTreeBuilder,
-) -> std::io::Result<bool> {
-    if check_bits_head(huffmantree_builder)? {
-        return Ok(false);
-    }
-    *huffmantree_data = HuffmanTree::default();
-    let mut temp_code: u32 = 0;
-    let mut temp_bits: u8 = 0;
-
-    // First part, filling hashTable for codes that are of less than 8 bits
-    while temp_bits <= MAX_BITS_HASH as u8 {
-        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
-
-        if data_exist {
-            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
-
-            while data_exist {
-                // Processing hash values
-                let mut hash_value: u16 = (temp_code << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
-                let next_hash_value: u16 =
-                    ((temp_code.wrapping_add(1)) << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
-
-                while hash_value < next_hash_value {
-                    huffmantree_data.symbol_value_hash_exist[hash_value as usize] = true;
-                    huffmantree_data.symbol_value_hash[hash_value as usize] = current_symbol;
-                    huffmantree_data.code_bits_hash[hash_value as usize] = temp_bits;
-                    hash_value = hash_value.wrapping_add(1);
-                }
-
-                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
-                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
-                temp_code = temp_code.wrapping_sub(1);
-            }
+/// Turn a length symbol (0x100 and above) from the symbol tree into a copy length, reading any extra bits.
+fn read_write_size(
+    state_data: &mut StateData,
+    symbol_data: u16,
+    write_size_const_addition: u16,
+) -> std::io::Result<u32> {
+    let symbol_data = symbol_data.wrapping_sub(0x100);
+
+    let temp_code_div4_quot = symbol_data / 4;
+    let temp_code_div4_rem = symbol_data % 4;
+
+    let mut write_size: u32 = if temp_code_div4_quot == 0 {
+        symbol_data as u32
+    } else if temp_code_div4_quot < 7 {
+        (1 << (temp_code_div4_quot.wrapping_sub(1))) * (4 + temp_code_div4_rem) as u32
+    } else if symbol_data == 28 {
+        0xFF
+    } else {
+        return Err(DecompressError::InvalidCode {
+            context: "write_size code",
+            value: symbol_data as u32,
         }
+        .into());
+    };
 
-        temp_code = (temp_code << 1) + 1;
-        temp_bits = temp_bits.wrapping_add(1);
+    if temp_code_div4_quot > 1 && symbol_data != 28 {
+        let write_size_add_bits = extra_bits_width(temp_code_div4_quot)?;
+        #[allow(unused_assignments)]
+        let mut write_size_add: u32 = 0;
+        write_size_add = read_bits(state_data, write_size_add_bits)?;
+        write_size |= write_size_add;
+        drop_bits(state_data, write_size_add_bits)?;
     }
 
-    let mut temp_code_comparison_index: u16 = 0;
-    let mut symbol_offset: u16 = 0;
-
-    // Second part, filling classical structure for other codes
-    while temp_bits < MAX_CODE_BITS_LENGTH as u8 {
-        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
-
-        if data_exist {
-            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
-
-            while data_exist {
-                // Registering the code
-                huffmantree_data.symbol_value[symbol_offset as usize] = current_symbol;
-
-                symbol_offset = symbol_offset.wrapping_add(1);
-                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
-                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
+    Ok(write_size.wrapping_add(write_size_const_addition as u32))
+}
 
-                temp_code = temp_code.wrapping_sub(1);
-            }
+/// Read a symbol from the copy tree and turn it into a back-reference distance, reading any extra bits.
+fn read_write_offset(
+    state_data: &mut StateData,
+    huffmantree_copy: &mut HuffmanTree,
+) -> std::io::Result<u32> {
+    let mut symbol_data = 0;
+    read_code(huffmantree_copy, state_data, &mut symbol_data)?;
+    let temp_code_div2_quot = symbol_data / 2;
+    let temp_code_div2_rem = symbol_data % 2;
+
+    let mut write_offset: u32 = if temp_code_div2_quot == 0 {
+        symbol_data as u32
+    } else if temp_code_div2_quot < 17 {
+        (1 << (temp_code_div2_quot.wrapping_sub(1))) * (2 + temp_code_div2_rem) as u32
+    } else {
+        return Err(DecompressError::InvalidCode {
+            context: "write_offset code",
+            value: symbol_data as u32,
+        }
+        .into());
+    };
 
-            // Minimum code value for temp_bits bits
-            huffmantree_data.code_comparison[temp_code_comparison_index as usize] =
-                temp_code.wrapping_add(1) << (32 - temp_bits);
+    if temp_code_div2_quot > 1 {
+        let write_offset_add_bits = extra_bits_width(temp_code_div2_quot)?;
+        #[allow(unused_assignments)]
+        let mut write_offset_add: u32 = 0;
+        write_offset_add = read_bits(state_data, write_offset_add_bits)?;
+        write_offset |= write_offset_add;
+        drop_bits(state_data, write_offset_add_bits)?;
+    }
 
-            // Number of bits for l_codeCompIndex index
-            huffmantree_data.code_bits[temp_code_comparison_index as usize] = temp_bits;
+    Ok(write_offset.wrapping_add(1))
+}
 
-            // Offset in symbol_value table to reach the value
-            huffmantree_data.symbol_value_offset[temp_code_comparison_index as usize] =
-                symbol_offset.wrapping_sub(1);
+/// Code lengths of the static Huffman dictionary, paired with `STATIC_SYMBOLS_DATA`.
+const STATIC_BITS_DATA: [u8; 256] = [
+    3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7, 7, 7, 7, 7, 7, 7, 8, 8, 8, 8, 8, 8,
+    9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10,
+    11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 12, 12, 12, 13, 13, 13, 13,
+    13, 13, 14, 14, 14, 14, 15, 15, 15, 15, 15, 15, 15, 15, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 16,
+    16, 16, 16, 16, 16, 16,
+];
+
+/// Symbols of the static Huffman dictionary that decodes code lengths.
+const STATIC_SYMBOLS_DATA: [u16; 256] = [
+    0x0A, 0x09, 0x08, 0x0C, 0x0B, 0x07, 0x00, 0xE0, 0x2A, 0x29, 0x06, 0x4A, 0x40, 0x2C, 0x2B, 0x28,
+    0x20, 0x05, 0x04, 0x49, 0x48, 0x27, 0x26, 0x25, 0x0D, 0x03, 0x6A, 0x69, 0x4C, 0x4B, 0x47, 0x24,
+    0xE8, 0xA0, 0x89, 0x88, 0x68, 0x67, 0x63, 0x60, 0x46, 0x23, 0xE9, 0xC9, 0xC0, 0xA9, 0xA8, 0x8A,
+    0x87, 0x80, 0x66, 0x65, 0x45, 0x44, 0x43, 0x2D, 0x02, 0x01, 0xE5, 0xC8, 0xAA, 0xA5, 0xA4, 0x8B,
+    0x85, 0x84, 0x6C, 0x6B, 0x64, 0x4D, 0x0E, 0xE7, 0xCA, 0xC7, 0xA7, 0xA6, 0x86, 0x83, 0xE6, 0xE4,
+    0xC4, 0x8C, 0x2E, 0x22, 0xEC, 0xC6, 0x6D, 0x4E, 0xEA, 0xCC, 0xAC, 0xAB, 0x8D, 0x11, 0x10, 0x0F,
+    0xFF, 0xFE, 0xFD, 0xFC, 0xFB, 0xFA, 0xF9, 0xF8, 0xF7, 0xF6, 0xF5, 0xF4, 0xF3, 0xF2, 0xF1, 0xF0,
+    0xEF, 0xEE, 0xED, 0xEB, 0xE3, 0xE2, 0xE1, 0xDF, 0xDE, 0xDD, 0xDC, 0xDB, 0xDA, 0xD9, 0xD8, 0xD7,
+    0xD6, 0xD5, 0xD4, 0xD3, 0xD2, 0xD1, 0xD0, 0xCF, 0xCE, 0xCD, 0xCB, 0xC5, 0xC3, 0xC2, 0xC1, 0xBF,
+    0xBE, 0xBD, 0xBC, 0xBB, 0xBA, 0xB9, 0xB8, 0xB7, 0xB6, 0xB5, 0xB4, 0xB3, 0xB2, 0xB1, 0xB0, 0xAF,
+    0xAE, 0xAD, 0xA3, 0xA2, 0xA1, 0x9F, 0x9E, 0x9D, 0x9C, 0x9B, 0x9A, 0x99, 0x98, 0x97, 0x96, 0x95,
+    0x94, 0x93, 0x92, 0x91, 0x90, 0x8F, 0x8E, 0x82, 0x81, 0x7F, 0x7E, 0x7D, 0x7C, 0x7B, 0x7A, 0x79,
+    0x78, 0x77, 0x76, 0x75, 0x74, 0x73, 0x72, 0x71, 0x70, 0x6F, 0x6E, 0x62, 0x61, 0x5F, 0x5E, 0x5D,
+    0x5C, 0x5B, 0x5A, 0x59, 0x58, 0x57, 0x56, 0x55, 0x54, 0x53, 0x52, 0x51, 0x50, 0x4F, 0x42, 0x41,
+    0x3F, 0x3E, 0x3D, 0x3C, 0x3B, 0x3A, 0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30,
+    0x2F, 0x21, 0x1F, 0x1E, 0x1D, 0x1C, 0x1B, 0x1A, 0x19, 0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12,
+];
+
+/// The (code length, symbol) tables used to build the static Huffman dictionary.
+pub fn static_symbol_table() -> (&'static [u8; 256], &'static [u16; 256]) {
+    (&STATIC_BITS_DATA, &STATIC_SYMBOLS_DATA)
+}
 
-            temp_code_comparison_index = temp_code_comparison_index.wrapping_add(1);
+/// The dictionary used to read every stream's Huffman trees, built on first use.
+fn static_huffmantree_dict() -> &'static HuffmanTree {
+    static DAT_FILE_HUFFMANTREE_DICT: OnceLock<HuffmanTree> = OnceLock::new();
+    DAT_FILE_HUFFMANTREE_DICT.get_or_init(|| {
+        let mut dat_file_huffmantree_dict = HuffmanTree::default();
+        if !initialize_huffmantree_dict(&mut dat_file_huffmantree_dict)
+            .expect("Static Huffman dictionary is valid")
+        {
+            error!("Failed to initialize huffmantree dict!");
         }
+        dat_file_huffmantree_dict
+    })
+}
+
+fn initialize_huffmantree_dict(huffmantree_data: &mut HuffmanTree) -> std::io::Result<bool> {
+    let mut huffmantree_builder = HuffmanTreeBuilder::default();
 
-        temp_code = (temp_code << 1) + 1;
-        temp_bits = temp_bits.wrapping_add(1);
+    for index in 0..256 {
+        add_symbol(
+            &mut huffmantree_builder,
+            STATIC_SYMBOLS_DATA[index],
+            STATIC_BITS_DATA[index],
+        )?;
     }
 
-    Ok(true)
+    build_huffmantree(huffmantree_data, &mut huffmantree_builder)
 }
 
 fn parse_huffmantree(
     state_data: &mut StateData,
     huffmantree_data: &mut HuffmanTree,
-    dat_file_huffmantree_dict: &mut HuffmanTree,
+    dat_file_huffmantree_dict: &HuffmanTree,
     huffmantree_builder: &mut HuffmanTreeBuilder,
 ) -> std::io::Result<bool> {
     #[allow(unused_assignments)]
@@ -514,7 +580,10 @@ fn parse_huffmantree(
     symbol_number = read_bits(state_data, U16_IN_BITS)? as u16;
     drop_bits(state_data, U16_IN_BITS)?;
     if symbol_number > MAX_SYMBOL_VALUE as u16 {
-        println!("Too many symbols to decode.");
+        return Err(DecompressError::TooManySymbols {
+            count: symbol_number,
+        }
+        .into());
     }
     *huffmantree_builder = HuffmanTreeBuilder::default();
     let mut remaining_symbol: i16 = symbol_number.wrapping_sub(1) as i16;
@@ -539,5 +608,385 @@ fn parse_huffmantree(
             }
         }
     }
-    Ok(build_huffmantree(huffmantree_data, huffmantree_builder)?)
+    build_huffmantree(huffmantree_data, huffmantree_builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dat_builder::compress_entry_data;
+
+    fn inflate(input_data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        inflate_dat_file_buffer(input_data, &mut output_data_size, &mut output_data)?;
+        Ok(output_data)
+    }
+
+    #[test]
+    fn decompress_error_survives_conversion_to_io_error() {
+        let err: std::io::Error = DecompressError::TooManySymbols { count: 300 }.into();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::TooManySymbols { count: 300 })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Too many symbols to decode: 300 (maximum 285)."
+        );
+
+        let err: std::io::Error = DecompressError::Timeout {
+            written: 1,
+            expected: 2,
+        }
+        .into();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        let err = std::io::Error::new(std::io::ErrorKind::InvalidData, "not from a decoder");
+        assert_eq!(DecompressError::from_io(&err), None);
+    }
+
+    #[test]
+    fn inflate_terminates_at_end_of_stream() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 13) as u8).collect();
+        let input_data = compress_entry_data(&data);
+        assert_eq!(inflate(input_data.clone()).unwrap(), data);
+
+        // Declaring more output than the stream holds must stop at the end of the input
+        let mut input_data = input_data;
+        input_data[4..8].copy_from_slice(&(data.len() as u32 * 4).to_le_bytes());
+        let err = inflate(input_data).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn bounded_inflate_rejects_large_declared_size_before_allocating() {
+        let mut input_data = compress_entry_data(b"small");
+        input_data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        let err = inflate_dat_file_buffer_bounded(
+            input_data,
+            1024,
+            &mut output_data_size,
+            &mut output_data,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::OutputTooLarge {
+                declared: u32::MAX,
+                max: 1024,
+            })
+        );
+        assert_eq!(output_data.capacity(), 0);
+    }
+
+    #[test]
+    fn bounded_inflate_accepts_sizes_within_the_cap() {
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        inflate_dat_file_buffer_bounded(
+            compress_entry_data(b"small"),
+            5,
+            &mut output_data_size,
+            &mut output_data,
+        )
+        .unwrap();
+        assert_eq!(output_data, b"small");
+    }
+
+    /// Input whose first 32 bits, most significant first, are `bits`.
+    fn head_bytes(bits: u32) -> Vec<u8> {
+        bits.to_le_bytes().to_vec()
+    }
+
+    fn decode_static_symbol(head: u32) -> u16 {
+        let input_data = head_bytes(head);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        let mut symbol_data = 0;
+        read_code(static_huffmantree_dict(), &mut state_data, &mut symbol_data).unwrap();
+        symbol_data
+    }
+
+    #[test]
+    fn static_tree_decodes_known_codes() {
+        assert_eq!(decode_static_symbol(0b111 << 29), 0x08);
+        assert_eq!(decode_static_symbol(0b110 << 29), 0x09);
+        assert_eq!(decode_static_symbol(0b101 << 29), 0x0A);
+        assert_eq!(decode_static_symbol(0b1001 << 28), 0x00);
+        assert_eq!(decode_static_symbol(0b0110 << 28), 0x0C);
+    }
+
+    #[test]
+    fn static_tree_decodes_sixteen_bit_codes() {
+        assert_eq!(decode_static_symbol(0), 0xFF);
+        assert_eq!(decode_static_symbol(1 << 16), 0xFE);
+    }
+
+    #[test]
+    fn read_write_size_maps_length_symbols() {
+        let input_data = head_bytes(0);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        assert_eq!(read_write_size(&mut state_data, 0x103, 1).unwrap(), 4);
+        assert_eq!(read_write_size(&mut state_data, 0x107, 1).unwrap(), 8);
+        assert_eq!(read_write_size(&mut state_data, 0x11C, 1).unwrap(), 0x100);
+
+        // 0x108 is 8 plus one extra bit read from the stream
+        let input_data = head_bytes(1 << 31);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        assert_eq!(read_write_size(&mut state_data, 0x108, 1).unwrap(), 10);
+        assert_eq!(state_data.bytes_available_data, 31);
+
+        let err = read_write_size(&mut state_data, 0x11D, 1).unwrap_err();
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::InvalidCode {
+                context: "write_size code",
+                value: 29,
+            })
+        );
+    }
+
+    #[test]
+    fn read_write_offset_maps_copy_symbols() {
+        // One-bit copy tree: symbol 5 is `1`, symbol 4 is `0`
+        let mut builder = HuffmanTreeBuilder::default();
+        add_symbol(&mut builder, 4, 1).unwrap();
+        add_symbol(&mut builder, 5, 1).unwrap();
+        let mut huffmantree_copy = HuffmanTree::default();
+        assert!(build_huffmantree(&mut huffmantree_copy, &mut builder).unwrap());
+
+        // Symbol 4 is 4 plus one extra bit, then one is added
+        let input_data = head_bytes(0b01 << 30);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        assert_eq!(
+            read_write_offset(&mut state_data, &mut huffmantree_copy).unwrap(),
+            6
+        );
+
+        let input_data = head_bytes(0b10 << 30);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        assert_eq!(
+            read_write_offset(&mut state_data, &mut huffmantree_copy).unwrap(),
+            7
+        );
+        assert_eq!(state_data.bytes_available_data, 30);
+    }
+
+    /// A stream header declaring `output_size` bytes, followed by `words` of bit data.
+    fn stream_with_words(output_size: u32, words: &[u32]) -> Vec<u8> {
+        [0, output_size]
+            .iter()
+            .chain(words)
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn inflate_rejects_trees_with_too_many_symbols() {
+        // Four skipped bits, a zero write size addition, then a symbol count of 300
+        let input_data = stream_with_words(10, &[300 << 8, 0]);
+        let err = inflate(input_data).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::TooManySymbols { count: 300 })
+        );
+    }
+
+    #[test]
+    fn copy_symbols_with_oversized_widths_are_rejected() {
+        // Copy symbol 70 would need 34 extra bits
+        let mut builder = HuffmanTreeBuilder::default();
+        add_symbol(&mut builder, 70, 1).unwrap();
+        let mut huffmantree_copy = HuffmanTree::default();
+        assert!(build_huffmantree(&mut huffmantree_copy, &mut builder).unwrap());
+
+        let input_data = head_bytes(1 << 31);
+        let mut state_data = StateData::new(&input_data).unwrap();
+        let err = read_write_offset(&mut state_data, &mut huffmantree_copy).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::InvalidCode {
+                context: "write_offset code",
+                value: 70,
+            })
+        );
+
+        let err = extra_bits_width(33).unwrap_err();
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::InvalidCode {
+                context: "extra bit width",
+                value: 32,
+            })
+        );
+        assert_eq!(extra_bits_width(32).unwrap(), 31);
+    }
+
+    #[test]
+    fn copies_from_before_the_output_are_rejected() {
+        assert!(check_write_offset(1, 1).is_ok());
+        assert!(check_write_offset(5, 8).is_ok());
+        for (write_offset, output_position) in [(0, 4), (2, 1), (1, 0)] {
+            let err = check_write_offset(write_offset, output_position).unwrap_err();
+            assert_eq!(
+                DecompressError::from_io(&err),
+                Some(DecompressError::InvalidCode {
+                    context: "write_offset",
+                    value: write_offset,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn stats_count_literals_copies_and_trees() {
+        // The fixture encoder writes a one-byte copy for each repeated byte
+        let input_data = compress_entry_data(b"aabbbc");
+        let input_len = input_data.len() as u64;
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        let stats =
+            inflate_dat_file_buffer_stats(input_data, &mut output_data_size, &mut output_data)
+                .unwrap();
+
+        assert_eq!(output_data, b"aabbbc");
+        assert_eq!(
+            stats,
+            DecompressStats {
+                input_len,
+                output_len: 6,
+                huffman_tree_rebuilds: 1,
+                copy_ops: 3,
+                literal_ops: 3,
+            }
+        );
+        assert_eq!(stats.copy_ops + stats.literal_ops, stats.output_len);
+    }
+
+    #[test]
+    fn static_symbol_table_exposes_the_dictionary() {
+        let (bits_data, symbols_data) = static_symbol_table();
+        assert_eq!(bits_data.len(), 256);
+        assert_eq!(symbols_data.len(), 256);
+        assert_eq!((bits_data[0], symbols_data[0]), (3, 0x0A));
+        assert_eq!((bits_data[6], symbols_data[6]), (4, 0x00));
+        assert_eq!((bits_data[255], symbols_data[255]), (16, 0x12));
+
+        // Every symbol appears once
+        let mut symbols = symbols_data.to_vec();
+        symbols.sort_unstable();
+        assert_eq!(symbols, (0..256).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn prefix_matches_the_start_of_the_full_output() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 256) as u8).collect();
+        let input_data = compress_entry_data(&data);
+
+        for max_bytes in [0, 1, 4, 4097, 10_000, 20_000] {
+            let mut output_data = Vec::new();
+            inflate_dat_file_buffer_prefix(input_data.clone(), &mut output_data, max_bytes)
+                .unwrap();
+            let expected_len = data.len().min(max_bytes as usize);
+            assert_eq!(output_data, data[..expected_len], "max_bytes {}", max_bytes);
+        }
+    }
+
+    #[test]
+    fn slice_path_matches_owned_path() {
+        let data: Vec<u8> = (0..9_000u32).map(|i| (i / 3 % 256) as u8).collect();
+        let input_data = compress_entry_data(&data);
+
+        let mut slice_size = 0;
+        let mut slice_output = Vec::new();
+        inflate_dat_file_slice(&input_data, &mut slice_size, &mut slice_output).unwrap();
+
+        let mut owned_size = 0;
+        let mut owned_output = Vec::new();
+        inflate_dat_file_buffer(input_data, &mut owned_size, &mut owned_output).unwrap();
+
+        assert_eq!(slice_size, owned_size);
+        assert_eq!(slice_output, owned_output);
+        assert_eq!(slice_output, data);
+    }
+
+    #[test]
+    fn passed_deadline_times_out_large_entries() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        let err = inflate_dat_file_buffer_deadline(
+            compress_entry_data(&data),
+            Instant::now(),
+            &mut output_data_size,
+            &mut output_data,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        match DecompressError::from_io(&err) {
+            Some(DecompressError::Timeout { written, expected }) => {
+                assert!(written < expected);
+                assert_eq!(expected, data.len() as u32);
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decompressor_reuses_its_output_buffer() {
+        let entries: Vec<Vec<u8>> = (1..=4u32)
+            .map(|entry| (0..2000 * entry).map(|i| (i * entry % 256) as u8).collect())
+            .collect();
+        let mut decompressor = Decompressor::new();
+        let mut output_data = Vec::with_capacity(8000);
+        let output_pointer = output_data.as_ptr();
+
+        for _ in 0..3 {
+            for data in entries.iter().rev() {
+                decompressor
+                    .decompress_into(&compress_entry_data(data), &mut output_data)
+                    .unwrap();
+                assert_eq!(&output_data, data);
+                assert_eq!(output_data.as_ptr(), output_pointer);
+            }
+        }
+    }
+
+    #[test]
+    fn static_dictionary_is_built_once() {
+        let first = static_huffmantree_dict();
+        assert_eq!(inflate(compress_entry_data(b"twice")).unwrap(), b"twice");
+        assert_eq!(inflate(compress_entry_data(b"twice")).unwrap(), b"twice");
+        assert!(std::ptr::eq(first, static_huffmantree_dict()));
+    }
+
+    #[test]
+    fn truncated_stream_is_unexpected_eof() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 13) as u8).collect();
+        let input_data = compress_entry_data(&data);
+
+        // Cuts inside the literal codes; the last words of the stream may only hold padding
+        for len in [
+            input_data.len() / 4,
+            input_data.len() / 2,
+            input_data.len() * 3 / 4,
+        ] {
+            let err = inflate(input_data[..len].to_vec()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+            match DecompressError::from_io(&err) {
+                Some(DecompressError::UnexpectedEof { written, expected }) => {
+                    assert_eq!(expected, data.len() as u32);
+                    assert!(written < expected);
+                }
+                other => panic!("expected UnexpectedEof, got {:?}", other),
+            }
+        }
+    }
 }
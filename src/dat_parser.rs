@@ -1,27 +1,69 @@
 #![allow(dead_code)]
 use byteorder::{LittleEndian, ReadBytesExt};
+use log::warn;
+use memmap2::Mmap;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::dat_decompress;
+use crate::texture_decompress;
 
 /// The length of the DAT file identifier, typically "AN(" in ASCII.
-const DAT_MAGIC_NUMBER: usize = 3;
+pub(crate) const DAT_MAGIC_NUMBER: usize = 3;
 /// The length of the MFT file identifier, typically "Mft→" in ASCII.
-const MFT_MAGIC_NUMBER: usize = 4;
+pub(crate) const MFT_MAGIC_NUMBER: usize = 4;
+/// Expected DAT file identifier.
+pub(crate) const DAT_MAGIC: [u8; DAT_MAGIC_NUMBER] = *b"AN(";
+/// Expected MFT file identifier.
+pub(crate) const MFT_MAGIC: [u8; MFT_MAGIC_NUMBER] = *b"Mft\x1a";
+/// Size of one on-disk MFT row in bytes. The MFT header occupies the first row.
+pub(crate) const MFT_ENTRY_BYTES: u32 = 24;
+/// Size of one on-disk MFT row when `DatHeader::flag` selects 32-bit offsets; the header still takes 24 bytes.
+pub(crate) const MFT_ENTRY_BYTES_32: u32 = 20;
+/// `DatHeader::flag` bit marking MFT rows with 32-bit entry offsets.
+///
+/// Known archives store 0 here and use 64-bit offsets. The 32-bit layout has not been seen in a
+/// shipped archive, so a high bit is used to leave any low flag bits of real archives alone.
+pub(crate) const MFT_OFFSET_32_FLAG: u32 = 0x8000_0000;
 /// Index in the MFT data where the base ID and file ID are stored.
 const MFT_ENTRY_INDEX_NUM: usize = 1;
+/// Size of one on-disk index table row: a u32 file ID followed by a u32 base ID.
+///
+/// `MftIndexData` is not `#[repr(C)]`, so its in-memory size is not guaranteed to match.
+pub(crate) const MFT_INDEX_ENTRY_BYTES: u32 = 8;
 
-const CHUNK_SIZE: usize = 0x10000;
+/// `DatHeader::chunk_size` of known archives, which holds their block size rather than the CRC chunk stride.
+pub(crate) const DAT_BLOCK_SIZE: u32 = 512;
+/// Default size of the chunks that each end with a 4-byte CRC-32C in entry data.
+///
+/// Used for archives whose `DatHeader::chunk_size` is the 512-byte block size, which is every known archive.
+pub(crate) const CHUNK_SIZE: usize = 0x10000;
 
 pub enum ArchiveId {
     FileId,
     BaseId,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+impl ArchiveId {
+    /// A name for the entry requested by `number`: `file_<id>` for file IDs and `base_<id>` for base IDs.
+    pub fn entry_name(&self, number: usize) -> String {
+        match self {
+            ArchiveId::FileId => format!("file_{}", number),
+            ArchiveId::BaseId => format!("base_{}", number),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DatHeader {
     /// The version of the DAT file format. Usually set to 151.
     pub version: u8,
@@ -41,11 +83,14 @@ pub struct DatHeader {
     pub mft_offset: u64,
     /// Size of the MFT in bytes.
     pub mft_size: u32,
-    /// A flag field; its purpose is currently unclear but may indicate file properties or settings.
+    /// Layout flags. 0, the value in known archives, means MFT rows hold 64-bit offsets.
+    ///
+    /// `MFT_OFFSET_32_FLAG` selects 32-bit offsets instead; other bits are ignored.
     pub flag: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MftHeader {
     /// A 4-character ASCII identifier, typically "Mft→".
     pub identifier: [u8; MFT_MAGIC_NUMBER],
@@ -59,7 +104,75 @@ pub struct MftHeader {
     pub unknown_field_3: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+impl DatHeader {
+    /// The identifier as ASCII text, with non-printable bytes escaped as `\xNN`.
+    pub fn identifier_str(&self) -> Cow<'_, str> {
+        identifier_string(&self.identifier)
+    }
+
+    /// Candidate value for `crc`: the CRC-32C of the 16 header bytes stored before it.
+    ///
+    /// The algorithm is not documented, so this is the same CRC-32C used for entry chunks,
+    /// over version, identifier, header size, `unknown_field` and chunk size as stored on disk.
+    pub fn computed_crc(&self) -> u32 {
+        let mut crc_input = Vec::with_capacity(16);
+        crc_input.push(self.version);
+        crc_input.extend_from_slice(&self.identifier);
+        crc_input.extend_from_slice(&self.header_size.to_le_bytes());
+        crc_input.extend_from_slice(&self.unknown_field.to_le_bytes());
+        crc_input.extend_from_slice(&self.chunk_size.to_le_bytes());
+        crc32c(&crc_input)
+    }
+
+    /// Whether `flag` marks MFT rows as holding 32-bit entry offsets.
+    pub fn has_32_bit_offsets(&self) -> bool {
+        self.flag & MFT_OFFSET_32_FLAG != 0
+    }
+
+    /// Size of one MFT row after the header row, as selected by `flag`.
+    pub fn mft_entry_bytes(&self) -> u32 {
+        if self.has_32_bit_offsets() {
+            MFT_ENTRY_BYTES_32
+        } else {
+            MFT_ENTRY_BYTES
+        }
+    }
+}
+
+impl MftHeader {
+    /// The identifier as ASCII text, with non-printable bytes escaped as `\xNN`.
+    pub fn identifier_str(&self) -> Cow<'_, str> {
+        identifier_string(&self.identifier)
+    }
+}
+
+/// Render identifier bytes as text, borrowing them when they are all printable ASCII.
+fn identifier_string(identifier: &[u8]) -> Cow<'_, str> {
+    let is_printable = |byte: &u8| (0x20..=0x7E).contains(byte);
+    if identifier.iter().all(is_printable) {
+        // Printable ASCII is always valid UTF-8
+        return Cow::Borrowed(std::str::from_utf8(identifier).unwrap_or_default());
+    }
+
+    Cow::Owned(
+        identifier
+            .iter()
+            .map(|byte| {
+                if is_printable(byte) {
+                    (*byte as char).to_string()
+                } else {
+                    format!("\\x{:02X}", byte)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// A CRC-32C stored in entry data: its byte position in the raw entry and its value.
+pub type ChunkCrc = (u64, u32);
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MftData {
     /// The offset in the file where the data for this entry begins.
     pub offset: u64,
@@ -67,7 +180,7 @@ pub struct MftData {
     pub size: u32,
     /// Indicates compression status: 8 means the file is compressed.
     pub compression_flag: u16,
-    /// Flags related to the entry; exact meaning requires further analysis.
+    /// Flags related to the entry; see `MftData::flags` for the known bits.
     pub entry_flag: u16,
     /// A counter or version number; its exact role is unclear.
     pub counter: u32,
@@ -78,10 +191,65 @@ pub struct MftData {
     /// Skipped when parsing data first time, because it takes a long time
     pub uncompressed_size: u32,
     /// u64 for position crc_32c data begin, the other one is the data itself 4 of u8 data in u32
-    pub crc_32c_data: Vec<(u64, u32)>,
+    /// Filled in by `DatFile::extract_mft_data_with_crcs`.
+    pub crc_32c_data: Vec<ChunkCrc>,
+}
+
+impl MftData {
+    /// Interpret `entry_flag` as a set of `EntryFlags`.
+    pub fn flags(&self) -> EntryFlags {
+        EntryFlags::from_bits_retain(self.entry_flag)
+    }
+}
+
+/// The bits of `MftData::entry_flag`. Bits without a named constant are kept as-is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EntryFlags(u16);
+
+impl EntryFlags {
+    /// Assumed to mark a row that holds live data, with rows lacking it free or deleted.
+    /// This is unverified: it is inferred from the rows seen in real archives, not from
+    /// a format description, so don't drop rows on it alone without checking their data.
+    pub const IN_USE: EntryFlags = EntryFlags(0x1);
+
+    /// Wrap raw flag bits, including any unnamed ones.
+    pub const fn from_bits_retain(bits: u16) -> EntryFlags {
+        EntryFlags(bits)
+    }
+
+    /// The raw flag bits.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether every bit in `other` is set.
+    pub const fn contains(self, other: EntryFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether no bits are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether `IN_USE` is set. See that constant for why this is only a guess at
+    /// whether the row is allocated.
+    pub const fn is_in_use(self) -> bool {
+        self.contains(EntryFlags::IN_USE)
+    }
+}
+
+impl std::ops::BitOr for EntryFlags {
+    type Output = EntryFlags;
+
+    fn bitor(self, rhs: EntryFlags) -> EntryFlags {
+        EntryFlags(self.0 | rhs.0)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MftIndexData {
     /// A unique identifier for a specific self.dat_file. Multiple file IDs can reference the same base ID, indicating that these files are related or derived from the same source.
     pub file_id: u32,
@@ -89,44 +257,234 @@ pub struct MftIndexData {
     pub base_id: u32,
 }
 
-#[derive(Debug)]
+/// Location details of an MFT entry, as resolved from a file ID or base ID.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MftEntryInfo {
+    /// The index of the entry in `mft_data`.
+    pub index: usize,
+    /// The offset in the file where the data for this entry begins.
+    pub offset: u64,
+    /// The size of the data for this entry in bytes.
+    pub size: u32,
+    /// Indicates compression status: 8 means the file is compressed.
+    pub compression_flag: u16,
+}
+
+/// An entry's data as returned by `DatFile::extract_entry`.
+#[derive(Debug, Default)]
+pub struct ExtractedEntry {
+    /// The entry bytes as stored in the archive, including any chunk CRCs.
+    pub raw_data: Vec<u8>,
+    /// The decompressed data, or a copy of `raw_data` when the entry is stored uncompressed.
+    pub decompressed_data: Vec<u8>,
+    /// Whether the entry was compressed and `decompressed_data` came from inflating it.
+    pub was_compressed: bool,
+    /// Name built from the requested ID by `ArchiveId::entry_name`, without an extension.
+    pub name: String,
+}
+
+/// Output format for `DatFile::export_manifest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Csv,
+    /// Needs the `serde` feature.
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// One row of the manifest written by `DatFile::export_manifest`.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestRow {
+    /// The index of the entry in `mft_data`.
+    pub index: usize,
+    /// The offset in the file where the data for this entry begins.
+    pub offset: u64,
+    /// The size of the data for this entry in bytes.
+    pub size: u32,
+    /// Indicates compression status: 8 means the file is compressed.
+    pub compression_flag: u16,
+    /// Flags related to the entry; exact meaning requires further analysis.
+    pub entry_flag: u16,
+    /// A counter or version number; its exact role is unclear.
+    pub counter: u32,
+    /// CRC (Cyclic Redundancy Check) for verifying the integrity of this entry.
+    pub crc: u32,
+    /// The base ID of the entry, if the index table references it.
+    pub base_id: Option<u32>,
+    /// Every file ID that maps to this entry, in index table order.
+    pub file_ids: Vec<u32>,
+}
+
+/// Parsed archive metadata written by `DatFile::save_index`.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct SavedIndex<'a> {
+    filename: &'a str,
+    file_size: u64,
+    dat_header: &'a DatHeader,
+    mft_header: &'a MftHeader,
+    mft_data: &'a [MftData],
+    mft_index_data: &'a [MftIndexData],
+}
+
+/// Parsed archive metadata read back by `DatFile::load_index`.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct LoadedIndex {
+    filename: String,
+    file_size: u64,
+    dat_header: DatHeader,
+    mft_header: MftHeader,
+    mft_data: Vec<MftData>,
+    mft_index_data: Vec<MftIndexData>,
+}
+
+/// Options for `DatFile::load_with_options` and `DatFile::load_mmap_with_options`.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    /// Reject paths that do not end in `.dat`. The header magic is always validated.
+    pub check_extension: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            check_extension: true,
+        }
+    }
+}
+
+/// A seekable byte source that a `DatFile` can be parsed from.
+pub trait DatReader: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> DatReader for T {}
+
 pub struct DatFile {
+    /// Path the archive was loaded from; empty when loaded from a reader.
     pub filename: String,
     pub file_size: u64,
     pub dat_header: DatHeader,
     pub mft_header: MftHeader,
     pub mft_data: Vec<MftData>,
     pub mft_index_data: Vec<MftIndexData>,
-    pub dat_file: BufReader<File>,
+    pub dat_file: Box<dyn DatReader>,
+    /// Longest time decompressing a single entry may take before failing with `ErrorKind::TimedOut`.
+    ///
+    /// `None`, the default, lets decompression run to completion.
+    pub extraction_timeout: Option<Duration>,
+    /// Size of the chunks that each end with a 4-byte CRC-32C in compressed entry data; must be larger than 4.
+    ///
+    /// Initialized from `DatHeader::chunk_size` by `crc_chunk_size_for_header`: known archives store their
+    /// 512-byte block size there and get `CHUNK_SIZE` (0x10000), while any other value is used as the stride.
+    pub crc_chunk_size: usize,
+    /// Last modification time of the archive file, read when it was loaded from a path.
+    pub modified: Option<SystemTime>,
+    /// The whole archive when loaded with `load_mmap`; entries are then sliced out of it instead of read.
+    mapped_data: Option<Arc<Mmap>>,
+}
+
+/// A shared memory map that can back a `Cursor`.
+struct SharedMmap(Arc<Mmap>);
+
+impl AsRef<[u8]> for SharedMmap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for DatFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatFile")
+            .field("filename", &self.filename)
+            .field("file_size", &self.file_size)
+            .field("dat_header", &self.dat_header)
+            .field("mft_header", &self.mft_header)
+            .field("mft_data", &self.mft_data)
+            .field("mft_index_data", &self.mft_index_data)
+            .field("extraction_timeout", &self.extraction_timeout)
+            .field("crc_chunk_size", &self.crc_chunk_size)
+            .field("modified", &self.modified)
+            .finish_non_exhaustive()
+    }
 }
 
 impl DatFile {
     /// Load a `.dat` file and parse its contents into a `DatFile` structure.
     pub fn load<P: AsRef<Path>>(file_path: P) -> std::io::Result<DatFile> {
-        // Check if the file extension is '.dat'
-        let file_path_str = file_path.as_ref().to_str().unwrap_or_default().to_string();
-        if !file_path_str.to_lowercase().ends_with(".dat") {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid file extension. Expected '.dat'.",
-            ));
-        }
+        DatFile::load_with_options(file_path, LoadOptions::default())
+    }
+
+    /// Load a DAT file like `load`, with the checks controlled by `options`.
+    pub fn load_with_options<P: AsRef<Path>>(
+        file_path: P,
+        options: LoadOptions,
+    ) -> std::io::Result<DatFile> {
+        let file_path_str = checked_path(file_path.as_ref(), &options)?;
 
         // Open the file and create a buffered reader.
         let file = File::open(file_path)?;
-        let mut dat_file = BufReader::new(file);
-        let _ = dat_file.seek(SeekFrom::End(0));
-        let position = dat_file.stream_position().unwrap();
-        let _ = dat_file.seek(SeekFrom::Start(0));
+        let metadata = file.metadata()?;
+        let mut data_dat_file = DatFile::load_from_reader(BufReader::new(file), metadata.len())?;
+        data_dat_file.filename = file_path_str;
+        data_dat_file.modified = metadata.modified().ok();
+
+        Ok(data_dat_file)
+    }
+
+    /// Load a `.dat` file like `load`, but memory-map it so entries are read by slicing the mapping.
+    ///
+    /// The archive must not be modified by other processes while it is mapped.
+    pub fn load_mmap<P: AsRef<Path>>(file_path: P) -> std::io::Result<DatFile> {
+        DatFile::load_mmap_with_options(file_path, LoadOptions::default())
+    }
+
+    /// Memory-map a DAT file like `load_mmap`, with the checks controlled by `options`.
+    pub fn load_mmap_with_options<P: AsRef<Path>>(
+        file_path: P,
+        options: LoadOptions,
+    ) -> std::io::Result<DatFile> {
+        let file_path_str = checked_path(file_path.as_ref(), &options)?;
+
+        let file = File::open(file_path)?;
+        // SAFETY: the mapping is read-only, and the archive is documented as not being modified while mapped.
+        let mapped_data = Arc::new(unsafe { Mmap::map(&file)? });
+        let file_size = mapped_data.len() as u64;
+
+        let mut data_dat_file = DatFile::load_from_reader(
+            Cursor::new(SharedMmap(Arc::clone(&mapped_data))),
+            file_size,
+        )?;
+        data_dat_file.filename = file_path_str;
+        data_dat_file.modified = file.metadata()?.modified().ok();
+        data_dat_file.mapped_data = Some(mapped_data);
+
+        Ok(data_dat_file)
+    }
+
+    /// Parse a DAT archive from any seekable reader, such as an in-memory `Cursor`.
+    ///
+    /// `len` is the total size of the archive in bytes. Unlike `load`, no file
+    /// extension check is performed.
+    pub fn load_from_reader<R: Read + Seek + Send + Sync + 'static>(
+        mut reader: R,
+        len: u64,
+    ) -> std::io::Result<DatFile> {
+        reader.seek(SeekFrom::Start(0))?;
         // Initialize the DatFile structure with default values.
         let mut data_dat_file = DatFile {
-            filename: file_path_str,
-            file_size: position,
+            filename: String::new(),
+            file_size: len,
             dat_header: Default::default(),
             mft_header: Default::default(),
             mft_data: Default::default(),
             mft_index_data: Default::default(),
-            dat_file,
+            dat_file: Box::new(reader),
+            extraction_timeout: None,
+            crc_chunk_size: CHUNK_SIZE,
+            modified: None,
+            mapped_data: None,
         };
 
         // Read and parse the headers and data.
@@ -138,13 +496,126 @@ impl DatFile {
         Ok(data_dat_file)
     }
 
+    /// Collect the `mft_data` indices of up to `max` ATEX/ATEC textures, in MFT order.
+    ///
+    /// Only the start of each entry is read and inflated, so this is much cheaper than extracting everything.
+    /// Entries that fail to read are skipped.
+    pub fn find_textures(&mut self, max: usize) -> Vec<usize> {
+        let mut texture_indices = Vec::new();
+        for index in 0..self.mft_data.len() {
+            if texture_indices.len() >= max {
+                break;
+            }
+            if self.mft_data[index].size == 0 {
+                continue;
+            }
+            let Ok(prefix) = self.read_entry_prefix(index, 4) else {
+                continue;
+            };
+            if prefix == *b"ATEX" || prefix == *b"ATEC" {
+                texture_indices.push(index);
+            }
+        }
+        texture_indices
+    }
+
+    /// Count the texture formats among the first `max_scan` MFT entries, by fourcc name.
+    ///
+    /// Only each texture's header is read and inflated, like `find_textures`. Fourccs without a
+    /// known name are counted under their escaped bytes. Entries that fail to read are skipped.
+    pub fn texture_format_histogram(&mut self, max_scan: usize) -> BTreeMap<String, usize> {
+        let mut histogram = BTreeMap::new();
+        for index in 0..self.mft_data.len().min(max_scan) {
+            if self.mft_data[index].size == 0 {
+                continue;
+            }
+            let Ok(header) =
+                self.read_entry_prefix(index, texture_decompress::TEXTURE_HEADER_SIZE as u32)
+            else {
+                continue;
+            };
+            let format_name = match texture_decompress::probe_dimensions(&header) {
+                Ok((_, _, format_name)) => format_name.to_string(),
+                Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                    identifier_string(&header[4..8]).into_owned()
+                }
+                Err(_) => continue,
+            };
+            *histogram.entry(format_name).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Save the parsed headers, MFT and index table to `path` as JSON, so `load_index` can skip parsing.
+    #[cfg(feature = "serde")]
+    pub fn save_index<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let saved_index = SavedIndex {
+            filename: &self.filename,
+            file_size: self.file_size,
+            dat_header: &self.dat_header,
+            mft_header: &self.mft_header,
+            mft_data: &self.mft_data,
+            mft_index_data: &self.mft_index_data,
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &saved_index)?;
+        writer.flush()
+    }
+
+    /// Restore a `DatFile` saved with `save_index`, reopening the archive at its recorded filename.
+    ///
+    /// Fails if the archive's size no longer matches the saved index.
+    #[cfg(feature = "serde")]
+    pub fn load_index<P: AsRef<Path>>(path: P) -> std::io::Result<DatFile> {
+        let reader = BufReader::new(File::open(path)?);
+        let loaded_index: LoadedIndex = serde_json::from_reader(reader)?;
+
+        let dat_file = reopen_reader(&loaded_index.filename)?;
+        let metadata = dat_file.get_ref().metadata()?;
+        let file_size = metadata.len();
+        if file_size != loaded_index.file_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Saved index is stale: {} is {} bytes, expected {}.",
+                    loaded_index.filename, file_size, loaded_index.file_size
+                ),
+            ));
+        }
+
+        let crc_chunk_size = crc_chunk_size_for_header(loaded_index.dat_header.chunk_size);
+        Ok(DatFile {
+            filename: loaded_index.filename,
+            file_size,
+            dat_header: loaded_index.dat_header,
+            mft_header: loaded_index.mft_header,
+            mft_data: loaded_index.mft_data,
+            mft_index_data: loaded_index.mft_index_data,
+            dat_file: Box::new(dat_file),
+            extraction_timeout: None,
+            crc_chunk_size,
+            modified: metadata.modified().ok(),
+            mapped_data: None,
+        })
+    }
+
     /// Read and parse the DAT file header.
     fn read_dat_header(&mut self) -> std::io::Result<()> {
         self.dat_header.version = self.dat_file.read_u8()?;
         self.dat_file.read_exact(&mut self.dat_header.identifier)?;
+        if self.dat_header.identifier != DAT_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid DAT identifier. Expected {:02X?}, found {:02X?}.",
+                    DAT_MAGIC, self.dat_header.identifier
+                ),
+            ));
+        }
         self.dat_header.header_size = self.dat_file.read_u32::<LittleEndian>()?;
         self.dat_header.unknown_field = self.dat_file.read_u32::<LittleEndian>()?;
         self.dat_header.chunk_size = self.dat_file.read_u32::<LittleEndian>()?;
+        self.crc_chunk_size = crc_chunk_size_for_header(self.dat_header.chunk_size);
         self.dat_header.crc = self.dat_file.read_u32::<LittleEndian>()?;
         self.dat_header.unknown_field_2 = self.dat_file.read_u32::<LittleEndian>()?;
         self.dat_header.mft_offset = self.dat_file.read_u64::<LittleEndian>()?;
@@ -155,26 +626,78 @@ impl DatFile {
 
     /// Read and parse the MFT file header.
     fn read_mft_header(&mut self) -> std::io::Result<()> {
+        if self
+            .dat_header
+            .mft_offset
+            .checked_add(self.dat_header.mft_size as u64)
+            .is_none_or(|mft_end| mft_end > self.file_size)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "MFT at offset {} with size {} exceeds file size {}.",
+                    self.dat_header.mft_offset, self.dat_header.mft_size, self.file_size
+                ),
+            ));
+        }
+
         self.dat_file
             .seek(SeekFrom::Start(self.dat_header.mft_offset))?;
         self.dat_file.read_exact(&mut self.mft_header.identifier)?;
+        if self.mft_header.identifier != MFT_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid MFT identifier. Expected {:02X?}, found {:02X?}.",
+                    MFT_MAGIC, self.mft_header.identifier
+                ),
+            ));
+        }
         self.mft_header.unknown_field = self.dat_file.read_u64::<LittleEndian>()?;
         self.mft_header.mft_entry_size = self.dat_file.read_u32::<LittleEndian>()?;
         self.mft_header.unknown_field_2 = self.dat_file.read_u32::<LittleEndian>()?;
         self.mft_header.unknown_field_3 = self.dat_file.read_u32::<LittleEndian>()?;
+
+        // The entry count includes the header row, so it can never exceed what fits in mft_size
+        let max_entry_count = match self.dat_header.mft_size.checked_sub(MFT_ENTRY_BYTES) {
+            Some(row_bytes) => row_bytes / self.dat_header.mft_entry_bytes() + 1,
+            None => 0,
+        };
+        if self.mft_header.mft_entry_size == 0 || self.mft_header.mft_entry_size > max_entry_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid MFT entry count {}. MFT size {} allows at most {} entries.",
+                    self.mft_header.mft_entry_size, self.dat_header.mft_size, max_entry_count
+                ),
+            ));
+        }
         self.mft_header.mft_entry_size -= 1; // Adjust size based on data format
         Ok(())
     }
 
     /// Read and parse the MFT data entries.
     fn read_mft_data(&mut self) -> std::io::Result<()> {
-        for _ in 0..self.mft_header.mft_entry_size {
-            let offset = self.dat_file.read_u64::<LittleEndian>()?;
-            let size = self.dat_file.read_u32::<LittleEndian>()?;
-            let compression_flag = self.dat_file.read_u16::<LittleEndian>()?;
-            let entry_flag = self.dat_file.read_u16::<LittleEndian>()?;
-            let counter = self.dat_file.read_u32::<LittleEndian>()?;
-            let crc = self.dat_file.read_u32::<LittleEndian>()?;
+        // The rows are fixed-size records, so read them all at once and parse from memory.
+        let mft_entry_bytes = self.dat_header.mft_entry_bytes() as usize;
+        let mut mft_rows = vec![0; self.mft_header.mft_entry_size as usize * mft_entry_bytes];
+        self.dat_file.read_exact(&mut mft_rows)?;
+
+        self.mft_data
+            .reserve(self.mft_header.mft_entry_size as usize);
+        for mft_row in mft_rows.chunks_exact(mft_entry_bytes) {
+            let mut mft_row = Cursor::new(mft_row);
+            // Reading the wrong width would shift every later field and row
+            let offset = if self.dat_header.has_32_bit_offsets() {
+                mft_row.read_u32::<LittleEndian>()? as u64
+            } else {
+                mft_row.read_u64::<LittleEndian>()?
+            };
+            let size = mft_row.read_u32::<LittleEndian>()?;
+            let compression_flag = mft_row.read_u16::<LittleEndian>()?;
+            let entry_flag = mft_row.read_u16::<LittleEndian>()?;
+            let counter = mft_row.read_u32::<LittleEndian>()?;
+            let crc = mft_row.read_u32::<LittleEndian>()?;
             self.mft_data.push(MftData {
                 offset,
                 size,
@@ -191,13 +714,30 @@ impl DatFile {
 
     /// Read and parse the MFT index data.
     fn read_mft_index_data(&mut self) -> std::io::Result<()> {
-        let num_index_entries = self.mft_data.get(MFT_ENTRY_INDEX_NUM).map_or(0, |entry| {
-            entry.size / std::mem::size_of::<MftIndexData>() as u32
-        });
-        let mft_index_data_offset = self
-            .mft_data
-            .get(MFT_ENTRY_INDEX_NUM)
-            .map_or(0, |entry| entry.offset);
+        let index_entry = self.mft_data.get(MFT_ENTRY_INDEX_NUM).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "MFT has {} entries and no index table; file ID and base ID lookups are unavailable.",
+                    self.mft_data.len()
+                ),
+            )
+        })?;
+        if index_entry
+            .offset
+            .checked_add(index_entry.size as u64)
+            .is_none_or(|index_end| index_end > self.file_size)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Index table at offset {} with size {} exceeds file size {}.",
+                    index_entry.offset, index_entry.size, self.file_size
+                ),
+            ));
+        }
+        let num_index_entries = index_entry.size / MFT_INDEX_ENTRY_BYTES;
+        let mft_index_data_offset = index_entry.offset;
 
         self.dat_file.seek(SeekFrom::Start(mft_index_data_offset))?;
 
@@ -209,131 +749,2072 @@ impl DatFile {
         Ok(())
     }
 
-    pub fn extract_mft_data(
-        &mut self,
+    /// Whether the stored header CRC matches `DatHeader::computed_crc`.
+    ///
+    /// The header CRC algorithm is a best guess, so a mismatch on a real archive is not proof of corruption;
+    /// compare `dat_header.crc` with `dat_header.computed_crc()` to investigate.
+    pub fn verify_header_crc(&self) -> bool {
+        self.dat_header.crc == self.dat_header.computed_crc()
+    }
+
+    /// Look up where an entry lives in the archive, resolving `number` the same way as `extract_mft_data`.
+    pub fn entry_info(
+        &self,
         archive_id: ArchiveId,
         number: usize,
-    ) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
-        let mut index_found: Option<usize> = None;
-
-        match archive_id {
-            ArchiveId::FileId => {
-                for i in 0..self.mft_index_data.len() {
-                    if self.mft_index_data.get(i).unwrap().file_id as usize == number {
-                        index_found =
-                            Some(self.mft_index_data.get(i).unwrap().base_id as usize - 1);
-                        break;
-                    }
-                }
-            }
-            ArchiveId::BaseId => {
-                for i in 0..self.mft_index_data.len() {
-                    if self.mft_index_data.get(i).unwrap().base_id as usize == number {
-                        index_found =
-                            Some(self.mft_index_data.get(i).unwrap().base_id as usize - 1);
-                        break;
-                    }
-                }
+    ) -> std::io::Result<MftEntryInfo> {
+        let index_found = self.find_mft_index(archive_id, number)?;
+        let mft_entry = mft_entry_at(&self.mft_data, index_found)?;
+
+        Ok(MftEntryInfo {
+            index: index_found,
+            offset: mft_entry.offset,
+            size: mft_entry.size,
+            compression_flag: mft_entry.compression_flag,
+        })
+    }
+
+    /// Resolve a file ID or base ID to its row in `mft_data`.
+    ///
+    /// Returns `None` when the ID is not in the index table.
+    pub fn resolve_index(&self, archive_id: ArchiveId, number: usize) -> Option<usize> {
+        let index_data = self.find_index_row(archive_id, number)?;
+        self.base_id_row(index_data.base_id)
+    }
+
+    /// Find the first index table row whose file ID or base ID is `number`.
+    fn find_index_row(&self, archive_id: ArchiveId, number: usize) -> Option<&MftIndexData> {
+        self.mft_index_data
+            .iter()
+            .find(|index_data| match archive_id {
+                ArchiveId::FileId => index_data.file_id as usize == number,
+                ArchiveId::BaseId => index_data.base_id as usize == number,
+            })
+    }
+
+    /// Turn a 1-based base ID into its row in `mft_data`, or `None` when it is 0 or past the end.
+    fn base_id_row(&self, base_id: u32) -> Option<usize> {
+        (base_id as usize)
+            .checked_sub(1)
+            .filter(|&index| index < self.mft_data.len())
+    }
+
+    /// Iterate over the MFT entries together with their index in `mft_data`.
+    pub fn iter_entries(&self) -> impl Iterator<Item = (usize, &MftData)> {
+        self.mft_data.iter().enumerate()
+    }
+
+    /// Count how many MFT entries use each `compression_flag` value.
+    pub fn compression_flag_histogram(&self) -> BTreeMap<u16, usize> {
+        let mut histogram = BTreeMap::new();
+        for mft_entry in &self.mft_data {
+            *histogram.entry(mft_entry.compression_flag).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Group the `mft_data` indices of entries that share both `crc` and `size`, a cheap proxy for identical content.
+    ///
+    /// Nothing is read or decompressed. Only groups of two or more are returned, ordered by their first index;
+    /// empty entries are left out.
+    pub fn duplicate_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+        for (index, mft_entry) in self.iter_entries() {
+            if mft_entry.size == 0 {
+                continue;
             }
+            groups
+                .entry((mft_entry.crc, mft_entry.size))
+                .or_default()
+                .push(index);
         }
 
-        // Check if index_found is set
-        let index_found = match index_found {
-            Some(index) => index,
-            None => {
+        let mut duplicate_groups: Vec<Vec<usize>> = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        duplicate_groups.sort_by_key(|group| group[0]);
+        duplicate_groups
+    }
+
+    /// Collect every file ID that points at `base_id`, in index table order.
+    pub fn file_ids_for_base(&self, base_id: u32) -> Vec<u32> {
+        self.mft_index_data
+            .iter()
+            .filter(|index_data| index_data.base_id == base_id)
+            .map(|index_data| index_data.file_id)
+            .collect()
+    }
+
+    /// Extract an entry and fingerprint it as (decompressed length, CRC-32C of the decompressed bytes).
+    pub fn extract_and_hash(
+        &mut self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<(u64, u32)> {
+        let (_, decompressed_data) = self.extract_mft_data(archive_id, number)?;
+        Ok((decompressed_data.len() as u64, crc32c(&decompressed_data)))
+    }
+
+    /// Read the decompressed size of `mft_data[index]` from the start of its compressed stream, without decoding it.
+    ///
+    /// Uncompressed entries report their stored size. The result is cached in `MftData::uncompressed_size`.
+    pub fn uncompressed_size(&mut self, index: usize) -> std::io::Result<u32> {
+        let mft_entry = mft_entry_at(&self.mft_data, index)?;
+
+        let uncompressed_size = if mft_entry.compression_flag == 0 {
+            mft_entry.size
+        } else {
+            // The stream starts with 4 unused bytes followed by the decompressed size;
+            // both sit well inside the first CRC chunk.
+            if mft_entry.size < 12 {
                 return Err(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "MFT entry not found",
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Compressed entry {} is too small ({} bytes) to hold a size header.",
+                        index, mft_entry.size
+                    ),
                 ));
             }
+            let stream_header = self.read_stored_bytes(index, 8)?;
+            u32::from_le_bytes([
+                stream_header[4],
+                stream_header[5],
+                stream_header[6],
+                stream_header[7],
+            ])
         };
 
-        let mft_entry = self.mft_data.get(index_found).unwrap();
-        #[allow(unused_mut)]
-        let raw_data_size = self.mft_data.get(index_found).unwrap().size;
-        self.dat_file
-            .seek(std::io::SeekFrom::Start(mft_entry.offset))?;
-
-        let mut raw_data = Vec::with_capacity(raw_data_size as usize);
-        raw_data.resize(raw_data_size as usize, 0);
-        self.dat_file.read_exact(&mut raw_data)?;
-        let mut raw_data_cleaned = raw_data.clone();
+        self.mft_data[index].uncompressed_size = uncompressed_size;
+        Ok(uncompressed_size)
+    }
 
-        // CRC-32C (Cyclic Redundancy Check 32-bit Castagnoli) is a variant of the CRC-32 algorithm that uses the Castagnoli polynomial.
-        // Define the range to remove 4 bytes from each cycle
-        let start_index = CHUNK_SIZE - 4; // Start of the range to remove
-        let end_index = CHUNK_SIZE; // End of the range to remove
+    /// Write one row per MFT entry to `out`, including the file IDs and base ID mapped to it.
+    ///
+    /// CSV output starts with a header row and joins multiple file IDs with `;`.
+    /// JSON output is a single array of objects.
+    pub fn export_manifest<W: Write>(
+        &self,
+        out: &mut W,
+        format: ManifestFormat,
+    ) -> std::io::Result<()> {
+        let mut rows: Vec<ManifestRow> = self
+            .mft_data
+            .iter()
+            .enumerate()
+            .map(|(index, mft_entry)| ManifestRow {
+                index,
+                offset: mft_entry.offset,
+                size: mft_entry.size,
+                compression_flag: mft_entry.compression_flag,
+                entry_flag: mft_entry.entry_flag,
+                counter: mft_entry.counter,
+                crc: mft_entry.crc,
+                base_id: None,
+                file_ids: Vec::new(),
+            })
+            .collect();
 
-        // Check the size of the raw data
-        if raw_data_size > CHUNK_SIZE as u32 {
-            // If data is larger than CHUNK_SIZE, remove 4 bytes in each cycle
-            let mut position = 0;
-            while position + CHUNK_SIZE <= raw_data_cleaned.len() {
-                // Remove 4 bytes from the specified range for each chunk
-                raw_data_cleaned.drain(position + start_index..position + end_index);
-                position += CHUNK_SIZE - 4; // Move to the next chunk
-            }
+        for index_data in &self.mft_index_data {
+            let Some(row) = (index_data.base_id as usize)
+                .checked_sub(1)
+                .and_then(|index| rows.get_mut(index))
+            else {
+                continue;
+            };
+            row.base_id = Some(index_data.base_id);
+            row.file_ids.push(index_data.file_id);
+        }
 
-            // After processing full chunks, handle the remaining data
-            if raw_data_cleaned.len() > 4 {
-                raw_data_cleaned.truncate(raw_data_cleaned.len() - 4); // Remove 4 bytes before EOF
+        match format {
+            ManifestFormat::Csv => {
+                writeln!(
+                    out,
+                    "index,offset,size,compression_flag,entry_flag,counter,crc,base_id,file_ids"
+                )?;
+                for row in &rows {
+                    let base_id = row.base_id.map(|id| id.to_string()).unwrap_or_default();
+                    let file_ids: Vec<String> =
+                        row.file_ids.iter().map(|id| id.to_string()).collect();
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{},{},{},{}",
+                        row.index,
+                        row.offset,
+                        row.size,
+                        row.compression_flag,
+                        row.entry_flag,
+                        row.counter,
+                        row.crc,
+                        base_id,
+                        file_ids.join(";")
+                    )?;
+                }
             }
-        } else if raw_data_size == CHUNK_SIZE as u32 {
-            // If data is exactly CHUNK_SIZE, remove 4 bytes from the specified range
-            raw_data_cleaned.drain(start_index..end_index);
-        } else if raw_data_size < CHUNK_SIZE as u32 {
-            // If data is smaller than CHUNK_SIZE, no removal, just truncate the last 4 bytes
-            if raw_data_cleaned.len() > 4 {
-                raw_data_cleaned.truncate(raw_data_cleaned.len() - 4);
+            #[cfg(feature = "serde")]
+            ManifestFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, &rows)?;
+                writeln!(out)?;
             }
         }
+        Ok(())
+    }
 
-        if mft_entry.compression_flag != 0 {
-            let mut decompressed_data_size: u32 = 0;
-            let mut decompressed_data: Vec<u8> = Vec::new();
-            dat_decompress::inflate_dat_file_buffer(
-                raw_data_cleaned,
-                &mut decompressed_data_size,
-                &mut decompressed_data,
-            )?;
+    /// Resolve `number` like `resolve_index`, failing with `ErrorKind::NotFound` when the ID is not
+    /// in the index table and `ErrorKind::InvalidData` when its row holds an unusable base ID such as 0.
+    fn find_mft_index(&self, archive_id: ArchiveId, number: usize) -> std::io::Result<usize> {
+        let index_data = self.find_index_row(archive_id, number).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "MFT entry not found")
+        })?;
+        self.base_id_row(index_data.base_id).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Index table maps ID {} to base ID {}, which is not a row of the {}-entry MFT.",
+                    number,
+                    index_data.base_id,
+                    self.mft_data.len()
+                ),
+            )
+        })
+    }
 
-            return Ok((raw_data, decompressed_data));
-        } else {
-            Ok((raw_data, raw_data_cleaned))
+    pub fn extract_mft_data(
+        &mut self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        let index_found = self.find_mft_index(archive_id, number)?;
+        self.extract_by_index(index_found)
+    }
+
+    /// Like `extract_mft_data`, but also report whether the entry was compressed.
+    pub fn extract_entry(
+        &mut self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<ExtractedEntry> {
+        let name = archive_id.entry_name(number);
+        let index_found = self.find_mft_index(archive_id, number)?;
+        let (raw_data, decompressed_data) = self.extract_by_index(index_found)?;
+        Ok(ExtractedEntry {
+            raw_data,
+            decompressed_data,
+            was_compressed: self.mft_data[index_found].compression_flag != 0,
+            name,
+        })
+    }
+
+    /// Read up to the first `max_bytes` of the stored bytes of each entry in `indices`, by index in `mft_data`.
+    ///
+    /// Nothing is decompressed, and only the requested prefixes are read, through one newly opened
+    /// handle like `extract_mft_data_reopened`.
+    pub fn raw_prefixes(&self, indices: &[usize], max_bytes: u32) -> std::io::Result<Vec<Vec<u8>>> {
+        let mut dat_file: Box<dyn DatReader + '_> = match &self.mapped_data {
+            Some(mapped_data) => Box::new(Cursor::new(&mapped_data[..])),
+            None => Box::new(reopen_reader(&self.filename)?),
+        };
+
+        let mut prefixes = Vec::with_capacity(indices.len());
+        for &index in indices {
+            let mft_entry = readable_mft_entry_at(&self.mft_data, index, self.file_size)?;
+            dat_file.seek(SeekFrom::Start(mft_entry.offset))?;
+            let mut prefix = vec![0; mft_entry.size.min(max_bytes) as usize];
+            dat_file.read_exact(&mut prefix)?;
+            prefixes.push(prefix);
         }
+        Ok(prefixes)
     }
-}
 
-/// Print a hex dump of the given buffer.
-pub fn hex_dump(buffer: &Vec<u8>, bytes_per_line: usize, max_lines: usize) -> String {
-    let mut result = String::new();
-    for (i, chunk) in buffer.chunks(bytes_per_line).enumerate() {
-        if i == max_lines {
-            break;
+    /// Read the whole index table region, `mft_data[MFT_ENTRY_INDEX_NUM]`, as stored, without parsing it.
+    ///
+    /// Fails with `ErrorKind::NotFound` when the MFT has no index table row.
+    pub fn raw_index_bytes(&mut self) -> std::io::Result<Vec<u8>> {
+        self.read_raw_entry(MFT_ENTRY_INDEX_NUM)
+    }
+
+    /// Read the stored bytes of `mft_data[index]` exactly as they are in the archive.
+    ///
+    /// No CRCs are stripped and nothing is decompressed, so the result is always `size` bytes long.
+    /// Fails with `ErrorKind::NotFound` when `index` is out of range.
+    pub fn read_raw_entry(&mut self, index: usize) -> std::io::Result<Vec<u8>> {
+        self.read_stored_bytes(index, u32::MAX)
+    }
+
+    /// Read up to the first `len` stored bytes of `mft_data[index]`, slicing the mapping when there is one.
+    ///
+    /// Every read of entry bytes through `self` goes here, so mapped archives never seek the reader.
+    fn read_stored_bytes(&mut self, index: usize, len: u32) -> std::io::Result<Vec<u8>> {
+        let mft_entry = readable_mft_entry_at(&self.mft_data, index, self.file_size)?;
+        let start = mft_entry.offset as usize;
+        let end = start + mft_entry.size.min(len) as usize;
+        if let Some(mapped_data) = &self.mapped_data {
+            return Ok(mapped_data[start..end].to_vec());
         }
-        // Print the offset
-        result.push_str(&format!("{:08X}: ", i * bytes_per_line));
 
-        // Print the hexadecimal representation
-        for byte in chunk {
-            result.push_str(&format!("{:02X} ", byte));
+        self.dat_file.seek(SeekFrom::Start(mft_entry.offset))?;
+        let mut stored_data = vec![0; end - start];
+        self.dat_file.read_exact(&mut stored_data)?;
+        Ok(stored_data)
+    }
+
+    /// Read up to the first `max_bytes` of the decompressed data of `mft_data[index]`, reading at most one CRC chunk of the archive.
+    fn read_entry_prefix(&mut self, index: usize, max_bytes: u32) -> std::io::Result<Vec<u8>> {
+        let compressed = mft_entry_at(&self.mft_data, index)?.compression_flag != 0;
+        if !compressed {
+            return self.read_stored_bytes(index, max_bytes);
         }
 
-        // Pad the last line with spaces if necessary
-        for _ in 0..(bytes_per_line - chunk.len()) {
-            result.push_str("   ");
+        let raw_data = self.read_stored_bytes(index, self.crc_chunk_size as u32)?;
+        if raw_data.len() <= 4 {
+            return Ok(Vec::new());
         }
 
-        // Print the ASCII representation
-        result.push_str("|");
-        for byte in chunk {
-            if byte.is_ascii_graphic() || *byte == b' ' {
-                result.push(*byte as char);
-            } else {
-                result.push('.');
-            }
+        // A full first chunk keeps its CRC at the end, which strip_chunk_crcs removes like any other chunk.
+        let raw_data_cleaned = strip_chunk_crcs(&raw_data, self.crc_chunk_size)?;
+        let mut decompressed_data: Vec<u8> = Vec::new();
+        dat_decompress::inflate_dat_file_buffer_prefix(
+            raw_data_cleaned,
+            &mut decompressed_data,
+            max_bytes,
+        )?;
+        Ok(decompressed_data)
+    }
+
+    /// Try to decompress every compressed entry and collect the ones that fail, by index in `mft_data`.
+    ///
+    /// A failing entry does not stop the scan. Uncompressed entries are skipped.
+    /// Each error is kept as an `io::Error` because reading the entry can fail too (see
+    /// `DecompressError`); use `DecompressError::from_io` to tell a corrupt stream apart.
+    pub fn validate_all(&mut self) -> Vec<(usize, std::io::Error)> {
+        let compressed_indices: Vec<usize> = self
+            .iter_entries()
+            .filter(|(_, mft_entry)| mft_entry.compression_flag != 0)
+            .map(|(index, _)| index)
+            .collect();
+
+        compressed_indices
+            .into_iter()
+            .filter_map(|index| self.extract_by_index(index).err().map(|err| (index, err)))
+            .collect()
+    }
+
+    /// Read and decompress `mft_data[index]` directly, without resolving a file ID or base ID.
+    ///
+    /// Fails with `ErrorKind::NotFound` when `index` is out of range.
+    pub fn extract_by_index(&mut self, index: usize) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        let raw_data = self.read_raw_entry(index)?;
+        decode_mft_entry(
+            raw_data,
+            &self.mft_data[index],
+            self.extraction_timeout,
+            self.crc_chunk_size,
+        )
+    }
+
+    /// Like `extract_mft_data`, but also return the CRC-32C values stripped from a compressed entry.
+    ///
+    /// Each value is paired with its byte position within the raw entry data, one per chunk.
+    /// The list is also stored in the entry's `MftData::crc_32c_data`; it is empty for uncompressed entries.
+    pub fn extract_mft_data_with_crcs(
+        &mut self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<(Vec<u8>, Vec<u8>, Vec<ChunkCrc>)> {
+        let index_found = self.find_mft_index(archive_id, number)?;
+
+        let (raw_data, decompressed_data) = self.extract_by_index(index_found)?;
+        let compressed = self.mft_data[index_found].compression_flag != 0;
+
+        let crc_32c_data = if compressed {
+            chunk_crcs(&raw_data, self.crc_chunk_size)
+        } else {
+            Vec::new()
+        };
+        self.mft_data[index_found].crc_32c_data = crc_32c_data.clone();
+
+        Ok((raw_data, decompressed_data, crc_32c_data))
+    }
+
+    /// Like `extract_mft_data`, but reads through a newly opened handle so a shared `DatFile` can serve concurrent callers.
+    ///
+    /// Only works for archives loaded with `load` or `load_mmap`. Mapped archives are sliced directly.
+    pub fn extract_mft_data_reopened(
+        &self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        let index_found = self.find_mft_index(archive_id, number)?;
+        self.extract_by_index_reopened(index_found)
+    }
+
+    /// Like `extract_entry`, but reads through a newly opened handle like `extract_mft_data_reopened`.
+    pub fn extract_entry_reopened(
+        &self,
+        archive_id: ArchiveId,
+        number: usize,
+    ) -> std::io::Result<ExtractedEntry> {
+        let name = archive_id.entry_name(number);
+        let index_found = self.find_mft_index(archive_id, number)?;
+        let (raw_data, decompressed_data) = self.extract_by_index_reopened(index_found)?;
+        Ok(ExtractedEntry {
+            raw_data,
+            decompressed_data,
+            was_compressed: self.mft_data[index_found].compression_flag != 0,
+            name,
+        })
+    }
+
+    /// Like `extract_by_index`, but reads through a newly opened handle like `extract_mft_data_reopened`.
+    pub fn extract_by_index_reopened(&self, index: usize) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        if let Some(mapped_data) = &self.mapped_data {
+            return read_mapped_mft_entry(
+                &mapped_data[..],
+                &self.mft_data,
+                index,
+                self.extraction_timeout,
+                self.crc_chunk_size,
+            );
         }
-        result.push_str("|\n");
+        let mut dat_file = reopen_reader(&self.filename)?;
+        read_mft_entry(
+            &mut dat_file,
+            &self.mft_data,
+            index,
+            self.file_size,
+            self.extraction_timeout,
+            self.crc_chunk_size,
+        )
+    }
+
+    /// Extract an entry and write either its raw or decompressed bytes to `path`.
+    ///
+    /// Missing parent directories are created.
+    pub fn extract_to_file(
+        &mut self,
+        archive_id: ArchiveId,
+        number: usize,
+        path: &Path,
+        decompressed: bool,
+    ) -> std::io::Result<()> {
+        let (raw_data, decompressed_data) = self.extract_mft_data(archive_id, number)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        if decompressed {
+            writer.write_all(&decompressed_data)?;
+        } else {
+            writer.write_all(&raw_data)?;
+        }
+        writer.flush()
+    }
+
+    /// Extract and decompress many MFT entries in parallel, by their index in `mft_data`.
+    ///
+    /// Each worker thread reopens the archive from `filename`, so this only works for
+    /// archives loaded with `load`. Results are returned in the same order as `indices`.
+    ///
+    /// A failed entry's error is an `io::Error`, as described on `DecompressError`; when
+    /// decompression was the cause, `DecompressError::from_io` recovers it.
+    pub fn extract_many(&self, indices: &[usize]) -> Vec<std::io::Result<Vec<u8>>> {
+        let worker_count = std::thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(indices.len().max(1));
+        let next_index = AtomicUsize::new(0);
+        let filename = self.filename.as_str();
+        let mft_data = self.mft_data.as_slice();
+        let file_size = self.file_size;
+        let extraction_timeout = self.extraction_timeout;
+        let crc_chunk_size = self.crc_chunk_size;
+        let mapped_data = self
+            .mapped_data
+            .as_deref()
+            .map(|mapped_data| &mapped_data[..]);
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..worker_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut worker_results = Vec::new();
+                        // Opened on first use, and never for mapped archives
+                        let mut dat_file = None;
+                        loop {
+                            let position = next_index.fetch_add(1, Ordering::Relaxed);
+                            let Some(&mft_index) = indices.get(position) else {
+                                break;
+                            };
+                            let result = match mapped_data {
+                                Some(mapped_data) => read_mapped_mft_entry(
+                                    mapped_data,
+                                    mft_data,
+                                    mft_index,
+                                    extraction_timeout,
+                                    crc_chunk_size,
+                                ),
+                                None => dat_file
+                                    .get_or_insert_with(|| reopen_reader(filename))
+                                    .as_mut()
+                                    .map_err(|err| std::io::Error::new(err.kind(), err.to_string()))
+                                    .and_then(|dat_file| {
+                                        read_mft_entry(
+                                            dat_file,
+                                            mft_data,
+                                            mft_index,
+                                            file_size,
+                                            extraction_timeout,
+                                            crc_chunk_size,
+                                        )
+                                    }),
+                            }
+                            .map(|(_, decompressed_data)| decompressed_data);
+                            worker_results.push((position, result));
+                        }
+                        worker_results
+                    })
+                })
+                .collect();
+
+            collect_worker_results(
+                indices.len(),
+                workers.into_iter().map(|worker| worker.join()),
+            )
+        })
+    }
+}
+
+/// Put each worker's `(position, result)` pairs back in order.
+///
+/// A worker that panicked loses the results it had, so every position no worker reported fails with `ErrorKind::Other`.
+fn collect_worker_results(
+    len: usize,
+    workers: impl Iterator<Item = std::thread::Result<Vec<(usize, std::io::Result<Vec<u8>>)>>>,
+) -> Vec<std::io::Result<Vec<u8>>> {
+    let mut results: Vec<Option<std::io::Result<Vec<u8>>>> = (0..len).map(|_| None).collect();
+    for worker_results in workers.flatten() {
+        for (position, result) in worker_results {
+            results[position] = Some(result);
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| {
+                Err(std::io::Error::other(
+                    "The extraction worker for this entry panicked.",
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Apply the path checks in `options` and return the path as the archive's filename.
+fn checked_path(file_path: &Path, options: &LoadOptions) -> std::io::Result<String> {
+    let file_path_str = file_path.to_str().unwrap_or_default().to_string();
+    // Check if the file extension is '.dat'
+    if options.check_extension && !file_path_str.to_lowercase().ends_with(".dat") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid file extension. Expected '.dat'.",
+        ));
+    }
+    Ok(file_path_str)
+}
+
+/// Open an independent reader over the archive at `filename`.
+fn reopen_reader(filename: &str) -> std::io::Result<BufReader<File>> {
+    if filename.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Archive was not loaded from a path and cannot be reopened.",
+        ));
+    }
+    Ok(BufReader::new(File::open(filename)?))
+}
+
+/// Borrow `mft_data[index]`, failing with `NotFound` when the index is out of range.
+fn mft_entry_at(mft_data: &[MftData], index: usize) -> std::io::Result<&MftData> {
+    mft_data.get(index).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "MFT index {} is out of range ({} entries).",
+                index,
+                mft_data.len()
+            ),
+        )
+    })
+}
+
+/// Like `mft_entry_at`, but also fail with `InvalidData` when the entry's data runs past the end of the archive.
+fn readable_mft_entry_at(
+    mft_data: &[MftData],
+    index: usize,
+    file_size: u64,
+) -> std::io::Result<&MftData> {
+    let mft_entry = mft_entry_at(mft_data, index)?;
+    if mft_entry
+        .offset
+        .checked_add(mft_entry.size as u64)
+        .is_none_or(|end| end > file_size)
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "MFT entry {} at offset {} with {} bytes runs past the end of the archive ({} bytes).",
+                index, mft_entry.offset, mft_entry.size, file_size
+            ),
+        ));
+    }
+    Ok(mft_entry)
+}
+
+/// Read the raw bytes of `mft_data[index]` and return them along with the cleaned or decompressed data.
+///
+/// The entry is bounds-checked against `file_size` by `readable_mft_entry_at` before anything is read.
+fn read_mft_entry(
+    dat_file: &mut dyn DatReader,
+    mft_data: &[MftData],
+    index: usize,
+    file_size: u64,
+    timeout: Option<Duration>,
+    crc_chunk_size: usize,
+) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    let mft_entry = readable_mft_entry_at(mft_data, index, file_size)?;
+    let raw_data_size = mft_entry.size;
+    dat_file.seek(std::io::SeekFrom::Start(mft_entry.offset))?;
+
+    let mut raw_data = vec![0; raw_data_size as usize];
+    dat_file.read_exact(&mut raw_data)?;
+
+    decode_mft_entry(raw_data, mft_entry, timeout, crc_chunk_size)
+}
+
+/// Copy the raw bytes of `mft_data[index]` out of a memory-mapped archive and decode them like `read_mft_entry`.
+///
+/// The entry is bounds-checked against the mapping by `readable_mft_entry_at`, like every other read.
+fn read_mapped_mft_entry(
+    mapped_data: &[u8],
+    mft_data: &[MftData],
+    index: usize,
+    timeout: Option<Duration>,
+    crc_chunk_size: usize,
+) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    let mft_entry = readable_mft_entry_at(mft_data, index, mapped_data.len() as u64)?;
+    let start = mft_entry.offset as usize;
+    let raw_data = mapped_data[start..start + mft_entry.size as usize].to_vec();
+
+    decode_mft_entry(raw_data, mft_entry, timeout, crc_chunk_size)
+}
+
+/// Return `raw_data` along with its cleaned or decompressed data.
+///
+/// Decompression fails with `ErrorKind::TimedOut` if it takes longer than `timeout`.
+fn decode_mft_entry(
+    raw_data: Vec<u8>,
+    mft_entry: &MftData,
+    timeout: Option<Duration>,
+    crc_chunk_size: usize,
+) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    // Only compressed entries carry the per-chunk CRC layout; uncompressed payloads are returned untouched.
+    if mft_entry.compression_flag == 0 {
+        let raw_data_copy = raw_data.clone();
+        return Ok((raw_data, raw_data_copy));
+    }
+
+    // Entries of 4 bytes or less hold at most the trailing CRC, so there is nothing to clean or inflate.
+    if raw_data.len() <= 4 {
+        return Ok((raw_data, Vec::new()));
+    }
+    let raw_data_cleaned = strip_chunk_crcs(&raw_data, crc_chunk_size)?;
+
+    let mut decompressed_data_size: u32 = 0;
+    let mut decompressed_data: Vec<u8> = Vec::new();
+    match timeout {
+        Some(timeout) => dat_decompress::inflate_dat_file_buffer_deadline(
+            raw_data_cleaned,
+            Instant::now() + timeout,
+            &mut decompressed_data_size,
+            &mut decompressed_data,
+        )?,
+        None => dat_decompress::inflate_dat_file_buffer(
+            raw_data_cleaned,
+            &mut decompressed_data_size,
+            &mut decompressed_data,
+        )?,
+    }
+
+    Ok((raw_data, decompressed_data))
+}
+
+/// Pick the CRC chunk stride for an archive from its `DatHeader::chunk_size`.
+///
+/// The 512-byte block size of known archives maps to `CHUNK_SIZE`. Other values are unexpected and
+/// logged, then used as the stride, unless they are too small to hold a CRC.
+pub(crate) fn crc_chunk_size_for_header(header_chunk_size: u32) -> usize {
+    match header_chunk_size {
+        DAT_BLOCK_SIZE => CHUNK_SIZE,
+        0..=4 => {
+            warn!(
+                "DAT chunk size {} is too small for CRC chunks; using {} bytes.",
+                header_chunk_size, CHUNK_SIZE
+            );
+            CHUNK_SIZE
+        }
+        _ => {
+            warn!(
+                "Unexpected DAT chunk size {}; using it as the CRC chunk stride.",
+                header_chunk_size
+            );
+            header_chunk_size as usize
+        }
+    }
+}
+
+/// Collect the CRC-32C at the end of every `chunk_size` chunk of entry data, including a final partial chunk.
+///
+/// Positions are byte offsets into `raw_data`. Values are read as little-endian u32s.
+fn chunk_crcs(raw_data: &[u8], chunk_size: usize) -> Vec<ChunkCrc> {
+    let mut crc_32c_data = Vec::new();
+    for (chunk_index, chunk) in raw_data.chunks(chunk_size).enumerate() {
+        if chunk.len() < 4 {
+            continue;
+        }
+        let crc_position = chunk_index * chunk_size + chunk.len() - 4;
+        let crc_value = u32::from_le_bytes([
+            raw_data[crc_position],
+            raw_data[crc_position + 1],
+            raw_data[crc_position + 2],
+            raw_data[crc_position + 3],
+        ]);
+        crc_32c_data.push((crc_position as u64, crc_value));
+    }
+    crc_32c_data
+}
+
+/// Remove the 4-byte CRC-32C that ends every `chunk_size` bytes of entry data, and the one before EOF.
+///
+/// CRC-32C (Cyclic Redundancy Check 32-bit Castagnoli) is a variant of the CRC-32 algorithm that uses the Castagnoli polynomial.
+/// Fails with `ErrorKind::InvalidInput` when `chunk_size` leaves no room for data next to the CRC.
+fn strip_chunk_crcs(raw_data: &[u8], chunk_size: usize) -> std::io::Result<Vec<u8>> {
+    if chunk_size <= 4 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("CRC chunk size {} must be larger than 4 bytes.", chunk_size),
+        ));
+    }
+    let mut raw_data_cleaned = Vec::with_capacity(raw_data.len());
+
+    // Copy each full chunk without its trailing CRC; a final partial chunk is copied whole
+    for chunk in raw_data.chunks(chunk_size) {
+        if chunk.len() == chunk_size {
+            raw_data_cleaned.extend_from_slice(&chunk[..chunk_size - 4]);
+        } else {
+            raw_data_cleaned.extend_from_slice(chunk);
+        }
+    }
+
+    // Remove the 4 bytes before EOF, unless the data was exactly one chunk
+    if raw_data.len() != chunk_size && raw_data_cleaned.len() > 4 {
+        raw_data_cleaned.truncate(raw_data_cleaned.len() - 4);
+    }
+
+    Ok(raw_data_cleaned)
+}
+
+/// Reflected CRC-32C (Castagnoli) polynomial.
+const CRC32C_POLYNOMIAL: u32 = 0x82F6_3B78;
+
+/// Lookup table for `crc32c`, one entry per byte value.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32C_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32C of `data`, the checksum used for the chunk CRCs in entry data.
+pub fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Format a hex dump of the given buffer, with an offset column, hex bytes, and an ASCII gutter.
+///
+/// At most `max_lines` lines of `bytes_per_line` bytes each are included.
+pub fn hex_dump_string(buffer: &[u8], bytes_per_line: usize, max_lines: usize) -> String {
+    let mut result = String::new();
+    for (i, chunk) in buffer.chunks(bytes_per_line).enumerate() {
+        if i == max_lines {
+            break;
+        }
+        push_hex_dump_line(&mut result, i * bytes_per_line, chunk, bytes_per_line);
+    }
+    result
+}
+
+/// Write the same hex dump as `hex_dump_string` for everything read from `src`, one line at a time.
+///
+/// Only one line of input is held in memory, so this suits entries too large to buffer.
+pub fn hex_dump_stream<R: Read, W: Write>(
+    mut src: R,
+    mut dst: W,
+    bytes_per_line: usize,
+    max_lines: usize,
+) -> std::io::Result<()> {
+    let mut chunk = vec![0; bytes_per_line];
+    let mut line = String::new();
+    for i in 0..max_lines {
+        // Fill the whole line unless the input ends, since reads may return fewer bytes
+        let mut filled = 0;
+        while filled < bytes_per_line {
+            match src.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+
+        line.clear();
+        push_hex_dump_line(
+            &mut line,
+            i * bytes_per_line,
+            &chunk[..filled],
+            bytes_per_line,
+        );
+        dst.write_all(line.as_bytes())?;
+        if filled < bytes_per_line {
+            break;
+        }
+    }
+    dst.flush()
+}
+
+/// Append one hex dump line for `chunk`, which starts at `offset` in the dumped data.
+fn push_hex_dump_line(result: &mut String, offset: usize, chunk: &[u8], bytes_per_line: usize) {
+    // Print the offset
+    result.push_str(&format!("{:08X}: ", offset));
+
+    // Print the hexadecimal representation
+    for byte in chunk {
+        result.push_str(&format!("{:02X} ", byte));
+    }
+
+    // Pad the last line with spaces if necessary
+    for _ in 0..(bytes_per_line - chunk.len()) {
+        result.push_str("   ");
+    }
+
+    // Print the ASCII representation
+    result.push('|');
+    for byte in chunk {
+        if byte.is_ascii_graphic() || *byte == b' ' {
+            result.push(*byte as char);
+        } else {
+            result.push('.');
+        }
+    }
+    result.push_str("|\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dat_builder::{DatBuilder, compress_entry_data};
+    use std::path::PathBuf;
+
+    fn load(bytes: Vec<u8>) -> std::io::Result<DatFile> {
+        let len = bytes.len() as u64;
+        DatFile::load_from_reader(Cursor::new(bytes), len)
+    }
+
+    /// Offset of the MFT in `minimal_archive`.
+    const MINIMAL_MFT_OFFSET: u64 = 53;
+
+    /// A header, one stored entry, an index table mapping file ID 7 to it, and the MFT, laid out by hand.
+    ///
+    /// The MFT rows are the header, the index table and the entry, so the entry has base ID 3.
+    fn minimal_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+        archive.push(151);
+        archive.extend_from_slice(b"AN(");
+        archive.extend_from_slice(&40u32.to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+        archive.extend_from_slice(&512u32.to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+        archive.extend_from_slice(&MINIMAL_MFT_OFFSET.to_le_bytes());
+        archive.extend_from_slice(&(4 * MFT_ENTRY_BYTES).to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+
+        archive.extend_from_slice(b"hello");
+        archive.extend_from_slice(&7u32.to_le_bytes());
+        archive.extend_from_slice(&3u32.to_le_bytes());
+        assert_eq!(archive.len() as u64, MINIMAL_MFT_OFFSET);
+
+        archive.extend_from_slice(b"Mft\x1a");
+        archive.extend_from_slice(&0u64.to_le_bytes());
+        archive.extend_from_slice(&4u32.to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+        archive.extend_from_slice(&0u32.to_le_bytes());
+        for (offset, size) in [(0u64, 40u32), (45, 8), (40, 5)] {
+            archive.extend_from_slice(&offset.to_le_bytes());
+            archive.extend_from_slice(&size.to_le_bytes());
+            archive.extend_from_slice(&[0; 12]);
+        }
+        archive
+    }
+
+    #[test]
+    fn load_from_reader_parses_minimal_archive() {
+        let mut dat_file = load(minimal_archive()).unwrap();
+
+        assert!(dat_file.filename.is_empty());
+        assert_eq!(dat_file.dat_header.version, 151);
+        assert_eq!(dat_file.dat_header.mft_offset, MINIMAL_MFT_OFFSET);
+        assert_eq!(dat_file.mft_header.mft_entry_size, 3);
+        assert_eq!(dat_file.mft_data.len(), 3);
+        assert_eq!(dat_file.mft_data[2].offset, 40);
+        assert_eq!(dat_file.mft_data[2].size, 5);
+        assert_eq!(dat_file.mft_index_data.len(), 1);
+        assert_eq!(dat_file.mft_index_data[0].file_id, 7);
+        assert_eq!(dat_file.mft_index_data[0].base_id, 3);
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 7).unwrap();
+        assert_eq!(raw_data, b"hello");
+        assert_eq!(decompressed_data, b"hello");
+    }
+
+    #[test]
+    fn load_accepts_correct_magic() {
+        let dat_file = load(minimal_archive()).unwrap();
+        assert_eq!(dat_file.dat_header.identifier, DAT_MAGIC);
+        assert_eq!(dat_file.mft_header.identifier, MFT_MAGIC);
+    }
+
+    #[test]
+    fn load_rejects_corrupted_dat_magic() {
+        let mut archive = minimal_archive();
+        archive[1..4].copy_from_slice(b"PK\x03");
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Invalid DAT identifier"));
+        assert!(err.to_string().contains("[50, 4B, 03]"));
+    }
+
+    #[test]
+    fn load_rejects_corrupted_mft_magic() {
+        let mut archive = minimal_archive();
+        let mft_offset = MINIMAL_MFT_OFFSET as usize;
+        archive[mft_offset..mft_offset + 4].copy_from_slice(b"Mft\x00");
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Invalid MFT identifier"));
+        assert!(err.to_string().contains("[4D, 66, 74, 00]"));
+    }
+
+    #[test]
+    fn load_rejects_inflated_mft_entry_count() {
+        let mut archive = minimal_archive();
+        let count_offset = MINIMAL_MFT_OFFSET as usize + 12;
+        archive[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let started = Instant::now();
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string()
+                .contains("Invalid MFT entry count 4294967295")
+        );
+        // Rejected from the header alone, without reading rows to EOF
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn load_rejects_mft_range_that_overflows() {
+        let mut archive = minimal_archive();
+        archive[24..32].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("exceeds file size"));
+    }
+
+    #[test]
+    fn load_rejects_index_table_past_end_of_file() {
+        let mut archive = minimal_archive();
+        // Size field of the index table row, the second MFT row
+        let size_offset = MINIMAL_MFT_OFFSET as usize + 2 * MFT_ENTRY_BYTES as usize + 8;
+        archive[size_offset..size_offset + 4].copy_from_slice(&0x1000_0000u32.to_le_bytes());
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Index table at offset 45"));
+    }
+
+    /// Write `bytes` to a `.dat` file in the temp directory, unique to this process and `name`.
+    fn write_temp_archive(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tarir-{}-{}.dat", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// A built archive with stored and compressed entries; file IDs 100.. map to them in order.
+    fn built_archive() -> (Vec<u8>, Vec<Vec<u8>>) {
+        let contents: Vec<Vec<u8>> = (0..6u32)
+            .map(|entry| {
+                (0..(entry * 3000 + 10))
+                    .map(|i| (i * (entry + 1) % 253) as u8)
+                    .collect()
+            })
+            .collect();
+        let mut builder = DatBuilder::new();
+        for (file_id, data) in (100..).zip(&contents) {
+            let base_id = if file_id % 2 == 0 {
+                builder.add_entry(compress_entry_data(data), 8)
+            } else {
+                builder.add_entry(data.clone(), 0)
+            };
+            builder.add_file_id(file_id, base_id);
+        }
+        (builder.build(), contents)
+    }
+
+    #[test]
+    fn extract_many_matches_serial_extraction() {
+        let (archive, contents) = built_archive();
+        let path = write_temp_archive("extract-many", &archive);
+        let mut dat_file = DatFile::load(&path).unwrap();
+
+        let indices: Vec<usize> = (3..dat_file.mft_data.len()).rev().chain([999]).collect();
+        let parallel = dat_file.extract_many(&indices);
+        assert_eq!(parallel.len(), indices.len());
+        for (&index, result) in indices.iter().zip(&parallel) {
+            match dat_file.extract_by_index(index) {
+                Ok((_, serial)) => assert_eq!(result.as_ref().unwrap(), &serial),
+                Err(err) => assert_eq!(result.as_ref().unwrap_err().kind(), err.kind()),
+            }
+        }
+        assert_eq!(parallel[0].as_ref().unwrap(), contents.last().unwrap());
+        assert!(parallel.last().unwrap().is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn entry_info_reports_the_resolved_mft_row() {
+        let (archive, _) = built_archive();
+        let dat_file = load(archive).unwrap();
+
+        let info = dat_file.entry_info(ArchiveId::FileId, 102).unwrap();
+        assert_eq!(info.index, 5);
+        assert_eq!(info.offset, dat_file.mft_data[info.index].offset);
+        assert_eq!(info.size, dat_file.mft_data[info.index].size);
+        assert_eq!(info.compression_flag, 8);
+
+        let info = dat_file.entry_info(ArchiveId::BaseId, 5).unwrap();
+        assert_eq!(info.offset, dat_file.mft_data[4].offset);
+        assert_eq!(info.compression_flag, 0);
+    }
+
+    #[test]
+    fn extract_to_file_writes_raw_and_decompressed_bytes() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 102).unwrap();
+
+        let dir =
+            std::env::temp_dir().join(format!("tarir-{}-extract-to-file", std::process::id()));
+        let path = dir.join("nested").join("entry_102.bin");
+        dat_file
+            .extract_to_file(ArchiveId::FileId, 102, &path, true)
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), decompressed_data);
+        assert_eq!(decompressed_data, contents[2]);
+
+        dat_file
+            .extract_to_file(ArchiveId::FileId, 102, &path, false)
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), raw_data);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hex_dump_string_formats_offsets_bytes_and_ascii() {
+        let buffer = b"Hello, world!\x00\x01\x7fABCD";
+        assert_eq!(buffer.len(), 20);
+        assert_eq!(
+            hex_dump_string(buffer, 16, 16),
+            "00000000: 48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 21 00 01 7F |Hello, world!...|\n\
+             00000010: 41 42 43 44                                     |ABCD|\n"
+        );
+        assert_eq!(hex_dump_string(buffer, 16, 1).lines().count(), 1);
+    }
+
+    /// Two entries, with file IDs 20 and 21 sharing the first one and 30 pointing at the second.
+    fn shared_base_archive() -> Vec<u8> {
+        let mut builder = DatBuilder::new();
+        let shared_base_id = builder.add_entry(b"shared".to_vec(), 0);
+        let other_base_id = builder.add_entry(b"other".to_vec(), 0);
+        builder
+            .add_file_id(20, shared_base_id)
+            .add_file_id(30, other_base_id)
+            .add_file_id(21, shared_base_id);
+        builder.build()
+    }
+
+    #[test]
+    fn resolve_index_follows_file_ids_to_shared_base_ids() {
+        let dat_file = load(shared_base_archive()).unwrap();
+
+        assert_eq!(dat_file.resolve_index(ArchiveId::FileId, 20), Some(3));
+        assert_eq!(dat_file.resolve_index(ArchiveId::FileId, 21), Some(3));
+        assert_eq!(dat_file.resolve_index(ArchiveId::FileId, 30), Some(4));
+        assert_eq!(dat_file.resolve_index(ArchiveId::BaseId, 4), Some(3));
+    }
+
+    #[test]
+    fn resolve_index_returns_none_for_unknown_ids() {
+        let dat_file = load(shared_base_archive()).unwrap();
+
+        assert_eq!(dat_file.resolve_index(ArchiveId::FileId, 22), None);
+        assert_eq!(dat_file.resolve_index(ArchiveId::BaseId, 99), None);
+    }
+
+    #[test]
+    fn file_ids_for_base_collects_every_file_id() {
+        let dat_file = load(shared_base_archive()).unwrap();
+
+        assert_eq!(dat_file.file_ids_for_base(4), vec![20, 21]);
+        assert_eq!(dat_file.file_ids_for_base(5), vec![30]);
+        assert!(dat_file.file_ids_for_base(6).is_empty());
+    }
+
+    #[test]
+    fn export_manifest_writes_csv_header_and_rows() {
+        let dat_file = load(shared_base_archive()).unwrap();
+        let mut out = Vec::new();
+        dat_file
+            .export_manifest(&mut out, ManifestFormat::Csv)
+            .unwrap();
+
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "index,offset,size,compression_flag,entry_flag,counter,crc,base_id,file_ids"
+        );
+        assert_eq!(lines.len(), 1 + dat_file.mft_data.len());
+        assert!(lines[4].starts_with("3,"));
+        assert!(lines[4].ends_with(",4,20;21"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn export_manifest_writes_a_json_array() {
+        let dat_file = load(shared_base_archive()).unwrap();
+        let mut out = Vec::new();
+        dat_file
+            .export_manifest(&mut out, ManifestFormat::Json)
+            .unwrap();
+
+        let rows: Vec<ManifestRow> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(rows.len(), dat_file.mft_data.len());
+        assert_eq!(rows[4].index, 4);
+        assert_eq!(rows[4].offset, dat_file.mft_data[4].offset);
+        assert_eq!(rows[4].base_id, Some(5));
+        assert_eq!(rows[4].file_ids, vec![30]);
+        assert_eq!(rows[0].base_id, None);
+    }
+
+    #[test]
+    fn header_chunk_size_sets_the_crc_stride() {
+        assert_eq!(crc_chunk_size_for_header(DAT_BLOCK_SIZE), CHUNK_SIZE);
+        assert_eq!(crc_chunk_size_for_header(0), CHUNK_SIZE);
+        assert_eq!(crc_chunk_size_for_header(4), CHUNK_SIZE);
+        assert_eq!(crc_chunk_size_for_header(0x1000), 0x1000);
+
+        let data: Vec<u8> = (0..20000u32).map(|i| (i % 241) as u8).collect();
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(compress_entry_data(&data), 8);
+        builder.add_file_id(1, base_id).chunk_size(0x1000);
+        let mut dat_file = load(builder.build()).unwrap();
+        assert_eq!(dat_file.dat_header.chunk_size, 0x1000);
+        assert_eq!(dat_file.crc_chunk_size, 0x1000);
+
+        let (raw_data, decompressed_data, crcs) = dat_file
+            .extract_mft_data_with_crcs(ArchiveId::FileId, 1)
+            .unwrap();
+        assert_eq!(decompressed_data, data);
+        // Every full chunk ends in the CRC of the 0xFFC bytes before it
+        assert!(crcs.len() > 2);
+        for (chunk_index, &(position, crc)) in crcs.iter().enumerate() {
+            let chunk_start = chunk_index * 0x1000;
+            if chunk_index + 1 < crcs.len() {
+                assert_eq!(position as usize, chunk_start + 0xFFC);
+            }
+            assert_eq!(crc, crc32c(&raw_data[chunk_start..position as usize]));
+        }
+    }
+
+    #[test]
+    fn load_rejects_mft_without_index_table() {
+        let mut archive = minimal_archive();
+        // The header row plus a single MFT row
+        let count_offset = MINIMAL_MFT_OFFSET as usize + 12;
+        archive[count_offset..count_offset + 4].copy_from_slice(&2u32.to_le_bytes());
+
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "MFT has 1 entries and no index table; file ID and base ID lookups are unavailable."
+        );
+    }
+
+    #[test]
+    fn index_entry_count_uses_eight_byte_rows() {
+        let dat_file = load(shared_base_archive()).unwrap();
+        assert_eq!(dat_file.mft_data[MFT_ENTRY_INDEX_NUM].size, 24);
+        assert_eq!(dat_file.mft_index_data.len(), 3);
+
+        // A trailing partial row is ignored
+        let mut archive = minimal_archive();
+        let size_offset = MINIMAL_MFT_OFFSET as usize + 2 * MFT_ENTRY_BYTES as usize + 8;
+        archive[size_offset..size_offset + 4].copy_from_slice(&12u32.to_le_bytes());
+        let dat_file = load(archive).unwrap();
+        assert_eq!(dat_file.mft_index_data.len(), 1);
+        assert_eq!(dat_file.mft_index_data[0].file_id, 7);
+    }
+
+    #[test]
+    fn tiny_entries_extract_without_crc_removal() {
+        // The entry row is the third row after the MFT header
+        let entry_row = MINIMAL_MFT_OFFSET as usize + 3 * MFT_ENTRY_BYTES as usize;
+        for size in [0u32, 3, 4] {
+            for compression_flag in [0u16, 8] {
+                let mut archive = minimal_archive();
+                archive[entry_row + 8..entry_row + 12].copy_from_slice(&size.to_le_bytes());
+                archive[entry_row + 12..entry_row + 14]
+                    .copy_from_slice(&compression_flag.to_le_bytes());
+                let mut dat_file = load(archive).unwrap();
+
+                let (raw_data, decompressed_data) =
+                    dat_file.extract_mft_data(ArchiveId::FileId, 7).unwrap();
+                assert_eq!(raw_data, &b"hello"[..size as usize]);
+                if compression_flag == 0 {
+                    assert_eq!(decompressed_data, raw_data);
+                } else {
+                    assert!(decompressed_data.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crc32c_matches_the_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn extract_and_hash_is_stable() {
+        let mut dat_file = load(minimal_archive()).unwrap();
+        assert_eq!(
+            dat_file.extract_and_hash(ArchiveId::FileId, 7).unwrap(),
+            (5, 0x9A71_BB4C)
+        );
+        assert_eq!(
+            dat_file.extract_and_hash(ArchiveId::BaseId, 3).unwrap(),
+            (5, 0x9A71_BB4C)
+        );
+    }
+
+    #[test]
+    fn large_stored_entries_are_not_stripped() {
+        // Several CRC strides long, so stripping chunk CRCs would drop bytes
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(data.clone(), 0);
+        builder.add_file_id(100, base_id);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 100).unwrap();
+        assert_eq!(raw_data, data);
+        assert_eq!(decompressed_data, data);
+    }
+
+    #[test]
+    fn uncompressed_size_matches_extracted_length() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+
+        for (index, data) in (3..dat_file.mft_data.len()).zip(&contents) {
+            let (_, decompressed_data) = dat_file.extract_by_index(index).unwrap();
+            assert_eq!(decompressed_data.len(), data.len());
+            assert_eq!(
+                dat_file.uncompressed_size(index).unwrap() as usize,
+                decompressed_data.len()
+            );
+            assert_eq!(
+                dat_file.mft_data[index].uncompressed_size as usize,
+                data.len()
+            );
+        }
+    }
+
+    #[test]
+    fn load_with_options_accepts_other_extensions() {
+        let (archive, contents) = built_archive();
+        let path = std::env::temp_dir().join(format!("tarir-{}-archive.bin", std::process::id()));
+        std::fs::write(&path, &archive).unwrap();
+
+        let err = DatFile::load(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        let mut dat_file = DatFile::load_with_options(
+            &path,
+            LoadOptions {
+                check_extension: false,
+            },
+        )
+        .unwrap();
+        let (_, decompressed_data) = dat_file.extract_mft_data(ArchiveId::FileId, 100).unwrap();
+        assert_eq!(decompressed_data, contents[0]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// The CRC removal `strip_chunk_crcs` replaced, draining each CRC in place.
+    fn drain_chunk_crcs(raw_data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut raw_data_cleaned = raw_data.to_vec();
+        if raw_data.len() > chunk_size {
+            let mut position = 0;
+            while position + chunk_size <= raw_data_cleaned.len() {
+                raw_data_cleaned.drain(position + chunk_size - 4..position + chunk_size);
+                position += chunk_size - 4;
+            }
+            if raw_data_cleaned.len() > 4 {
+                raw_data_cleaned.truncate(raw_data_cleaned.len() - 4);
+            }
+        } else if raw_data.len() == chunk_size {
+            raw_data_cleaned.drain(chunk_size - 4..chunk_size);
+        } else if raw_data_cleaned.len() > 4 {
+            raw_data_cleaned.truncate(raw_data_cleaned.len() - 4);
+        }
+        raw_data_cleaned
+    }
+
+    #[test]
+    fn strip_chunk_crcs_matches_draining_in_place() {
+        let raw_data: Vec<u8> = (0..200u32).map(|i| i as u8).collect();
+        for len in 0..raw_data.len() {
+            assert_eq!(
+                strip_chunk_crcs(&raw_data[..len], 16).unwrap(),
+                drain_chunk_crcs(&raw_data[..len], 16),
+                "length {}",
+                len
+            );
+        }
+
+        let raw_data: Vec<u8> = (0..CHUNK_SIZE as u32 * 3 + 100)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        assert_eq!(
+            strip_chunk_crcs(&raw_data, CHUNK_SIZE).unwrap(),
+            drain_chunk_crcs(&raw_data, CHUNK_SIZE)
+        );
+        assert_eq!(
+            strip_chunk_crcs(&raw_data, CHUNK_SIZE).unwrap().len(),
+            raw_data.len() - 4 * 4
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saved_index_reloads_and_extracts() {
+        let (archive, contents) = built_archive();
+        let path = write_temp_archive("saved-index", &archive);
+        let index_path = path.with_extension("json");
+        let dat_file = DatFile::load(&path).unwrap();
+        dat_file.save_index(&index_path).unwrap();
+
+        let mut reloaded = DatFile::load_index(&index_path).unwrap();
+        assert_eq!(reloaded.filename, dat_file.filename);
+        assert_eq!(reloaded.mft_data.len(), dat_file.mft_data.len());
+        assert_eq!(reloaded.mft_index_data.len(), dat_file.mft_index_data.len());
+        let (_, decompressed_data) = reloaded.extract_mft_data(ArchiveId::FileId, 102).unwrap();
+        assert_eq!(decompressed_data, contents[2]);
+
+        // A changed archive makes the saved index stale
+        std::fs::write(&path, &archive[..archive.len() - 1]).unwrap();
+        let err = DatFile::load_index(&index_path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(index_path).unwrap();
+    }
+
+    #[test]
+    fn entry_flags_name_the_in_use_bit() {
+        assert!(EntryFlags::from_bits_retain(0x1).is_in_use());
+        assert!(EntryFlags::from_bits_retain(0x3).is_in_use());
+        assert!(!EntryFlags::from_bits_retain(0x2).is_in_use());
+        assert!(EntryFlags::from_bits_retain(0).is_empty());
+        assert_eq!(
+            (EntryFlags::IN_USE | EntryFlags::from_bits_retain(0x8)).bits(),
+            0x9
+        );
+
+        let mut archive = minimal_archive();
+        let entry_flag_offset = MINIMAL_MFT_OFFSET as usize + 3 * 24 + 14;
+        archive[entry_flag_offset..entry_flag_offset + 2].copy_from_slice(&3u16.to_le_bytes());
+        let dat_file = load(archive).unwrap();
+        assert_eq!(dat_file.mft_data[2].flags().bits(), 3);
+        assert!(dat_file.mft_data[2].flags().contains(EntryFlags::IN_USE));
+        assert!(!dat_file.mft_data[1].flags().is_in_use());
+    }
+
+    #[test]
+    fn find_textures_collects_texture_entries() {
+        let mut texture = b"ATEXDXT1".to_vec();
+        texture.extend_from_slice(&[4, 0, 4, 0]);
+        texture.extend_from_slice(&[0; 16]);
+
+        let mut builder = DatBuilder::new();
+        builder.add_entry(b"not a texture".to_vec(), 0);
+        let texture_base_id = builder.add_entry(compress_entry_data(&texture), 8);
+        builder.add_entry(compress_entry_data(b"ATEPnot one either"), 8);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let texture_index = texture_base_id as usize - 1;
+        assert_eq!(dat_file.find_textures(10), vec![texture_index]);
+        assert_eq!(dat_file.find_textures(0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn extract_with_crcs_recovers_one_crc_per_chunk() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 241) as u8).collect();
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(compress_entry_data(&data), 8);
+        builder.add_file_id(100, base_id);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let (raw_data, decompressed_data, crc_32c_data) = dat_file
+            .extract_mft_data_with_crcs(ArchiveId::FileId, 100)
+            .unwrap();
+        assert_eq!(decompressed_data, data);
+        assert!(raw_data.len() > 2 * CHUNK_SIZE);
+        assert_eq!(crc_32c_data.len(), raw_data.len().div_ceil(CHUNK_SIZE));
+        for (chunk_index, &(position, crc_value)) in crc_32c_data.iter().enumerate() {
+            let piece = &raw_data[chunk_index * CHUNK_SIZE..position as usize];
+            assert_eq!(crc32c(piece), crc_value);
+        }
+        assert_eq!(
+            dat_file.mft_data[base_id as usize - 1].crc_32c_data,
+            crc_32c_data
+        );
+    }
+
+    #[test]
+    fn compression_flag_histogram_counts_each_flag() {
+        let mut builder = DatBuilder::new();
+        builder.add_entry(b"stored".to_vec(), 0);
+        builder.add_entry(compress_entry_data(b"one"), 8);
+        builder.add_entry(compress_entry_data(b"two"), 8);
+        builder.add_entry(b"odd flag".to_vec(), 3);
+        let dat_file = load(builder.build()).unwrap();
+
+        // The header, MFT and index rows are stored uncompressed
+        let histogram = dat_file.compression_flag_histogram();
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [(0, 4), (3, 1), (8, 2)]
+        );
+    }
+
+    #[test]
+    fn mapped_archive_reads_match_the_buffered_reader() {
+        let mut texture = b"ATEXDXT1".to_vec();
+        texture.extend_from_slice(&[4, 0, 4, 0]);
+        texture.extend_from_slice(&[0; 16]);
+        let mut builder = DatBuilder::new();
+        for entry in 0..4u32 {
+            let data: Vec<u8> = (0..entry * 40_000 + 7).map(|i| (i % 239) as u8).collect();
+            builder.add_entry(compress_entry_data(&data), 8);
+            builder.add_entry(data, 0);
+        }
+        builder.add_entry(compress_entry_data(&texture), 8);
+        // No .dat extension, so the options must reach the mapped load too
+        let path = std::env::temp_dir().join(format!("tarir-{}-mapped.bin", std::process::id()));
+        std::fs::write(&path, builder.build()).unwrap();
+        let options = LoadOptions {
+            check_extension: false,
+        };
+
+        let mut buffered = DatFile::load_with_options(&path, options).unwrap();
+        let mut mapped = DatFile::load_mmap_with_options(&path, options).unwrap();
+        assert!(mapped.mapped_data.is_some());
+        assert!(DatFile::load_mmap(&path).is_err());
+
+        for index in 0..buffered.mft_data.len() {
+            assert_eq!(
+                mapped.extract_by_index(index).unwrap(),
+                buffered.extract_by_index(index).unwrap()
+            );
+            assert_eq!(
+                mapped.uncompressed_size(index).unwrap(),
+                buffered.uncompressed_size(index).unwrap()
+            );
+        }
+        assert_eq!(mapped.find_textures(10), buffered.find_textures(10));
+        assert_eq!(mapped.find_textures(10).len(), 1);
+        assert_eq!(
+            mapped.texture_format_histogram(usize::MAX),
+            buffered.texture_format_histogram(usize::MAX)
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn extraction_timeout_stops_large_entries() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(compress_entry_data(&data), 8);
+        builder.add_file_id(100, base_id);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        dat_file.extraction_timeout = Some(std::time::Duration::ZERO);
+        let err = dat_file
+            .extract_mft_data(ArchiveId::FileId, 100)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        dat_file.extraction_timeout = None;
+        let (_, decompressed_data) = dat_file.extract_mft_data(ArchiveId::FileId, 100).unwrap();
+        assert_eq!(decompressed_data, data);
+    }
+
+    #[test]
+    fn bulk_mft_parsing_matches_reading_field_by_field() {
+        let mut archive = minimal_archive();
+        let first_row = MINIMAL_MFT_OFFSET as usize + 24;
+        for row in 0..3 {
+            let row_start = first_row + row * 24;
+            archive[row_start + 14..row_start + 16]
+                .copy_from_slice(&(row as u16 + 1).to_le_bytes());
+            archive[row_start + 16..row_start + 20]
+                .copy_from_slice(&(0x1000 * row as u32 + 7).to_le_bytes());
+            archive[row_start + 20..row_start + 24]
+                .copy_from_slice(&(0xDEAD_0000 | row as u32).to_le_bytes());
+        }
+        let dat_file = load(archive.clone()).unwrap();
+
+        let mut reader = Cursor::new(&archive[first_row..]);
+        for mft_entry in &dat_file.mft_data {
+            assert_eq!(mft_entry.offset, reader.read_u64::<LittleEndian>().unwrap());
+            assert_eq!(mft_entry.size, reader.read_u32::<LittleEndian>().unwrap());
+            assert_eq!(
+                mft_entry.compression_flag,
+                reader.read_u16::<LittleEndian>().unwrap()
+            );
+            assert_eq!(
+                mft_entry.entry_flag,
+                reader.read_u16::<LittleEndian>().unwrap()
+            );
+            assert_eq!(
+                mft_entry.counter,
+                reader.read_u32::<LittleEndian>().unwrap()
+            );
+            assert_eq!(mft_entry.crc, reader.read_u32::<LittleEndian>().unwrap());
+        }
+        assert_eq!(dat_file.mft_data.len(), 3);
+        assert_eq!(dat_file.mft_data[2].crc, 0xDEAD_0002);
+    }
+
+    #[test]
+    fn identifier_str_escapes_non_printable_bytes() {
+        let dat_file = load(minimal_archive()).unwrap();
+        assert_eq!(dat_file.dat_header.identifier_str(), "AN(");
+        assert_eq!(dat_file.mft_header.identifier_str(), "Mft\\x1A");
+        assert!(matches!(
+            dat_file.dat_header.identifier_str(),
+            Cow::Borrowed(_)
+        ));
+
+        assert_eq!(identifier_string(b"\x00ab\xFF"), "\\x00ab\\xFF");
+    }
+
+    #[test]
+    fn extract_by_index_matches_the_file_id_row() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+
+        let index = dat_file.entry_info(ArchiveId::FileId, 104).unwrap().index;
+        assert_eq!(
+            dat_file.extract_by_index(index).unwrap(),
+            dat_file.extract_mft_data(ArchiveId::FileId, 104).unwrap()
+        );
+        assert_eq!(dat_file.extract_by_index(index).unwrap().1, contents[4]);
+
+        let err = dat_file
+            .extract_by_index(dat_file.mft_data.len())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn multi_chunk_textures_decode_after_extraction() {
+        // 512x256 DXT1 is 8192 blocks; block `i` is a solid color with `i` as its RGB565 value
+        let (width, height) = (512u16, 256u16);
+        let block_count = 8192u32;
+        let mut texture = b"ATEXDXT1".to_vec();
+        texture.extend_from_slice(&width.to_le_bytes());
+        texture.extend_from_slice(&height.to_le_bytes());
+        texture.extend_from_slice(&(block_count * 8 + 8).to_le_bytes());
+        texture.extend_from_slice(&0u32.to_le_bytes());
+        // With no compression flags every block is raw: all first words, then all second words
+        for block in 0..block_count {
+            texture.extend_from_slice(&block.to_le_bytes());
+        }
+        texture.extend_from_slice(&vec![0; block_count as usize * 4]);
+
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(compress_entry_data(&texture), 8);
+        builder.add_file_id(100, base_id);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 100).unwrap();
+        assert!(raw_data.len() > CHUNK_SIZE);
+        assert_eq!(decompressed_data, texture);
+
+        let (_, _, pixels) = texture_decompress::decode_to_rgba8(&decompressed_data).unwrap();
+        for block in [0, 1, 127, 128, 4095, 8191] {
+            let (block_x, block_y) = ((block % 128) * 4, (block / 128) * 4);
+            let offset = (block_y * width as usize + block_x) * 4;
+            let color = block as u16;
+            let (red, green, blue) = (color >> 11, (color >> 5) & 0x3F, color & 0x1F);
+            let expected = [
+                ((red << 3) | (red >> 2)) as u8,
+                ((green << 2) | (green >> 4)) as u8,
+                ((blue << 3) | (blue >> 2)) as u8,
+                0xFF,
+            ];
+            assert_eq!(pixels[offset..offset + 4], expected, "block {}", block);
+        }
+    }
+
+    #[test]
+    fn extract_entry_reports_whether_it_was_compressed() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+
+        let compressed = dat_file.extract_entry(ArchiveId::FileId, 100).unwrap();
+        assert!(compressed.was_compressed);
+        assert_eq!(compressed.decompressed_data, contents[0]);
+        assert_ne!(compressed.raw_data, compressed.decompressed_data);
+
+        let stored = dat_file.extract_entry(ArchiveId::FileId, 101).unwrap();
+        assert!(!stored.was_compressed);
+        assert_eq!(stored.decompressed_data, contents[1]);
+        assert_eq!(stored.raw_data, stored.decompressed_data);
+        assert_eq!(stored.name, "file_101");
+    }
+
+    #[test]
+    fn validate_all_collects_corrupt_entries() {
+        // A stream whose first Huffman tree declares 300 symbols
+        let corrupt: Vec<u8> = [0u32, 10, 300 << 8, 0]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let mut builder = DatBuilder::new();
+        builder.add_entry(compress_entry_data(b"fine"), 8);
+        let corrupt_base_id = builder.add_entry(corrupt, 8);
+        builder.add_entry(compress_entry_data(b"also fine"), 8);
+        builder.add_entry(b"stored".to_vec(), 0);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let failures = dat_file.validate_all();
+        assert_eq!(failures.len(), 1);
+        let (index, err) = &failures[0];
+        assert_eq!(*index, corrupt_base_id as usize - 1);
+        assert_eq!(
+            dat_decompress::DecompressError::from_io(err),
+            Some(dat_decompress::DecompressError::TooManySymbols { count: 300 })
+        );
+    }
+
+    #[test]
+    fn entries_past_the_end_of_the_archive_are_invalid_data() {
+        let entry_row = MINIMAL_MFT_OFFSET as usize + 3 * MFT_ENTRY_BYTES as usize;
+        let mut archive = minimal_archive();
+        let file_size = archive.len() as u64;
+        archive[entry_row + 8..entry_row + 12].copy_from_slice(&(file_size as u32).to_le_bytes());
+        let mut dat_file = load(archive).unwrap();
+
+        let err = dat_file.extract_mft_data(ArchiveId::FileId, 7).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "MFT entry 2 at offset 40 with {} bytes runs past the end of the archive ({} bytes).",
+                file_size, file_size
+            )
+        );
+
+        // An offset near u64::MAX must not overflow
+        let mut archive = minimal_archive();
+        archive[entry_row..entry_row + 8].copy_from_slice(&(u64::MAX - 2).to_le_bytes());
+        let mut dat_file = load(archive).unwrap();
+        let err = dat_file.extract_by_index(2).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("MFT entry 2 "));
+    }
+
+    #[test]
+    fn every_read_path_rejects_entries_past_the_end_the_same_way() {
+        let entry_row = MINIMAL_MFT_OFFSET as usize + 3 * MFT_ENTRY_BYTES as usize;
+        let mut archive = minimal_archive();
+        let file_size = archive.len() as u32;
+        archive[entry_row + 8..entry_row + 12].copy_from_slice(&file_size.to_le_bytes());
+        let path = write_temp_archive("past-the-end", &archive);
+        let expected = format!(
+            "MFT entry 2 at offset 40 with {} bytes runs past the end of the archive ({} bytes).",
+            file_size, file_size
+        );
+
+        for mut dat_file in [
+            DatFile::load(&path).unwrap(),
+            DatFile::load_mmap(&path).unwrap(),
+        ] {
+            let errors = [
+                dat_file.extract_by_index(2).unwrap_err(),
+                dat_file.extract_by_index_reopened(2).unwrap_err(),
+                dat_file
+                    .extract_mft_data_with_crcs(ArchiveId::FileId, 7)
+                    .unwrap_err(),
+                dat_file.extract_many(&[2]).pop().unwrap().unwrap_err(),
+            ];
+            for err in errors {
+                assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+                assert_eq!(err.to_string(), expected);
+            }
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn panicked_workers_fail_their_entries() {
+        let panicked: std::thread::Result<_> = Err(Box::new("worker panicked"));
+        let results = collect_worker_results(
+            3,
+            [Ok(vec![(2, Ok(vec![2])), (0, Ok(vec![0]))]), panicked].into_iter(),
+        );
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &[0]);
+        assert_eq!(results[2].as_ref().unwrap(), &[2]);
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(err.to_string().contains("panicked"));
+    }
+
+    #[test]
+    fn entry_names_follow_the_requested_id() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+
+        let by_file_id = dat_file.extract_entry(ArchiveId::FileId, 102).unwrap();
+        assert_eq!(by_file_id.name, "file_102");
+        // The first built entry has base ID 4, so file ID 102 maps to base ID 6
+        let by_base_id = dat_file.extract_entry(ArchiveId::BaseId, 6).unwrap();
+        assert_eq!(by_base_id.name, "base_6");
+        assert_eq!(by_base_id.decompressed_data, contents[2]);
+        assert_eq!(by_file_id.decompressed_data, by_base_id.decompressed_data);
+    }
+
+    #[test]
+    fn custom_crc_stride_strips_the_expected_bytes() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i % 7) as u8).collect();
+        let compressed = compress_entry_data(&data);
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(compressed.clone(), 8);
+        builder.add_file_id(1, base_id).chunk_size(64);
+        let mut dat_file = load(builder.build()).unwrap();
+        assert_eq!(dat_file.crc_chunk_size, 64);
+
+        let (raw_data, decompressed_data) =
+            dat_file.extract_mft_data(ArchiveId::FileId, 1).unwrap();
+        assert_eq!(strip_chunk_crcs(&raw_data, 64).unwrap(), compressed);
+        assert_eq!(decompressed_data, data);
+
+        // With the default stride the CRCs stay in the stream and it no longer decodes to the data
+        dat_file.crc_chunk_size = CHUNK_SIZE;
+        if let Ok((_, decompressed_data)) = dat_file.extract_mft_data(ArchiveId::FileId, 1) {
+            assert_ne!(decompressed_data, data);
+        }
+    }
+
+    #[test]
+    fn raw_index_bytes_reads_the_whole_index_table() {
+        let mut dat_file = load(minimal_archive()).unwrap();
+
+        let index_bytes = dat_file.raw_index_bytes().unwrap();
+        assert_eq!(
+            index_bytes.len(),
+            dat_file.mft_data[MFT_ENTRY_INDEX_NUM].size as usize
+        );
+        assert_eq!(index_bytes, [7, 0, 0, 0, 3, 0, 0, 0]);
+
+        // Without an index table row there is nothing to read
+        dat_file.mft_data.truncate(MFT_ENTRY_INDEX_NUM);
+        let err = dat_file.raw_index_bytes().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn header_crc_matches_the_builder_header() {
+        let mut builder = DatBuilder::new();
+        builder.add_entry(b"data".to_vec(), 0);
+        let archive = builder.build();
+        let dat_file = load(archive.clone()).unwrap();
+        assert_eq!(dat_file.dat_header.crc, crc32c(&archive[..16]));
+        assert!(dat_file.verify_header_crc());
+
+        // Any change to the covered bytes breaks the match
+        let mut archive = archive;
+        archive[12] ^= 1;
+        let dat_file = load(archive).unwrap();
+        assert!(!dat_file.verify_header_crc());
+
+        // The hand-written archive stores no CRC at all
+        assert!(!load(minimal_archive()).unwrap().verify_header_crc());
+    }
+
+    #[test]
+    fn unexpected_chunk_size_is_logged_as_a_warning() {
+        crate::test_logger::install();
+
+        assert_eq!(crc_chunk_size_for_header(0x2345), 0x2345);
+        assert_eq!(
+            crate::test_logger::levels_of(
+                "Unexpected DAT chunk size 9029; using it as the CRC chunk stride."
+            ),
+            [log::Level::Warn]
+        );
+    }
+
+    #[test]
+    fn texture_format_histogram_counts_each_fourcc() {
+        let texture = |fourcc: &[u8; 4]| {
+            let mut data = b"ATEX".to_vec();
+            data.extend_from_slice(fourcc);
+            data.extend_from_slice(&[4, 0, 4, 0]);
+            data.extend_from_slice(&[0; 16]);
+            data
+        };
+        let mut builder = DatBuilder::new();
+        builder.add_entry(texture(b"DXT5"), 0);
+        builder.add_entry(compress_entry_data(&texture(b"3DCX")), 8);
+        builder.add_entry(b"not a texture".to_vec(), 0);
+        builder.add_entry(compress_entry_data(&texture(b"DXT5")), 8);
+        builder.add_entry(texture(b"ZZZZ"), 0);
+        let mut dat_file = load(builder.build()).unwrap();
+
+        let histogram = dat_file.texture_format_histogram(usize::MAX);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [
+                ("3DCX".to_string(), 1),
+                ("DXT5".to_string(), 2),
+                ("ZZZZ".to_string(), 1),
+            ]
+        );
+
+        // The three header rows come first, so only the first built entry is scanned
+        let histogram = dat_file.texture_format_histogram(4);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [("DXT5".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn base_id_zero_is_an_error_not_a_panic() {
+        // The index table row of file ID 7 starts at byte 45, its base ID 4 bytes later
+        let mut archive = minimal_archive();
+        archive[49..53].copy_from_slice(&0u32.to_le_bytes());
+        let mut dat_file = load(archive).unwrap();
+        assert_eq!(dat_file.mft_index_data[0].base_id, 0);
+
+        let err = dat_file.extract_mft_data(ArchiveId::FileId, 7).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Index table maps ID 7 to base ID 0, which is not a row of the 3-entry MFT."
+        );
+        let err = dat_file.extract_mft_data(ArchiveId::BaseId, 0).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(dat_file.resolve_index(ArchiveId::FileId, 7), None);
+        assert!(dat_file.entry_info(ArchiveId::FileId, 7).is_err());
+    }
+
+    /// Hands out at most 5 bytes per read, to exercise line assembly across short reads.
+    struct ShortReads<'a>(&'a [u8]);
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(5).min(self.0.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn hex_dump_stream_matches_the_in_memory_dump() {
+        let buffer: Vec<u8> = (0..75u8).map(|i| i.wrapping_mul(37)).collect();
+
+        for max_lines in [1, 3, 16] {
+            let mut streamed = Vec::new();
+            hex_dump_stream(ShortReads(&buffer), &mut streamed, 16, max_lines).unwrap();
+            assert_eq!(
+                String::from_utf8(streamed).unwrap(),
+                hex_dump_string(&buffer, 16, max_lines)
+            );
+        }
+
+        // A buffer ending on a line boundary has no trailing empty line
+        let mut streamed = Vec::new();
+        hex_dump_stream(&buffer[..64], &mut streamed, 16, 16).unwrap();
+        assert_eq!(
+            String::from_utf8(streamed).unwrap(),
+            hex_dump_string(&buffer[..64], 16, 16)
+        );
+        assert_eq!(hex_dump_string(&buffer[..64], 16, 16).lines().count(), 4);
+    }
+
+    #[test]
+    fn duplicate_groups_share_crc_and_size() {
+        let mut builder = DatBuilder::new();
+        for data in [&b"same"[..], b"diff", b"same", b"longer", b"same"] {
+            builder.add_entry(data.to_vec(), 0);
+        }
+        let mut dat_file = load(builder.build()).unwrap();
+        // The builder writes no row CRCs, so give each entry the CRC of its contents
+        for index in 3..dat_file.mft_data.len() {
+            let (raw_data, _) = dat_file.extract_by_index(index).unwrap();
+            dat_file.mft_data[index].crc = crc32c(&raw_data);
+        }
+
+        assert_eq!(dat_file.duplicate_groups(), [vec![3, 5, 7]]);
+
+        // Matching size alone is not enough
+        dat_file.mft_data[5].crc ^= 1;
+        assert_eq!(dat_file.duplicate_groups(), [vec![3, 7]]);
+    }
+
+    #[test]
+    fn read_raw_entry_returns_the_stored_bytes() {
+        let (archive, contents) = built_archive();
+        let mut dat_file = load(archive).unwrap();
+
+        for index in 0..dat_file.mft_data.len() {
+            let raw_data = dat_file.read_raw_entry(index).unwrap();
+            assert_eq!(raw_data.len(), dat_file.mft_data[index].size as usize);
+        }
+        // Compressed entries keep their chunk CRCs and bitstream; stored ones are the data itself
+        let compressed = dat_file.read_raw_entry(3).unwrap();
+        assert_eq!(compressed, dat_file.extract_by_index(3).unwrap().0);
+        assert_ne!(compressed, contents[0]);
+        assert_eq!(dat_file.read_raw_entry(4).unwrap(), contents[1]);
+
+        let err = dat_file
+            .read_raw_entry(dat_file.mft_data.len())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    /// `minimal_archive` rewritten with `flag` set to `MFT_OFFSET_32_FLAG` and 20-byte MFT rows.
+    fn minimal_archive_32() -> Vec<u8> {
+        let archive = minimal_archive();
+        let mft_offset = MINIMAL_MFT_OFFSET as usize;
+        let mut archive_32 = archive[..mft_offset + MFT_ENTRY_BYTES as usize].to_vec();
+        archive_32[32..36]
+            .copy_from_slice(&(MFT_ENTRY_BYTES + 3 * MFT_ENTRY_BYTES_32).to_le_bytes());
+        archive_32[36..40].copy_from_slice(&MFT_OFFSET_32_FLAG.to_le_bytes());
+        for row in
+            archive[mft_offset + MFT_ENTRY_BYTES as usize..].chunks_exact(MFT_ENTRY_BYTES as usize)
+        {
+            // Keep the low half of the offset and everything after it
+            archive_32.extend_from_slice(&row[..4]);
+            archive_32.extend_from_slice(&row[8..]);
+        }
+        archive_32
+    }
+
+    #[test]
+    fn header_flag_selects_the_mft_offset_width() {
+        // 64-bit rows: only a 64-bit read sees the high half of the offset, and low flag bits are ignored
+        let entry_row = MINIMAL_MFT_OFFSET as usize + 3 * MFT_ENTRY_BYTES as usize;
+        for flag in [0u32, 1] {
+            let mut archive = minimal_archive();
+            archive[36..40].copy_from_slice(&flag.to_le_bytes());
+            archive[entry_row..entry_row + 8].copy_from_slice(&0x1_0000_0028u64.to_le_bytes());
+            let dat_file = load(archive).unwrap();
+
+            assert!(!dat_file.dat_header.has_32_bit_offsets());
+            assert_eq!(dat_file.dat_header.mft_entry_bytes(), MFT_ENTRY_BYTES);
+            assert_eq!(dat_file.mft_data[2].offset, 0x1_0000_0028);
+            assert_eq!(dat_file.mft_data[2].size, 5);
+        }
+
+        // 32-bit rows parse to the same entries as the 64-bit original
+        let mut original = load(minimal_archive()).unwrap();
+        let mut dat_file = load(minimal_archive_32()).unwrap();
+        assert!(dat_file.dat_header.has_32_bit_offsets());
+        assert_eq!(dat_file.dat_header.mft_entry_bytes(), MFT_ENTRY_BYTES_32);
+        assert_eq!(dat_file.mft_data.len(), original.mft_data.len());
+        for (row_32, row_64) in dat_file.mft_data.iter().zip(&original.mft_data) {
+            assert_eq!((row_32.offset, row_32.size), (row_64.offset, row_64.size));
+        }
+        assert_eq!(dat_file.mft_index_data[0].file_id, 7);
+        assert_eq!(
+            dat_file.extract_mft_data(ArchiveId::FileId, 7).unwrap(),
+            original.extract_mft_data(ArchiveId::FileId, 7).unwrap()
+        );
+
+        // Read as 64-bit rows, the same bytes no longer fit the declared MFT size
+        let mut archive = minimal_archive_32();
+        archive[36..40].copy_from_slice(&0u32.to_le_bytes());
+        let err = load(archive).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
     }
-    result
 }
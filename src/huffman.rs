@@ -0,0 +1,343 @@
+use crate::bitreader::{StateData, drop_bits, read_bits};
+use crate::dat_decompress::DecompressError;
+
+pub(crate) const MAX_BITS_HASH: usize = 8;
+pub(crate) const MAX_CODE_BITS_LENGTH: usize = 32;
+pub(crate) const MAX_SYMBOL_VALUE: usize = 285;
+
+/// A canonical Huffman decoding table shared by the DAT and texture decompressors.
+///
+/// Codes of up to `MAX_BITS_HASH` bits are looked up directly; longer codes are found by comparison.
+#[derive(Debug, Clone)]
+pub(crate) struct HuffmanTree {
+    pub(crate) code_comparison: [u32; MAX_CODE_BITS_LENGTH],
+    pub(crate) symbol_value_offset: [u16; MAX_CODE_BITS_LENGTH],
+    pub(crate) code_bits: [u8; MAX_CODE_BITS_LENGTH],
+    pub(crate) symbol_value: [u16; MAX_SYMBOL_VALUE],
+    pub(crate) symbol_value_hash_exist: [bool; 1 << MAX_BITS_HASH],
+    pub(crate) symbol_value_hash: [u16; 1 << MAX_BITS_HASH],
+    pub(crate) code_bits_hash: [u8; 1 << MAX_BITS_HASH],
+}
+
+impl Default for HuffmanTree {
+    fn default() -> Self {
+        HuffmanTree {
+            code_comparison: [0; MAX_CODE_BITS_LENGTH],
+            symbol_value_offset: [0; MAX_CODE_BITS_LENGTH],
+            code_bits: [0; MAX_CODE_BITS_LENGTH],
+            symbol_value: [0; MAX_SYMBOL_VALUE],
+            symbol_value_hash_exist: [false; 1 << MAX_BITS_HASH],
+            symbol_value_hash: [0; 1 << MAX_BITS_HASH],
+            code_bits_hash: [0; 1 << MAX_BITS_HASH],
+        }
+    }
+}
+
+/// Symbols grouped by code length, as linked lists, before `build_huffmantree` turns them into a `HuffmanTree`.
+#[derive(Debug)]
+pub(crate) struct HuffmanTreeBuilder {
+    pub(crate) bits_head_exist: [bool; MAX_CODE_BITS_LENGTH],
+    pub(crate) bits_head: [u16; MAX_CODE_BITS_LENGTH],
+    pub(crate) bits_body_exist: [bool; MAX_SYMBOL_VALUE],
+    pub(crate) bits_body: [u16; MAX_SYMBOL_VALUE],
+}
+
+impl Default for HuffmanTreeBuilder {
+    fn default() -> Self {
+        HuffmanTreeBuilder {
+            bits_head_exist: [false; MAX_CODE_BITS_LENGTH],
+            bits_head: [0; MAX_CODE_BITS_LENGTH],
+            bits_body_exist: [false; MAX_SYMBOL_VALUE],
+            bits_body: [0; MAX_SYMBOL_VALUE],
+        }
+    }
+}
+
+pub(crate) fn read_code(
+    huffmantree_data: &HuffmanTree,
+    state_data: &mut StateData,
+    symbol_data: &mut u16,
+) -> std::io::Result<()> {
+    let index_num = read_bits(state_data, MAX_BITS_HASH as u8)? as usize;
+
+    let exist = huffmantree_data.symbol_value_hash_exist[index_num];
+
+    if exist {
+        *symbol_data = huffmantree_data.symbol_value_hash
+            [read_bits(state_data, MAX_BITS_HASH as u8)? as usize];
+
+        let code_bits_hash =
+            huffmantree_data.code_bits_hash[read_bits(state_data, MAX_BITS_HASH as u8)? as usize];
+
+        drop_bits(state_data, code_bits_hash)?;
+    } else {
+        let code_value = read_bits(state_data, 32)?;
+        let index_data = huffmantree_data
+            .code_comparison
+            .iter()
+            .position(|&code_comparison| code_value >= code_comparison)
+            .ok_or(DecompressError::InvalidCode {
+                context: "Huffman code",
+                value: code_value,
+            })?;
+
+        let temp_bits: u8 = huffmantree_data.code_bits[index_data];
+
+        // Step 1: Read 32 bits from state_data
+        let read_bits_value = read_bits(state_data, 32)?;
+
+        // Step 2: Subtract code_comparison from read_bits_value (with wrapping)
+        let adjusted_bits =
+            read_bits_value.wrapping_sub(huffmantree_data.code_comparison[index_data]);
+
+        // Step 3: Perform the right shift operation (with wrapping)
+        let shifted_bits = adjusted_bits.wrapping_shr((32 - temp_bits as u16) as u32);
+
+        // Step 4: Subtract the shifted value from the symbol_value_offset (with wrapping)
+        let symbol_index = huffmantree_data.symbol_value_offset[index_data]
+            .wrapping_sub(shifted_bits as u16) as usize;
+
+        // Step 5: Retrieve the symbol_data using the calculated index
+        *symbol_data = *huffmantree_data.symbol_value.get(symbol_index).ok_or(
+            DecompressError::InvalidCode {
+                context: "Huffman symbol index",
+                value: symbol_index as u32,
+            },
+        )?;
+
+        drop_bits(state_data, temp_bits)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn add_symbol(
+    huffmantree_builder: &mut HuffmanTreeBuilder,
+    symbol_data: u16,
+    bit_data: u8,
+) -> std::io::Result<()> {
+    if bit_data as usize >= MAX_CODE_BITS_LENGTH || symbol_data as usize >= MAX_SYMBOL_VALUE {
+        return Err(DecompressError::InvalidCode {
+            context: "Huffman symbol",
+            value: symbol_data as u32,
+        }
+        .into());
+    }
+
+    if huffmantree_builder.bits_head_exist[bit_data as usize] {
+        huffmantree_builder.bits_body[symbol_data as usize] =
+            huffmantree_builder.bits_head[bit_data as usize];
+
+        huffmantree_builder.bits_body_exist[symbol_data as usize] = true;
+
+        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
+    } else {
+        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
+
+        huffmantree_builder.bits_head_exist[bit_data as usize] = true;
+    }
+    Ok(())
+}
+
+fn check_bits_head(huffmantree_builder: &mut HuffmanTreeBuilder) -> std::io::Result<bool> {
+    for head in huffmantree_builder.bits_head_exist {
+        if head {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+pub(crate) fn build_huffmantree(
+    huffmantree_data: &mut HuffmanTree,
+    huffmantree_builder: &mut HuffmanTreeBuilder,
+) -> std::io::Result<bool> {
+    if check_bits_head(huffmantree_builder)? {
+        return Ok(false);
+    }
+    *huffmantree_data = HuffmanTree::default();
+    let mut temp_code: u32 = 0;
+    let mut temp_bits: u8 = 0;
+
+    // First part, filling hashTable for codes that are of less than 8 bits
+    while temp_bits <= MAX_BITS_HASH as u8 {
+        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
+
+        if data_exist {
+            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
+
+            while data_exist {
+                // Processing hash values
+                let mut hash_value: u16 = (temp_code << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
+                let next_hash_value: u16 =
+                    ((temp_code.wrapping_add(1)) << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
+
+                while hash_value < next_hash_value {
+                    huffmantree_data.symbol_value_hash_exist[hash_value as usize] = true;
+                    huffmantree_data.symbol_value_hash[hash_value as usize] = current_symbol;
+                    huffmantree_data.code_bits_hash[hash_value as usize] = temp_bits;
+                    hash_value = hash_value.wrapping_add(1);
+                }
+
+                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
+                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
+                temp_code = temp_code.wrapping_sub(1);
+            }
+        }
+
+        temp_code = (temp_code << 1) + 1;
+        temp_bits = temp_bits.wrapping_add(1);
+    }
+
+    let mut temp_code_comparison_index: u16 = 0;
+    let mut symbol_offset: u16 = 0;
+
+    // Second part, filling classical structure for other codes
+    while temp_bits < MAX_CODE_BITS_LENGTH as u8 {
+        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
+
+        if data_exist {
+            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
+
+            while data_exist {
+                // Registering the code
+                huffmantree_data.symbol_value[symbol_offset as usize] = current_symbol;
+
+                symbol_offset = symbol_offset.wrapping_add(1);
+                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
+                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
+
+                temp_code = temp_code.wrapping_sub(1);
+            }
+
+            // Minimum code value for temp_bits bits
+            huffmantree_data.code_comparison[temp_code_comparison_index as usize] =
+                temp_code.wrapping_add(1) << (32 - temp_bits);
+
+            // Number of bits for l_codeCompIndex index
+            huffmantree_data.code_bits[temp_code_comparison_index as usize] = temp_bits;
+
+            // Offset in symbol_value table to reach the value
+            huffmantree_data.symbol_value_offset[temp_code_comparison_index as usize] =
+                symbol_offset.wrapping_sub(1);
+
+            temp_code_comparison_index = temp_code_comparison_index.wrapping_add(1);
+        }
+
+        temp_code = (temp_code << 1) + 1;
+        temp_bits = temp_bits.wrapping_add(1);
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a tree from `(symbol, bits)` pairs, added in order.
+    fn tree(symbols: &[(u16, u8)]) -> HuffmanTree {
+        let mut huffmantree_builder = HuffmanTreeBuilder::default();
+        for &(symbol, bits) in symbols {
+            add_symbol(&mut huffmantree_builder, symbol, bits).unwrap();
+        }
+        let mut huffmantree_data = HuffmanTree::default();
+        assert!(build_huffmantree(&mut huffmantree_data, &mut huffmantree_builder).unwrap());
+        huffmantree_data
+    }
+
+    /// Decode one symbol from a stream whose first 32 bits are `head`; returns it and the bits left.
+    fn decode(huffmantree_data: &mut HuffmanTree, head: u32) -> std::io::Result<(u16, u8)> {
+        let input_data = head.to_le_bytes();
+        let mut state_data = StateData::new(&input_data)?;
+        let mut symbol_data = 0;
+        read_code(huffmantree_data, &mut state_data, &mut symbol_data)?;
+        Ok((symbol_data, state_data.bytes_available_data))
+    }
+
+    #[test]
+    fn short_and_long_codes_decode() {
+        // `1` for symbol 7 from the hash table, `0111111111` for symbol 200 by comparison
+        let mut huffmantree_data = tree(&[(7, 1), (200, 10)]);
+        assert_eq!(decode(&mut huffmantree_data, 1 << 31).unwrap(), (7, 31));
+        assert_eq!(
+            decode(&mut huffmantree_data, 0b01_1111_1111 << 22).unwrap(),
+            (200, 22)
+        );
+    }
+
+    #[test]
+    fn empty_builder_builds_no_tree() {
+        let mut huffmantree_builder = HuffmanTreeBuilder::default();
+        let mut huffmantree_data = HuffmanTree::default();
+        assert!(!build_huffmantree(&mut huffmantree_data, &mut huffmantree_builder).unwrap());
+    }
+
+    #[test]
+    fn codes_below_every_comparison_are_invalid() {
+        // No hash entries, and no comparison a code can reach
+        let mut huffmantree_data = HuffmanTree {
+            code_comparison: [u32::MAX; MAX_CODE_BITS_LENGTH],
+            ..Default::default()
+        };
+        let err = decode(&mut huffmantree_data, 0x1234_5678).unwrap_err();
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::InvalidCode {
+                context: "Huffman code",
+                value: 0x1234_5678,
+            })
+        );
+    }
+
+    #[test]
+    fn symbol_offsets_past_the_table_are_invalid() {
+        let mut huffmantree_data = HuffmanTree::default();
+        huffmantree_data.code_bits[0] = 9;
+        huffmantree_data.symbol_value_offset[0] = MAX_SYMBOL_VALUE as u16 + 15;
+        let err = decode(&mut huffmantree_data, 0).unwrap_err();
+        assert_eq!(
+            DecompressError::from_io(&err),
+            Some(DecompressError::InvalidCode {
+                context: "Huffman symbol index",
+                value: MAX_SYMBOL_VALUE as u32 + 15,
+            })
+        );
+    }
+
+    #[test]
+    fn add_symbol_rejects_out_of_range_symbols_and_lengths() {
+        let mut huffmantree_builder = HuffmanTreeBuilder::default();
+        add_symbol(
+            &mut huffmantree_builder,
+            MAX_SYMBOL_VALUE as u16 - 1,
+            MAX_CODE_BITS_LENGTH as u8 - 1,
+        )
+        .unwrap();
+
+        for (symbol, bits) in [
+            (MAX_SYMBOL_VALUE as u16, 1),
+            (u16::MAX, 1),
+            (3, MAX_CODE_BITS_LENGTH as u8),
+            (3, u8::MAX),
+        ] {
+            let err = add_symbol(&mut huffmantree_builder, symbol, bits).unwrap_err();
+            assert_eq!(
+                DecompressError::from_io(&err),
+                Some(DecompressError::InvalidCode {
+                    context: "Huffman symbol",
+                    value: symbol as u32,
+                })
+            );
+        }
+        // Rejected symbols leave the builder untouched
+        assert_eq!(
+            huffmantree_builder
+                .bits_head_exist
+                .iter()
+                .filter(|&&exist| exist)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,10 @@
+mod bitreader;
+#[cfg(any(test, feature = "test-util"))]
+pub mod dat_builder;
+pub mod dat_decompress;
+pub mod dat_parser;
+mod huffman;
+pub mod pf_parser;
+#[cfg(test)]
+mod test_logger;
+pub mod texture_decompress;
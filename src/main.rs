@@ -1,132 +1,511 @@
-use actix_web::{App, HttpResponse, HttpServer, Responder, web};
-use std::sync::Mutex;
+use actix_web::http::header::{self, ContentRangeSpec, Range};
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder, web};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::ImageFormat;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
 use tera::{Context, Tera};
 
-mod dat_decompress;
-mod dat_parser;
-mod pf_parser;
+use tarir::dat_parser::{ArchiveId, DatFile, ExtractedEntry, hex_dump_string};
+use tarir::pf_parser::PfFile;
+use tarir::texture_decompress;
 
-use dat_parser::{ArchiveId, DatFile, hex_dump};
+/// Directory the HTML templates are loaded from, relative to the working directory.
+const TEMPLATE_DIR: &str = "templates";
+
+/// Templates compiled into the binary, used when a file is missing from `TEMPLATE_DIR`.
+///
+/// Files in `TEMPLATE_DIR` take precedence, so templates can be edited without rebuilding.
+const EMBEDDED_TEMPLATES: [(&str, &str); 4] = [
+    ("index.html", include_str!("../templates/index.html")),
+    ("entries.html", include_str!("../templates/entries.html")),
+    (
+        "data_view_base_id.html",
+        include_str!("../templates/data_view_base_id.html"),
+    ),
+    (
+        "data_view_file_id.html",
+        include_str!("../templates/data_view_file_id.html"),
+    ),
+];
+
+/// Archives to load when neither `--archive` nor `ARCHIVE_PATHS_ENV` is given, by name.
+const DEFAULT_ARCHIVE_PATHS: [(&str, &str); 2] = [
+    (
+        "Gw2",
+        "/home/ridwan/.local/share/Steam/steamapps/common/Guild Wars 2/Gw2.dat",
+    ),
+    (
+        "Local",
+        "/home/ridwan/.local/share/Steam/steamapps/compatdata/1284210/pfx/drive_c/users/steamuser/AppData/Roaming/Guild Wars 2/Local.dat",
+    ),
+];
+/// Archive served by the routes that do not take an archive name.
+const DEFAULT_ARCHIVE: &str = "Gw2";
+/// Longest a single entry may take to decompress before the request fails, so one bad entry cannot hold a worker.
+const EXTRACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of MFT entries listed per page on the home page and by `/api/entries`.
+const ENTRIES_PER_PAGE: usize = 50;
+/// Largest raw data preview `/api/entries` returns per entry, in bytes.
+const MAX_PREVIEW_BYTES: u32 = 64;
+/// Largest combined raw and decompressed size `/api/extract` encodes without `force=true`, in bytes.
+const MAX_BASE64_BYTES: usize = 16 * 1024 * 1024;
+/// Entries included by `/export/tar` when no `limit` is given.
+const DEFAULT_EXPORT_LIMIT: usize = 100;
+/// Size of a tar header and of the blocks member data is padded to.
+const TAR_BLOCK_SIZE: usize = 512;
+/// Bytes of context `/api/diff` shows on each side of the first difference.
+const DIFF_CONTEXT_BYTES: usize = 8;
+/// Address the server listens on when neither `--bind` nor `BIND_ADDRESS_ENV` is given.
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8080";
+/// Environment variable holding the bind address; the `--bind` argument takes precedence.
+const BIND_ADDRESS_ENV: &str = "TARIR_BIND";
+/// Environment variable holding `name=path` archives separated by `;`; `--archive` arguments take precedence.
+const ARCHIVE_PATHS_ENV: &str = "TARIR_ARCHIVES";
 
 struct AppState {
-    dat_file: Mutex<Option<DatFile>>,
+    /// Loaded archives, keyed by the names given to `resolve_archive_paths`.
+    ///
+    /// Archives are read-only once loaded; each request reads entries through its own file handle.
+    archives: HashMap<String, DatFile>,
     tera: Tera,
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let file_path = "/home/ridwan/.local/share/Steam/steamapps/common/Guild Wars 2/Gw2.dat";
-    let server_address = "127.0.0.1:8080";
+    // Show info and above unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let server_address = resolve_bind_address(
+        std::env::args().skip(1),
+        std::env::var(BIND_ADDRESS_ENV).ok(),
+    )?;
+    let archive_paths = resolve_archive_paths(
+        std::env::args().skip(1),
+        std::env::var(ARCHIVE_PATHS_ENV).ok(),
+    )?;
 
-    // Initialize the shared state with the DAT file
-    let dat_file = DatFile::load(file_path).ok();
-    if dat_file.is_some() {
-        println!("DAT file loaded successfully from: {}", file_path);
-    } else {
-        println!("Failed to load DAT file from: {}", file_path);
+    // Initialize the shared state with the DAT files; archives that fail to load are skipped
+    let mut archives = HashMap::new();
+    for (name, file_path) in archive_paths {
+        match DatFile::load(&file_path) {
+            Ok(mut dat_file) => {
+                info!(
+                    "DAT file {} loaded successfully from: {}",
+                    name,
+                    file_path.display()
+                );
+                dat_file.extraction_timeout = Some(EXTRACTION_TIMEOUT);
+                archives.insert(name, dat_file);
+            }
+            Err(err) => error!(
+                "Failed to load DAT file {} from {}: {}",
+                name,
+                file_path.display(),
+                err
+            ),
+        }
     }
 
     // Initialize Tera templates
-    let tera = Tera::new("templates/**/*").expect("Error initializing Tera templates");
+    let tera = load_templates();
 
-    let app_state = web::Data::new(AppState {
-        dat_file: Mutex::new(dat_file),
-        tera,
-    });
+    let app_state = web::Data::new(AppState { archives, tera });
 
     // Start the Actix Web server
-    println!("Starting server at: {}\n", server_address);
-    // Print each route's address and description
-    println!(
-        "Route: {}/ (GET) - Home page, returns the main interface of the server.",
-        server_address
+    info!("Starting server at: {}", server_address);
+    // Log each route's address and description
+    info!(
+        "Route: {}/?page={{page}} (GET) - Home page, lists the entries of the default archive {} per page.",
+        server_address, ENTRIES_PER_PAGE
     );
-    println!(
+    info!(
         "Route: {}/extract/base_id/{{index_number}} (GET) - Extracts data using the base ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
         "Route: {}/extract/file_id/{{index_number}} (GET) - Extracts data using the file ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
+        "Route: {}/extract/mft/{{index}} (GET) - Downloads decompressed data of the MFT row {{index}}, without resolving an ID.",
+        server_address
+    );
+    info!(
+        "Route: {}/hex/file_id/{{index_number}}?max_lines={{max_lines}} (GET) - Returns a plain text hex dump of the decompressed data using the file ID: {{index_number}}.",
+        server_address
+    );
+    info!(
         "Route: {}/download/compressed/base_id/{{index_number}} (GET) - Downloads compressed data using the base ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
         "Route: {}/download/compressed/file_id/{{index_number}} (GET) - Downloads compressed data using the file ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
         "Route: {}/download/decompressed/base_id/{{index_number}} (GET) - Downloads decompressed data using the base ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
         "Route: {}/download/decompressed/file_id/{{index_number}} (GET) - Downloads decompressed data using the file ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
+        "Route: {}/download/dds/file_id/{{index_number}} (GET) - Downloads a texture as a DDS file using the file ID: {{index_number}}.",
+        server_address
+    );
+    info!(
         "Route: {}/convert_to_image/base_id/{{index_number}} (GET) - Converts data to image using the base ID: {{index_number}}.",
         server_address
     );
-    println!(
+    info!(
         "Route: {}/convert_to_image/file_id/{{index_number}} (GET) - Converts data to image using the file ID: {{index_number}}.",
         server_address
     );
+    info!(
+        "Route: {}/archives/{{name}}/extract/base_id/{{index_number}} (GET) - Downloads decompressed data from the named archive using the base ID: {{index_number}}.",
+        server_address
+    );
+    info!(
+        "Route: {}/archives/{{name}}/extract/file_id/{{index_number}} (GET) - Downloads decompressed data from the named archive using the file ID: {{index_number}}.",
+        server_address
+    );
+    info!(
+        "Route: {}/pf/file_id/{{index_number}}/chunk/{{fourcc}} (GET) - Downloads one chunk of a PF file using the file ID: {{index_number}}.",
+        server_address
+    );
+    info!(
+        "Route: {}/api/header (GET) - Returns the parsed DAT and MFT headers as JSON.",
+        server_address
+    );
+    info!(
+        "Route: {}/api/entries?page={{page}}&preview={{bytes}} (GET) - Lists {} entries per page as JSON, optionally with a hex preview of up to {} raw bytes each.",
+        server_address, ENTRIES_PER_PAGE, MAX_PREVIEW_BYTES
+    );
+    info!(
+        "Route: {}/api/extract/file_id/{{index_number}}?encoding=base64&force={{force}} (GET) - Returns the raw and decompressed data as base64 JSON using the file ID: {{index_number}}.",
+        server_address
+    );
+    info!(
+        "Route: {}/api/diff?a={{file_id}}&b={{file_id}} (GET) - Compares the decompressed data of two file IDs and returns the first differing offset with {} bytes of hex context on each side.",
+        server_address, DIFF_CONTEXT_BYTES
+    );
+    info!(
+        "Route: {}/api/summary (GET) - Returns entry counts, total stored size, the archive version, file size and modification time as JSON.",
+        server_address
+    );
+    info!(
+        "Route: {}/export/tar?limit={{limit}} (GET) - Streams a tar archive of decompressed entries named by file ID, {} entries unless a limit is given.",
+        server_address, DEFAULT_EXPORT_LIMIT
+    );
+    info!(
+        "Route: {}/healthz (GET) - Reports whether the DAT file is loaded and how many entries it has.",
+        server_address
+    );
 
     HttpServer::new(move || {
-        let app = App::new()
+        App::new()
             .app_data(app_state.clone())
-            .route("/", web::get().to(index))
-            .route(
-                "/extract/base_id/{index_number}",
-                web::get().to(extract_data_base_id),
-            )
-            .route(
-                "/extract/file_id/{index_number}",
-                web::get().to(extract_data_file_id),
-            )
-            .route(
-                "/download/compressed/base_id/{index_number}",
-                web::get().to(download_compressed_data_base_id),
-            )
-            .route(
-                "/download/compressed/file_id/{index_number}",
-                web::get().to(download_compressed_data_file_id),
-            )
-            .route(
-                "/download/decompressed/base_id/{index_number}",
-                web::get().to(download_decompressed_data_base_id),
-            )
-            .route(
-                "/download/decompressed/file_id/{index_number}",
-                web::get().to(download_decompressed_data_file_id),
-            )
-            .route(
-                "/convert_to_image/base_id/{index_number}",
-                web::get().to(convert_to_image_base_id),
-            )
-            .route(
-                "/convert_to_image/file_id/{index_number}",
-                web::get().to(convert_to_image_file_id),
-            );
-
-        app
+            .configure(configure_routes)
     })
     .bind(server_address)?
     .run()
     .await
 }
 
-async fn index(data: web::Data<AppState>) -> impl Responder {
+/// Register every route of the server.
+fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index))
+        .route(
+            "/extract/base_id/{index_number}",
+            web::get().to(extract_data_base_id),
+        )
+        .route(
+            "/extract/file_id/{index_number}",
+            web::get().to(extract_data_file_id),
+        )
+        .route(
+            "/extract/mft/{index}",
+            web::get().to(extract_data_mft_index),
+        )
+        .route(
+            "/hex/file_id/{index_number}",
+            web::get().to(hex_dump_file_id),
+        )
+        .route(
+            "/download/compressed/base_id/{index_number}",
+            web::get().to(download_compressed_data_base_id),
+        )
+        .route(
+            "/download/compressed/file_id/{index_number}",
+            web::get().to(download_compressed_data_file_id),
+        )
+        .route(
+            "/download/decompressed/base_id/{index_number}",
+            web::get().to(download_decompressed_data_base_id),
+        )
+        .route(
+            "/download/decompressed/file_id/{index_number}",
+            web::get().to(download_decompressed_data_file_id),
+        )
+        .route(
+            "/download/dds/file_id/{index_number}",
+            web::get().to(download_dds_file_id),
+        )
+        .route(
+            "/convert_to_image/base_id/{index_number}",
+            web::get().to(convert_to_image_base_id),
+        )
+        .route(
+            "/convert_to_image/file_id/{index_number}",
+            web::get().to(convert_to_image_file_id),
+        )
+        .route(
+            "/archives/{name}/extract/base_id/{index_number}",
+            web::get().to(extract_archive_data_base_id),
+        )
+        .route(
+            "/archives/{name}/extract/file_id/{index_number}",
+            web::get().to(extract_archive_data_file_id),
+        )
+        .route(
+            "/pf/file_id/{index_number}/chunk/{fourcc}",
+            web::get().to(extract_pf_chunk_file_id),
+        )
+        .route("/api/header", web::get().to(api_header))
+        .route("/api/summary", web::get().to(api_summary))
+        .route("/api/entries", web::get().to(api_entries))
+        .route("/api/diff", web::get().to(api_diff))
+        .route(
+            "/api/extract/file_id/{index_number}",
+            web::get().to(api_extract_file_id),
+        )
+        .route("/export/tar", web::get().to(export_tar))
+        .route("/healthz", web::get().to(healthz));
+}
+
+/// Pick the server address from a `--bind <addr>` or `--bind=<addr>` argument, then `env_value`,
+/// then `DEFAULT_BIND_ADDRESS`.
+///
+/// The address must be an IP address and port such as `0.0.0.0:8080`; anything else fails with
+/// `ErrorKind::InvalidInput`. Other arguments are ignored.
+fn resolve_bind_address<I: Iterator<Item = String>>(
+    mut args: I,
+    env_value: Option<String>,
+) -> std::io::Result<SocketAddr> {
+    let mut bind_argument = None;
+    while let Some(arg) = args.next() {
+        if arg == "--bind" {
+            bind_argument = Some(args.next().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--bind requires an address, e.g. --bind 0.0.0.0:8080.",
+                )
+            })?);
+        } else if let Some(value) = arg.strip_prefix("--bind=") {
+            bind_argument = Some(value.to_string());
+        }
+    }
+
+    let address = bind_argument
+        .or(env_value)
+        .unwrap_or_else(|| DEFAULT_BIND_ADDRESS.to_string());
+    address.parse().map_err(|err| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid bind address '{}': {}", address, err),
+        )
+    })
+}
+
+/// Pick the archives to load from `--archive <name>=<path>` or `--archive=<name>=<path>` arguments,
+/// then `env_value` (`name=path` pairs separated by `;`), then `DEFAULT_ARCHIVE_PATHS`.
+///
+/// Any `--archive` argument replaces the environment and the defaults entirely, and may be repeated
+/// to load several archives. A pair without a name or a path fails with `ErrorKind::InvalidInput`.
+/// Other arguments are ignored.
+fn resolve_archive_paths<I: Iterator<Item = String>>(
+    mut args: I,
+    env_value: Option<String>,
+) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut archive_arguments = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--archive" {
+            archive_arguments.push(args.next().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--archive requires a name and path, e.g. --archive Gw2=/games/Gw2.dat.",
+                )
+            })?);
+        } else if let Some(value) = arg.strip_prefix("--archive=") {
+            archive_arguments.push(value.to_string());
+        }
+    }
+
+    let pairs = if !archive_arguments.is_empty() {
+        archive_arguments
+    } else if let Some(env_value) = env_value {
+        env_value
+            .split(';')
+            .filter(|pair| !pair.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        return Ok(DEFAULT_ARCHIVE_PATHS
+            .iter()
+            .map(|&(name, path)| (name.to_string(), PathBuf::from(path)))
+            .collect());
+    };
+
+    pairs
+        .iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+                Ok((name.to_string(), PathBuf::from(path)))
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid archive '{}', expected <name>=<path>.", pair),
+            )),
+        })
+        .collect()
+}
+
+/// Load the templates from `TEMPLATE_DIR`, filling in any missing ones from `EMBEDDED_TEMPLATES`.
+fn load_templates() -> Tera {
+    load_templates_from(TEMPLATE_DIR)
+}
+
+/// Load the templates from `template_dir`, filling in any missing ones from `EMBEDDED_TEMPLATES`.
+fn load_templates_from(template_dir: &str) -> Tera {
+    let mut tera = match Tera::new(&format!("{}/**/*", template_dir)) {
+        Ok(tera) => tera,
+        Err(err) => {
+            error!("Failed to load templates from '{}/': {}", template_dir, err);
+            Tera::default()
+        }
+    };
+
+    let missing_templates: Vec<(&str, &str)> = EMBEDDED_TEMPLATES
+        .iter()
+        .copied()
+        .filter(|(name, _)| !tera.get_template_names().any(|loaded| loaded == *name))
+        .collect();
+    if !missing_templates.is_empty() {
+        let names: Vec<&str> = missing_templates.iter().map(|(name, _)| *name).collect();
+        warn!(
+            "Templates not found in '{}/': {}. Using the embedded copies.",
+            template_dir,
+            names.join(", ")
+        );
+        tera.add_raw_templates(missing_templates)
+            .expect("Embedded templates are valid");
+    }
+
+    tera
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    /// Zero-based page of the entry table.
+    page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct EntriesQuery {
+    /// Zero-based page of entries.
+    page: Option<usize>,
+    /// Number of leading raw bytes to include per entry, capped at `MAX_PREVIEW_BYTES`.
+    preview: Option<u32>,
+}
+
+/// One row of the entry table on the home page and in `/api/entries`.
+#[derive(Serialize)]
+struct EntryRow {
+    index: usize,
+    size: u32,
+    compression_flag: u16,
+    /// The base ID of the entry, when the index table maps any file IDs to it.
+    base_id: Option<u32>,
+    file_ids: Vec<u32>,
+    /// The first raw bytes of the entry, hex encoded, when a preview was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preview: Option<String>,
+}
+
+/// Clamp a requested page to the pages available for `entry_count` entries, returning (page, page count).
+fn clamp_page(requested_page: Option<usize>, entry_count: usize) -> (usize, usize) {
+    let page_count = entry_count.div_ceil(ENTRIES_PER_PAGE);
+    let page = requested_page
+        .unwrap_or(0)
+        .min(page_count.saturating_sub(1));
+    (page, page_count)
+}
+
+/// Collect the entry table rows for `page`, with the file IDs mapped to each entry.
+fn entry_rows(dat_file: &DatFile, page: usize) -> Vec<EntryRow> {
+    let first_index = page.saturating_mul(ENTRIES_PER_PAGE);
+    let mut rows: Vec<EntryRow> = dat_file
+        .iter_entries()
+        .skip(first_index)
+        .take(ENTRIES_PER_PAGE)
+        .map(|(index, mft_entry)| EntryRow {
+            index,
+            size: mft_entry.size,
+            compression_flag: mft_entry.compression_flag,
+            base_id: None,
+            file_ids: Vec::new(),
+            preview: None,
+        })
+        .collect();
+
+    for index_data in &dat_file.mft_index_data {
+        let Some(row) = (index_data.base_id as usize)
+            .checked_sub(1)
+            .and_then(|index| index.checked_sub(first_index))
+            .and_then(|offset| rows.get_mut(offset))
+        else {
+            continue;
+        };
+        row.base_id = Some(index_data.base_id);
+        row.file_ids.push(index_data.file_id);
+    }
+
+    rows
+}
+
+async fn index(data: web::Data<AppState>, query: web::Query<PageQuery>) -> impl Responder {
     let mut context = Context::new();
     context.insert("message", "Welcome to the GW2 DAT File API!");
+    let mut archive_names: Vec<&String> = data.archives.keys().collect();
+    archive_names.sort();
+    context.insert("archives", &archive_names);
+
+    let entry_count = data
+        .archives
+        .get(DEFAULT_ARCHIVE)
+        .map_or(0, |dat_file| dat_file.mft_data.len());
+    let (page, page_count) = clamp_page(query.page, entry_count);
+    let entries = data
+        .archives
+        .get(DEFAULT_ARCHIVE)
+        .map(|dat_file| entry_rows(dat_file, page))
+        .unwrap_or_default();
+    context.insert("entries", &entries);
+    context.insert("page", &page);
+    context.insert("page_count", &page_count);
     let rendered = data.tera.render("index.html", &context);
 
     match rendered {
         Ok(body) => HttpResponse::Ok().body(body),
         Err(err) => {
-            eprintln!("Template error: {}", err);
+            error!("Template error: {}", err);
             HttpResponse::InternalServerError().body("Template rendering error")
         }
     }
@@ -134,12 +513,20 @@ async fn index(data: web::Data<AppState>) -> impl Responder {
 async fn extract_data_base_id(data: web::Data<AppState>, path: web::Path<u32>) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::BaseId, index_number as usize) {
-            Ok((raw_data, decompressed_data)) => {
-                let hex_raw_data = hex_dump(&raw_data, 16, 16); // 16 bytes per line, 16 lines max
-                let hex_decompressed_data = hex_dump(&decompressed_data, 16, 16);
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::BaseId, index_number as usize)
+        })
+        .await
+        {
+            Ok(ExtractedEntry {
+                raw_data,
+                decompressed_data,
+                was_compressed,
+                name,
+            }) => {
+                let hex_raw_data = hex_dump_string(&raw_data, 16, 16); // 16 bytes per line, 16 lines max
+                let hex_decompressed_data = hex_dump_string(&decompressed_data, 16, 16);
 
                 let mut context = Context::new();
                 context.insert("index_number", &index_number);
@@ -147,20 +534,20 @@ async fn extract_data_base_id(data: web::Data<AppState>, path: web::Path<u32>) -
                 context.insert("decompressed_data", &hex_decompressed_data);
                 context.insert("raw_data_length", &raw_data.len());
                 context.insert("decompressed_data_length", &decompressed_data.len());
+                context.insert("was_compressed", &was_compressed);
+                context.insert("name", &name);
 
                 let rendered = data.tera.render("data_view_base_id.html", &context);
 
                 match rendered {
                     Ok(body) => HttpResponse::Ok().body(body),
                     Err(err) => {
-                        eprintln!("Template error: {}", err);
+                        error!("Template error: {}", err);
                         HttpResponse::InternalServerError().body("Template rendering error")
                     }
                 }
             }
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
-            }
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -170,12 +557,20 @@ async fn extract_data_base_id(data: web::Data<AppState>, path: web::Path<u32>) -
 async fn extract_data_file_id(data: web::Data<AppState>, path: web::Path<u32>) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::FileId, index_number as usize) {
-            Ok((raw_data, decompressed_data)) => {
-                let hex_raw_data = hex_dump(&raw_data, 16, 16); // 16 bytes per line, 16 lines max
-                let hex_decompressed_data = hex_dump(&decompressed_data, 16, 16);
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok(ExtractedEntry {
+                raw_data,
+                decompressed_data,
+                was_compressed,
+                name,
+            }) => {
+                let hex_raw_data = hex_dump_string(&raw_data, 16, 16); // 16 bytes per line, 16 lines max
+                let hex_decompressed_data = hex_dump_string(&decompressed_data, 16, 16);
 
                 let mut context = Context::new();
                 context.insert("index_number", &index_number);
@@ -183,20 +578,84 @@ async fn extract_data_file_id(data: web::Data<AppState>, path: web::Path<u32>) -
                 context.insert("decompressed_data", &hex_decompressed_data);
                 context.insert("raw_data_length", &raw_data.len());
                 context.insert("decompressed_data_length", &decompressed_data.len());
+                context.insert("was_compressed", &was_compressed);
+                context.insert("name", &name);
 
                 let rendered = data.tera.render("data_view_file_id.html", &context);
 
                 match rendered {
                     Ok(body) => HttpResponse::Ok().body(body),
                     Err(err) => {
-                        eprintln!("Template error: {}", err);
+                        error!("Template error: {}", err);
                         HttpResponse::InternalServerError().body("Template rendering error")
                     }
                 }
             }
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+#[derive(Deserialize)]
+struct ExtractQuery {
+    /// `base64` to get the data as JSON; the HTML view is returned when absent.
+    encoding: Option<String>,
+    /// Encode entries larger than `MAX_BASE64_BYTES` anyway.
+    #[serde(default)]
+    force: bool,
+}
+
+async fn api_extract_file_id(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<u32>,
+    query: web::Query<ExtractQuery>,
+) -> HttpResponse {
+    match query.encoding.as_deref() {
+        None => {
+            return extract_data_file_id(data, path)
+                .await
+                .respond_to(&req)
+                .map_into_boxed_body();
+        }
+        Some("base64") => {}
+        Some(encoding) => {
+            return HttpResponse::BadRequest().body(format!(
+                "Unsupported encoding '{}'. Only 'base64' is available.",
+                encoding
+            ));
+        }
+    }
+    let index_number = path.into_inner();
+
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok(ExtractedEntry {
+                raw_data,
+                decompressed_data,
+                name,
+                ..
+            }) => {
+                let total_size = raw_data.len() + decompressed_data.len();
+                if total_size > MAX_BASE64_BYTES && !query.force {
+                    return HttpResponse::PayloadTooLarge().body(format!(
+                        "Entry data is {} bytes, more than the {} byte limit. Add force=true to encode it anyway.",
+                        total_size, MAX_BASE64_BYTES
+                    ));
+                }
+                HttpResponse::Ok().json(serde_json::json!({
+                    "name": format!("{}.{}", name, file_extension(&decompressed_data)),
+                    "raw": BASE64.encode(&raw_data),
+                    "decompressed": BASE64.encode(&decompressed_data),
+                }))
             }
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -209,22 +668,20 @@ async fn download_compressed_data_base_id(
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::BaseId, index_number as usize) {
-            Ok((raw_data, _)) => HttpResponse::Ok()
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::BaseId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => HttpResponse::Ok()
                 .content_type("application/octet-stream")
                 .insert_header((
                     "Content-Disposition",
-                    format!(
-                        "attachment; filename=compressed_base_id_{}.bin",
-                        index_number
-                    ),
+                    format!("attachment; filename={}_compressed.bin", entry.name),
                 ))
-                .body(raw_data),
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
-            }
+                .body(entry.raw_data),
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -237,22 +694,20 @@ async fn download_compressed_data_file_id(
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::FileId, index_number as usize) {
-            Ok((raw_data, _)) => HttpResponse::Ok()
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => HttpResponse::Ok()
                 .content_type("application/octet-stream")
                 .insert_header((
                     "Content-Disposition",
-                    format!(
-                        "attachment; filename=compressed_file_id_{}.bin",
-                        index_number
-                    ),
+                    format!("attachment; filename={}_compressed.bin", entry.name),
                 ))
-                .body(raw_data),
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
-            }
+                .body(entry.raw_data),
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -260,27 +715,27 @@ async fn download_compressed_data_file_id(
 }
 
 async fn download_decompressed_data_base_id(
+    req: HttpRequest,
     data: web::Data<AppState>,
     path: web::Path<u32>,
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::BaseId, index_number as usize) {
-            Ok((_, decompressed_data)) => HttpResponse::Ok()
-                .content_type("application/octet-stream")
-                .insert_header((
-                    "Content-Disposition",
-                    format!(
-                        "attachment; filename=decompressed_base_id_{}.bin",
-                        index_number
-                    ),
-                ))
-                .body(decompressed_data),
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::BaseId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => {
+                let filename = format!(
+                    "{}.{}",
+                    entry.name,
+                    file_extension(&entry.decompressed_data)
+                );
+                ranged_download(&req, entry.decompressed_data, filename)
             }
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -288,55 +743,147 @@ async fn download_decompressed_data_base_id(
 }
 
 async fn download_decompressed_data_file_id(
+    req: HttpRequest,
     data: web::Data<AppState>,
     path: web::Path<u32>,
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::FileId, index_number as usize) {
-            Ok((_, decompressed_data)) => HttpResponse::Ok()
-                .content_type("application/octet-stream")
-                .insert_header((
-                    "Content-Disposition",
-                    format!(
-                        "attachment; filename=decompressed_file_id_{}.bin",
-                        index_number
-                    ),
-                ))
-                .body(decompressed_data),
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => {
+                let filename = format!(
+                    "{}.{}",
+                    entry.name,
+                    file_extension(&entry.decompressed_data)
+                );
+                ranged_download(&req, entry.decompressed_data, filename)
             }
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
     }
 }
 
-async fn convert_to_image_base_id(
+async fn extract_data_mft_index(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<usize>,
+) -> impl Responder {
+    let mft_index = path.into_inner();
+
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_by_index_reopened(mft_index)
+        })
+        .await
+        {
+            Ok((_, decompressed_data)) => {
+                let filename = format!(
+                    "decompressed_mft_{}.{}",
+                    mft_index,
+                    file_extension(&decompressed_data)
+                );
+                ranged_download(&req, decompressed_data, filename)
+            }
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+#[derive(Deserialize)]
+struct HexQuery {
+    /// Number of 16-byte lines to include; the whole entry when absent.
+    max_lines: Option<usize>,
+}
+
+async fn hex_dump_file_id(
     data: web::Data<AppState>,
     path: web::Path<u32>,
+    query: web::Query<HexQuery>,
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::BaseId, index_number as usize) {
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_mft_data_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok((_, decompressed_data)) => HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(hex_dump_string(
+                    &decompressed_data,
+                    16,
+                    query.max_lines.unwrap_or(usize::MAX),
+                )),
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+async fn download_dds_file_id(data: web::Data<AppState>, path: web::Path<u32>) -> impl Responder {
+    let index_number = path.into_inner();
+
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_mft_data_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
             Ok((_, decompressed_data)) => {
-                if let Some(image_type) = detect_image_format(&decompressed_data) {
-                    HttpResponse::Ok()
-                        .content_type(image_type)
-                        .body(decompressed_data)
-                } else {
-                    HttpResponse::UnsupportedMediaType()
-                        .body("Data is not a supported image format.")
+                if texture_decompress::is_texture(&decompressed_data).is_none() {
+                    return HttpResponse::UnsupportedMediaType().body("Data is not a texture.");
+                }
+                match texture_decompress::to_dds(&decompressed_data) {
+                    Ok(dds_data) => HttpResponse::Ok()
+                        .content_type("application/octet-stream")
+                        .insert_header((
+                            "Content-Disposition",
+                            format!(
+                                "attachment; filename={}.dds",
+                                ArchiveId::FileId.entry_name(index_number as usize)
+                            ),
+                        ))
+                        .body(dds_data),
+                    Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                        HttpResponse::UnsupportedMediaType()
+                            .body(format!("Error converting texture: {}", err))
+                    }
+                    Err(err) => HttpResponse::InternalServerError()
+                        .body(format!("Error converting texture: {}", err)),
                 }
             }
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
-            }
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+async fn convert_to_image_base_id(
+    data: web::Data<AppState>,
+    path: web::Path<u32>,
+) -> impl Responder {
+    let index_number = path.into_inner();
+
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::BaseId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => image_response(entry.decompressed_data, &entry.name),
+            Err(err) => extraction_error(err),
         }
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
@@ -349,28 +896,495 @@ async fn convert_to_image_file_id(
 ) -> impl Responder {
     let index_number = path.into_inner();
 
-    let mut dat_file = data.dat_file.lock().unwrap();
-    if let Some(dat_file) = dat_file.as_mut() {
-        match dat_file.extract_mft_data(ArchiveId::FileId, index_number as usize) {
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_entry_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
+            Ok(entry) => image_response(entry.decompressed_data, &entry.name),
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+async fn extract_archive_data_base_id(
+    data: web::Data<AppState>,
+    path: web::Path<(String, u32)>,
+) -> impl Responder {
+    let (name, index_number) = path.into_inner();
+    extract_archive_data(&data, &name, ArchiveId::BaseId, index_number).await
+}
+
+async fn extract_archive_data_file_id(
+    data: web::Data<AppState>,
+    path: web::Path<(String, u32)>,
+) -> impl Responder {
+    let (name, index_number) = path.into_inner();
+    extract_archive_data(&data, &name, ArchiveId::FileId, index_number).await
+}
+
+/// Serve the decompressed data of an entry in the archive called `name` as an attachment.
+async fn extract_archive_data(
+    data: &web::Data<AppState>,
+    name: &str,
+    archive_id: ArchiveId,
+    index_number: u32,
+) -> HttpResponse {
+    let id_name = match archive_id {
+        ArchiveId::BaseId => "base_id",
+        ArchiveId::FileId => "file_id",
+    };
+
+    if data.archives.contains_key(name) {
+        match extract_blocking(data, name, move |dat_file| {
+            dat_file.extract_mft_data_reopened(archive_id, index_number as usize)
+        })
+        .await
+        {
+            Ok((_, decompressed_data)) => HttpResponse::Ok()
+                .content_type("application/octet-stream")
+                .insert_header((
+                    "Content-Disposition",
+                    format!(
+                        "attachment; filename={}_{}_{}.bin",
+                        name, id_name, index_number
+                    ),
+                ))
+                .body(decompressed_data),
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::NotFound().body(format!("Archive {} is not loaded.", name))
+    }
+}
+
+async fn extract_pf_chunk_file_id(
+    data: web::Data<AppState>,
+    path: web::Path<(u32, String)>,
+) -> impl Responder {
+    let (index_number, fourcc) = path.into_inner();
+    let Ok(fourcc_bytes) = <[u8; 4]>::try_from(fourcc.as_bytes()) else {
+        return HttpResponse::BadRequest().body("Chunk identifier must be 4 bytes long.");
+    };
+
+    if data.archives.contains_key(DEFAULT_ARCHIVE) {
+        match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.extract_mft_data_reopened(ArchiveId::FileId, index_number as usize)
+        })
+        .await
+        {
             Ok((_, decompressed_data)) => {
-                if let Some(image_type) = detect_image_format(&decompressed_data) {
-                    HttpResponse::Ok()
-                        .content_type(image_type)
-                        .body(decompressed_data)
-                } else {
-                    HttpResponse::UnsupportedMediaType()
-                        .body("Data is not a supported image format.")
+                let pf_file = match PfFile::parse(&decompressed_data) {
+                    Ok(pf_file) => pf_file,
+                    Err(err) => {
+                        return HttpResponse::UnsupportedMediaType()
+                            .body(format!("Data is not a PF file: {}", err));
+                    }
+                };
+                match pf_file.chunk(&fourcc_bytes) {
+                    Some(chunk) => HttpResponse::Ok()
+                        .content_type("application/octet-stream")
+                        .insert_header((
+                            "Content-Disposition",
+                            format!(
+                                "attachment; filename=file_id_{}_{}.bin",
+                                index_number, fourcc
+                            ),
+                        ))
+                        .body(chunk.chunk_data.clone()),
+                    None => {
+                        HttpResponse::NotFound().body(format!("PF file has no {} chunk.", fourcc))
+                    }
                 }
             }
-            Err(err) => {
-                HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
+            Err(err) => extraction_error(err),
+        }
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+async fn api_header(data: web::Data<AppState>) -> impl Responder {
+    if let Some(dat_file) = data.archives.get(DEFAULT_ARCHIVE) {
+        let dat_header = &dat_file.dat_header;
+        let mft_header = &dat_file.mft_header;
+        HttpResponse::Ok().json(serde_json::json!({
+            "dat_header": {
+                "version": dat_header.version,
+                "identifier": dat_header.identifier_str(),
+                "header_size": dat_header.header_size,
+                "chunk_size": dat_header.chunk_size,
+                "crc": dat_header.crc,
+                "mft_offset": dat_header.mft_offset,
+                "mft_size": dat_header.mft_size,
+                "flag": dat_header.flag,
+            },
+            "mft_header": {
+                "identifier": mft_header.identifier_str(),
+                "mft_entry_size": mft_header.mft_entry_size,
+            },
+        }))
+    } else {
+        HttpResponse::InternalServerError().body("DAT file not loaded.")
+    }
+}
+
+async fn api_entries(data: web::Data<AppState>, query: web::Query<EntriesQuery>) -> impl Responder {
+    let Some(dat_file) = data.archives.get(DEFAULT_ARCHIVE) else {
+        return HttpResponse::InternalServerError().body("DAT file not loaded.");
+    };
+
+    let (page, page_count) = clamp_page(query.page, dat_file.mft_data.len());
+    let mut entries = entry_rows(dat_file, page);
+    if let Some(preview_bytes) = query.preview {
+        let indices: Vec<usize> = entries.iter().map(|entry| entry.index).collect();
+        let prefixes = match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+            dat_file.raw_prefixes(&indices, preview_bytes.min(MAX_PREVIEW_BYTES))
+        })
+        .await
+        {
+            Ok(prefixes) => prefixes,
+            Err(err) => return extraction_error(err),
+        };
+        for (entry, prefix) in entries.iter_mut().zip(prefixes) {
+            entry.preview = Some(hex_bytes(&prefix));
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "page": page,
+        "page_count": page_count,
+        "entries": entries,
+    }))
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// File ID of the first entry.
+    a: u32,
+    /// File ID of the second entry.
+    b: u32,
+}
+
+/// Compare the decompressed data of two file IDs and report where they first differ.
+async fn api_diff(data: web::Data<AppState>, query: web::Query<DiffQuery>) -> impl Responder {
+    if !data.archives.contains_key(DEFAULT_ARCHIVE) {
+        return HttpResponse::InternalServerError().body("DAT file not loaded.");
+    }
+
+    let (file_id_a, file_id_b) = (query.a as usize, query.b as usize);
+    let (data_a, data_b) = match extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+        let (_, data_a) = dat_file.extract_mft_data_reopened(ArchiveId::FileId, file_id_a)?;
+        let (_, data_b) = dat_file.extract_mft_data_reopened(ArchiveId::FileId, file_id_b)?;
+        Ok((data_a, data_b))
+    })
+    .await
+    {
+        Ok(extracted) => extracted,
+        Err(err) => return extraction_error(err),
+    };
+
+    // When one entry is a prefix of the other, they differ where the shorter one ends
+    let first_difference = data_a
+        .iter()
+        .zip(&data_b)
+        .position(|(byte_a, byte_b)| byte_a != byte_b)
+        .or_else(|| (data_a.len() != data_b.len()).then(|| data_a.len().min(data_b.len())));
+
+    let Some(offset) = first_difference else {
+        return HttpResponse::Ok().json(serde_json::json!({
+            "identical": true,
+            "size": data_a.len(),
+        }));
+    };
+    let context_start = offset.saturating_sub(DIFF_CONTEXT_BYTES);
+    let context_end = offset + DIFF_CONTEXT_BYTES;
+    let context = |entry_data: &[u8]| {
+        hex_bytes(
+            &entry_data[context_start.min(entry_data.len())..context_end.min(entry_data.len())],
+        )
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "identical": false,
+        "offset": offset,
+        "context_offset": context_start,
+        "a": { "size": data_a.len(), "context": context(&data_a) },
+        "b": { "size": data_b.len(), "context": context(&data_b) },
+    }))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// Maximum number of entries to export; `DEFAULT_EXPORT_LIMIT` when absent.
+    limit: Option<usize>,
+}
+
+/// Stream the decompressed entries of the index table as a tar archive, one member per file ID.
+///
+/// Entries are extracted one at a time as the response is sent, so memory use is bounded by the largest entry.
+/// Entries that fail to extract are logged and left out.
+async fn export_tar(data: web::Data<AppState>, query: web::Query<ExportQuery>) -> HttpResponse {
+    let Some(dat_file) = data.archives.get(DEFAULT_ARCHIVE) else {
+        return HttpResponse::InternalServerError().body("DAT file not loaded.");
+    };
+    let member_count = dat_file
+        .mft_index_data
+        .len()
+        .min(query.limit.unwrap_or(DEFAULT_EXPORT_LIMIT));
+    let modified = dat_file
+        .modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_secs());
+
+    let members = futures_util::stream::unfold(0, move |position| {
+        let data = data.clone();
+        async move {
+            if position > member_count {
+                return None;
+            }
+            if position == member_count {
+                // Two zero blocks end the archive
+                let end_of_archive = web::Bytes::from(vec![0; 2 * TAR_BLOCK_SIZE]);
+                return Some((Ok::<_, std::io::Error>(end_of_archive), position + 1));
             }
+
+            let extracted = extract_blocking(&data, DEFAULT_ARCHIVE, move |dat_file| {
+                let file_id = dat_file.mft_index_data[position].file_id;
+                let (_, decompressed_data) =
+                    dat_file.extract_mft_data_reopened(ArchiveId::FileId, file_id as usize)?;
+                Ok((file_id, decompressed_data))
+            })
+            .await;
+            let member = match extracted {
+                Ok((file_id, decompressed_data)) => {
+                    let name = format!(
+                        "{}.{}",
+                        ArchiveId::FileId.entry_name(file_id as usize),
+                        file_extension(&decompressed_data)
+                    );
+                    tar_member(&name, &decompressed_data, modified)
+                }
+                Err(err) => {
+                    warn!("Skipping index row {} in tar export: {}", position, err);
+                    Vec::new()
+                }
+            };
+            Some((Ok(web::Bytes::from(member)), position + 1))
         }
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/x-tar")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename={}.tar", DEFAULT_ARCHIVE),
+        ))
+        .streaming(members)
+}
+
+/// Build a ustar member holding `contents`: a header block, then the data padded to whole blocks.
+fn tar_member(name: &str, contents: &[u8], modified: u64) -> Vec<u8> {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    let name_length = name.len().min(100);
+    header[..name_length].copy_from_slice(&name.as_bytes()[..name_length]);
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", contents.len()).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", modified).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with its own field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&byte| byte as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    let padded_length = contents.len().div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+    let mut member = Vec::with_capacity(TAR_BLOCK_SIZE + padded_length);
+    member.extend_from_slice(&header);
+    member.extend_from_slice(contents);
+    member.resize(TAR_BLOCK_SIZE + padded_length, 0);
+    member
+}
+
+async fn api_summary(data: web::Data<AppState>) -> impl Responder {
+    if let Some(dat_file) = data.archives.get(DEFAULT_ARCHIVE) {
+        let compressed_entries = dat_file
+            .mft_data
+            .iter()
+            .filter(|mft_entry| mft_entry.compression_flag != 0)
+            .count();
+        let total_size: u64 = dat_file
+            .mft_data
+            .iter()
+            .map(|mft_entry| mft_entry.size as u64)
+            .sum();
+        let modified = dat_file
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs());
+        HttpResponse::Ok().json(serde_json::json!({
+            "version": dat_file.dat_header.version,
+            "file_size": dat_file.file_size,
+            "modified": modified,
+            "entries": dat_file.mft_data.len(),
+            "total_size": total_size,
+            "compressed_entries": compressed_entries,
+            "uncompressed_entries": dat_file.mft_data.len() - compressed_entries,
+        }))
     } else {
         HttpResponse::InternalServerError().body("DAT file not loaded.")
     }
 }
 
+async fn healthz(data: web::Data<AppState>) -> impl Responder {
+    if let Some(dat_file) = data.archives.get(DEFAULT_ARCHIVE) {
+        HttpResponse::Ok().json(serde_json::json!({
+            "loaded": true,
+            "entries": dat_file.mft_data.len(),
+        }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(serde_json::json!({ "loaded": false }))
+    }
+}
+
+/// Run `extract` against the loaded archive called `name` on actix's blocking thread pool.
+///
+/// Reading and decompressing a large entry would otherwise stall the async worker and every request queued on it.
+async fn extract_blocking<T, F>(
+    data: &web::Data<AppState>,
+    name: &str,
+    extract: F,
+) -> std::io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&DatFile) -> std::io::Result<T> + Send + 'static,
+{
+    let data = web::Data::clone(data);
+    let name = name.to_string();
+    web::block(move || match data.archives.get(&name) {
+        Some(dat_file) => extract(dat_file),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Archive {} is not loaded.", name),
+        )),
+    })
+    .await
+    .unwrap_or_else(|err| Err(std::io::Error::other(err.to_string())))
+}
+
+/// Format `bytes` as lowercase hex with no separators.
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Map an extraction failure to a response: 404 for IDs that are not in the archive, 504 for timeouts, 500 otherwise.
+fn extraction_error(err: std::io::Error) -> HttpResponse {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        HttpResponse::NotFound().body(format!("Entry not found: {}", err))
+    } else if err.kind() == std::io::ErrorKind::TimedOut {
+        HttpResponse::GatewayTimeout().body(format!("Extraction timed out: {}", err))
+    } else {
+        HttpResponse::InternalServerError().body(format!("Error extracting data: {}", err))
+    }
+}
+
+/// Serve `body` as an attachment, honoring a single `Range: bytes=` request with a 206 response.
+fn ranged_download(req: &HttpRequest, body: Vec<u8>, filename: String) -> HttpResponse {
+    let full_length = body.len() as u64;
+    let byte_range = match req.get_header::<Range>() {
+        Some(Range::Bytes(ranges)) if ranges.len() == 1 => {
+            Some(ranges[0].to_satisfiable_range(full_length))
+        }
+        _ => None,
+    };
+
+    let mut response = match byte_range {
+        None => HttpResponse::Ok(),
+        Some(Some(_)) => HttpResponse::PartialContent(),
+        Some(None) => {
+            return HttpResponse::RangeNotSatisfiable()
+                .insert_header(header::ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(full_length),
+                }))
+                .finish();
+        }
+    };
+    response
+        .content_type("application/octet-stream")
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename={}", filename),
+        ));
+
+    match byte_range {
+        Some(Some((start, end))) => response
+            .insert_header(header::ContentRange(ContentRangeSpec::Bytes {
+                range: Some((start, end)),
+                instance_length: Some(full_length),
+            }))
+            .body(body[start as usize..=end as usize].to_vec()),
+        _ => response.body(body),
+    }
+}
+
+/// Serve entry data as an image, decoding ATEX textures to PNG.
+fn image_response(decompressed_data: Vec<u8>, name: &str) -> HttpResponse {
+    let (image_type, image_data) = if let Some(image_type) = detect_image_format(&decompressed_data)
+    {
+        (image_type, decompressed_data)
+    } else if texture_decompress::is_texture(&decompressed_data).is_some() {
+        match texture_to_png(&decompressed_data) {
+            Ok(png_data) => ("image/png", png_data),
+            Err(err) => {
+                // The data is a texture, so a failure here is a decoder problem rather than a bad request.
+                let texture_description =
+                    match texture_decompress::probe_dimensions(&decompressed_data) {
+                        Ok((width, height, format_name)) => {
+                            format!("{} texture ({}x{})", format_name, width, height)
+                        }
+                        Err(_) => "texture".to_string(),
+                    };
+                return HttpResponse::InternalServerError()
+                    .body(format!("Error decoding {}: {}", texture_description, err));
+            }
+        }
+    } else {
+        return HttpResponse::UnsupportedMediaType().body(
+            match detect_file_type(&decompressed_data) {
+                Some((file_type, _)) => {
+                    format!("{} data cannot be displayed as an image.", file_type)
+                }
+                None => "Data is not a supported image format.".to_string(),
+            },
+        );
+    };
+
+    HttpResponse::Ok()
+        .content_type(image_type)
+        .insert_header((
+            "Content-Disposition",
+            format!("inline; filename={}.{}", name, image_extension(image_type)),
+        ))
+        .insert_header(("Content-Length", image_data.len()))
+        .body(image_data)
+}
+
+/// File extension for a MIME type returned by `detect_image_format`.
+fn image_extension(image_type: &'static str) -> &'static str {
+    match image_type {
+        "image/jpeg" => "jpg",
+        _ => image_type.trim_start_matches("image/"),
+    }
+}
+
 fn detect_image_format(data: &[u8]) -> Option<&'static str> {
     if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
         Some("image/png")
@@ -382,7 +1396,1061 @@ fn detect_image_format(data: &[u8]) -> Option<&'static str> {
         || data.starts_with(&[0x4D, 0x4D, 0x00, 0x2A])
     {
         Some("image/tiff")
+    } else if data.len() >= 14 && data.starts_with(b"BM") {
+        Some("image/bmp")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
     } else {
         None
     }
 }
+
+/// Detect the MIME type and file extension of entry data, including formats browsers cannot display.
+fn detect_file_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if let Some(image_type) = detect_image_format(data) {
+        Some((image_type, image_extension(image_type)))
+    } else if data.starts_with(b"DDS ") {
+        Some(("image/vnd-ms.dds", "dds"))
+    } else {
+        None
+    }
+}
+
+/// File extension for a download of entry data, falling back to `bin`.
+fn file_extension(data: &[u8]) -> &'static str {
+    detect_file_type(data).map_or("bin", |(_, extension)| extension)
+}
+
+/// Decode an ATEX texture and encode it as PNG.
+fn texture_to_png(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let image = texture_decompress::decode_to_image(data)?;
+
+    let mut png_data = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png_data, ImageFormat::Png)
+        .map_err(std::io::Error::other)?;
+    Ok(png_data.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body, read_body_json};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tarir::dat_builder::{DatBuilder, compress_entry_data};
+
+    /// File ID of a stored entry starting with the PNG signature.
+    const PNG_FILE_ID: u32 = 1;
+    /// File ID of a stored DXT1 texture, solid red.
+    const TEXTURE_FILE_ID: u32 = 2;
+    /// File ID of a compressed entry holding `large_entry`.
+    const LARGE_FILE_ID: u32 = 3;
+    /// File ID of a stored PF file with a GEOM chunk.
+    const PF_FILE_ID: u32 = 4;
+
+    fn png_entry() -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(b"not a real image");
+        data
+    }
+
+    /// Large enough to span two CRC chunks once compressed.
+    fn large_entry() -> Vec<u8> {
+        (0..70_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn pf_entry() -> Vec<u8> {
+        let mut data = b"PF".to_vec();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&12u16.to_le_bytes());
+        data.extend_from_slice(b"MODL");
+        data.extend_from_slice(b"GEOM");
+        data.extend_from_slice(&11u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 3]);
+        data
+    }
+
+    /// An archive with the entries named by the `*_FILE_ID` constants.
+    fn test_archive() -> Vec<u8> {
+        let mut builder = DatBuilder::new();
+        for (file_id, data, compression_flag) in [
+            (PNG_FILE_ID, png_entry(), 0),
+            (
+                TEXTURE_FILE_ID,
+                texture_file(b"DXT1", 4, 4, &[0x0000_F800, 0]),
+                0,
+            ),
+            (LARGE_FILE_ID, compress_entry_data(&large_entry()), 8),
+            (PF_FILE_ID, pf_entry(), 0),
+        ] {
+            let base_id = builder.add_entry(data, compression_flag);
+            builder.add_file_id(file_id, base_id);
+        }
+        builder.build()
+    }
+
+    /// Memory-map each archive under its name. The files are deleted once mapped.
+    fn test_state(archives: Vec<(&str, Vec<u8>)>) -> web::Data<AppState> {
+        static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+        let archives = archives
+            .into_iter()
+            .map(|(name, bytes)| {
+                let path = std::env::temp_dir().join(format!(
+                    "tarir-{}-{}.dat",
+                    std::process::id(),
+                    NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+                ));
+                std::fs::write(&path, bytes).unwrap();
+                let dat_file = DatFile::load_mmap(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+                (name.to_string(), dat_file)
+            })
+            .collect();
+        web::Data::new(AppState {
+            archives,
+            tera: load_templates(),
+        })
+    }
+
+    fn default_state() -> web::Data<AppState> {
+        test_state(vec![(DEFAULT_ARCHIVE, test_archive())])
+    }
+
+    fn header_str<B>(response: &actix_web::dev::ServiceResponse<B>, name: &str) -> String {
+        response
+            .headers()
+            .get(name)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// An ATEX texture whose blocks are all stored as raw words after an empty compression header.
+    fn texture_file(fourcc: &[u8; 4], width: u16, height: u16, raw_words: &[u32]) -> Vec<u8> {
+        let mut data = b"ATEX".to_vec();
+        data.extend_from_slice(fourcc);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&((raw_words.len() * 4 + 8) as u32).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for word in raw_words {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn texture_to_png_encodes_decoded_pixels() {
+        // One DXT1 block: color 0 is pure red and every texel uses it
+        let png_data = texture_to_png(&texture_file(b"DXT1", 4, 4, &[0x0000_F800, 0])).unwrap();
+
+        assert!(png_data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]));
+        let image = image::load_from_memory_with_format(&png_data, ImageFormat::Png)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(image.dimensions(), (4, 4));
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn detect_image_format_recognizes_bmp_and_gif() {
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(14, 0);
+        assert_eq!(detect_image_format(&bmp), Some("image/bmp"));
+        assert_eq!(detect_image_format(b"GIF87a\x01\x00"), Some("image/gif"));
+        assert_eq!(detect_image_format(b"GIF89a"), Some("image/gif"));
+    }
+
+    #[test]
+    fn detect_file_type_returns_mime_and_extension() {
+        assert_eq!(
+            detect_file_type(b"DDS |\x00\x00\x00"),
+            Some(("image/vnd-ms.dds", "dds"))
+        );
+        assert_eq!(detect_file_type(&png_entry()), Some(("image/png", "png")));
+        assert_eq!(
+            detect_file_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(("image/jpeg", "jpg"))
+        );
+        assert_eq!(
+            detect_file_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some(("image/webp", "webp"))
+        );
+        assert_eq!(
+            detect_file_type(&[0x49, 0x49, 0x2A, 0x00]),
+            Some(("image/tiff", "tiff"))
+        );
+        assert_eq!(detect_file_type(b"GIF89a"), Some(("image/gif", "gif")));
+        assert_eq!(detect_file_type(b"ATEX"), None);
+        assert_eq!(file_extension(b"ATEX"), "bin");
+        assert_eq!(file_extension(b"DDS "), "dds");
+    }
+
+    #[test]
+    fn detect_image_format_ignores_short_buffers() {
+        assert_eq!(detect_image_format(b"BM\x00\x00"), None);
+        assert_eq!(detect_image_format(b"GIF8"), None);
+        assert_eq!(detect_image_format(b"GIF88a"), None);
+        assert_eq!(detect_image_format(b""), None);
+    }
+
+    #[actix_web::test]
+    async fn convert_to_image_names_the_download() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri(&format!("/convert_to_image/file_id/{}", PNG_FILE_ID))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header_str(&response, "content-type"), "image/png");
+        assert_eq!(
+            header_str(&response, "content-disposition"),
+            "inline; filename=file_1.png"
+        );
+        assert_eq!(
+            header_str(&response, "content-length"),
+            png_entry().len().to_string()
+        );
+        assert_eq!(read_body(response).await, png_entry());
+
+        // Textures are decoded, so they are named as PNGs too
+        let request = TestRequest::get()
+            .uri(&format!("/convert_to_image/file_id/{}", TEXTURE_FILE_ID))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header_str(&response, "content-disposition"),
+            "inline; filename=file_2.png"
+        );
+    }
+
+    #[actix_web::test]
+    async fn api_header_reports_parsed_headers() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/api/header").to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header_str(&response, "content-type"), "application/json");
+        let header: serde_json::Value = serde_json::from_slice(&read_body(response).await).unwrap();
+        assert_eq!(header["dat_header"]["version"], 151);
+        assert_eq!(header["dat_header"]["identifier"], "AN(");
+        assert_eq!(header["dat_header"]["chunk_size"], 512);
+        assert_eq!(header["mft_header"]["identifier"], "Mft\\x1A");
+        // Three reserved rows and four entries
+        assert_eq!(header["mft_header"]["mft_entry_size"], 7);
+    }
+
+    #[actix_web::test]
+    async fn api_header_without_archive_is_an_error() {
+        let app = init_service(
+            App::new()
+                .app_data(test_state(Vec::new()))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/api/header").to_request()).await;
+        assert_eq!(response.status(), 500);
+        assert_eq!(read_body(response).await, "DAT file not loaded.");
+    }
+
+    #[actix_web::test]
+    async fn download_decompressed_honors_byte_ranges() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+        let uri = format!("/download/decompressed/file_id/{}", LARGE_FILE_ID);
+
+        let request = TestRequest::get()
+            .uri(&uri)
+            .insert_header(("Range", "bytes=100-199"))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 206);
+        assert_eq!(
+            header_str(&response, "content-range"),
+            "bytes 100-199/70000"
+        );
+        assert_eq!(
+            header_str(&response, "content-disposition"),
+            "attachment; filename=file_3.bin"
+        );
+        assert_eq!(read_body(response).await, large_entry()[100..200]);
+
+        let request = TestRequest::get().uri(&uri).to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header_str(&response, "accept-ranges"), "bytes");
+        assert_eq!(read_body(response).await, large_entry());
+
+        let request = TestRequest::get()
+            .uri(&uri)
+            .insert_header(("Range", "bytes=80000-"))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 416);
+        assert_eq!(header_str(&response, "content-range"), "bytes */70000");
+    }
+
+    #[test]
+    fn missing_template_dir_falls_back_to_embedded_templates() {
+        let tera = load_templates_from("no-such-template-dir");
+        let mut names: Vec<&str> = tera.get_template_names().collect();
+        names.sort_unstable();
+        let mut expected: Vec<&str> = EMBEDDED_TEMPLATES.iter().map(|(name, _)| *name).collect();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn embedded_index_template_renders() {
+        let tera = load_templates_from("no-such-template-dir");
+        let mut context = Context::new();
+        context.insert("message", "Hello");
+        context.insert("archives", &["Local.dat"]);
+        context.insert("entries", &Vec::<EntryRow>::new());
+        context.insert("page", &0usize);
+        context.insert("page_count", &1usize);
+
+        let body = tera.render("index.html", &context).unwrap();
+        assert!(body.contains("<h1>Hello</h1>"));
+        assert!(body.contains("<li>Local.dat</li>"));
+        assert!(body.contains("<h2>Entries</h2>"));
+    }
+
+    #[actix_web::test]
+    async fn archive_routes_extract_from_each_named_archive() {
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(b"from gw2".to_vec(), 0);
+        builder.add_file_id(PNG_FILE_ID, base_id);
+        let state = test_state(vec![
+            (DEFAULT_ARCHIVE, test_archive()),
+            ("Gw2.dat", builder.build()),
+        ]);
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let uri = format!(
+            "/archives/{}/extract/file_id/{}",
+            DEFAULT_ARCHIVE, PNG_FILE_ID
+        );
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header_str(&response, "Content-Disposition"),
+            format!("attachment; filename={}_file_id_1.bin", DEFAULT_ARCHIVE)
+        );
+        assert_eq!(read_body(response).await, png_entry());
+
+        let uri = format!("/archives/Gw2.dat/extract/file_id/{}", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(read_body(response).await, &b"from gw2"[..]);
+
+        let response = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/archives/Other.dat/extract/file_id/1")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(
+            read_body(response).await,
+            "Archive Other.dat is not loaded."
+        );
+    }
+
+    #[actix_web::test]
+    async fn concurrent_extractions_both_complete() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/download/decompressed/file_id/{}", LARGE_FILE_ID);
+        let (first, second) = futures_util::future::join(
+            call_service(&app, TestRequest::get().uri(&uri).to_request()),
+            call_service(&app, TestRequest::get().uri(&uri).to_request()),
+        )
+        .await;
+        assert_eq!(first.status(), 200);
+        assert_eq!(second.status(), 200);
+        assert_eq!(read_body(first).await, large_entry());
+        assert_eq!(read_body(second).await, large_entry());
+    }
+
+    #[actix_web::test]
+    async fn healthz_reports_load_status() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+        let response = call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header_str(&response, "content-type"), "application/json");
+        let body: serde_json::Value = read_body_json(response).await;
+        assert_eq!(body, serde_json::json!({ "loaded": true, "entries": 7 }));
+
+        let app = init_service(
+            App::new()
+                .app_data(test_state(Vec::new()))
+                .configure(configure_routes),
+        )
+        .await;
+        let response = call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+        assert_eq!(response.status(), 503);
+        let body: serde_json::Value = read_body_json(response).await;
+        assert_eq!(body, serde_json::json!({ "loaded": false }));
+    }
+
+    #[actix_web::test]
+    async fn out_of_range_ids_are_not_found() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        for uri in [
+            "/extract/file_id/4000000000",
+            "/extract/base_id/4000000000",
+            "/extract/mft/4000000000",
+            "/download/decompressed/file_id/4000000000",
+        ] {
+            let response = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(response.status(), 404, "{}", uri);
+            let body = read_body(response).await;
+            assert!(body.starts_with(b"Entry not found: "), "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn download_dds_wraps_textures() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/download/dds/file_id/{}", TEXTURE_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header_str(&response, "content-type"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            header_str(&response, "Content-Disposition"),
+            "attachment; filename=file_2.dds"
+        );
+        let body = read_body(response).await;
+        assert_eq!(&body[0..4], b"DDS ");
+        assert_eq!(&body[84..88], b"DXT1");
+        // The 128-byte header, then the single 4x4 block
+        assert_eq!(body.len(), 128 + 8);
+
+        let uri = format!("/download/dds/file_id/{}", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 415);
+        assert_eq!(read_body(response).await, "Data is not a texture.");
+    }
+
+    #[actix_web::test]
+    async fn pf_chunk_route_returns_the_chunk_payload() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/pf/file_id/{}/chunk/GEOM", PF_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header_str(&response, "Content-Disposition"),
+            "attachment; filename=file_id_4_GEOM.bin"
+        );
+        assert_eq!(read_body(response).await, &[1u8, 2, 3][..]);
+
+        for (uri, status) in [
+            (format!("/pf/file_id/{}/chunk/AMAT", PF_FILE_ID), 404),
+            (format!("/pf/file_id/{}/chunk/GEOM", PNG_FILE_ID), 415),
+            (format!("/pf/file_id/{}/chunk/GEOMX", PF_FILE_ID), 400),
+            ("/pf/file_id/999/chunk/GEOM".to_string(), 404),
+        ] {
+            let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(response.status(), status, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn index_lists_entries_with_links() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(&app, TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert_eq!(body.matches("<tr class=\"entry\">").count(), 7);
+        assert!(body.contains(&format!("<li>{}</li>", DEFAULT_ARCHIVE)));
+        assert!(body.contains("<a href=\"/extract/base_id/4\">View</a>"));
+        assert!(body.contains("<a href=\"/convert_to_image/base_id/5\">Image</a>"));
+        assert!(body.contains("Page 1 of 1"));
+    }
+
+    #[actix_web::test]
+    async fn convert_to_image_separates_binaries_from_decode_failures() {
+        // A texture header with a fourcc the decoder does not know
+        let mut malformed_texture = b"ATEXABCD".to_vec();
+        malformed_texture.extend_from_slice(&[4, 0, 4, 0]);
+        malformed_texture.extend_from_slice(&[0; 16]);
+        let mut builder = DatBuilder::new();
+        for (file_id, data) in [
+            (1, b"plain binary data".to_vec()),
+            (2, b"DDS |\x00\x00\x00".to_vec()),
+            (3, malformed_texture),
+        ] {
+            let base_id = builder.add_entry(data, 0);
+            builder.add_file_id(file_id, base_id);
+        }
+        let app = init_service(
+            App::new()
+                .app_data(test_state(vec![(DEFAULT_ARCHIVE, builder.build())]))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/convert_to_image/file_id/1")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 415);
+        assert_eq!(
+            read_body(response).await,
+            "Data is not a supported image format."
+        );
+
+        let response = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/convert_to_image/file_id/2")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 415);
+        assert_eq!(
+            read_body(response).await,
+            "image/vnd-ms.dds data cannot be displayed as an image."
+        );
+
+        let response = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/convert_to_image/file_id/3")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 500);
+        let body = read_body(response).await;
+        assert!(body.starts_with(b"Error decoding texture: "));
+    }
+
+    #[actix_web::test]
+    async fn hex_route_returns_plain_text_dumps() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/hex/file_id/{}", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header_str(&response, "content-type"),
+            "text/plain; charset=utf-8"
+        );
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert_eq!(
+            body.lines().next().unwrap(),
+            "00000000: 89 50 4E 47 0D 0A 1A 0A 6E 6F 74 20 61 20 72 65 |.PNG....not a re|"
+        );
+
+        // No cap by default
+        let uri = format!("/hex/file_id/{}", LARGE_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert_eq!(body.lines().count(), large_entry().len().div_ceil(16));
+
+        let uri = format!("/hex/file_id/{}?max_lines=2", LARGE_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert_eq!(body.lines().count(), 2);
+        assert!(body.lines().nth(1).unwrap().starts_with("00000010: "));
+    }
+
+    #[actix_web::test]
+    async fn summary_counts_every_mft_entry() {
+        let state = default_state();
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response =
+            call_service(&app, TestRequest::get().uri("/api/summary").to_request()).await;
+        assert_eq!(response.status(), 200);
+        let summary: serde_json::Value = read_body_json(response).await;
+        let dat_file = &state.archives[DEFAULT_ARCHIVE];
+        assert_eq!(
+            summary["entries"],
+            dat_file.mft_header.mft_entry_size as u64
+        );
+        assert_eq!(
+            summary["compressed_entries"].as_u64().unwrap()
+                + summary["uncompressed_entries"].as_u64().unwrap(),
+            dat_file.mft_data.len() as u64
+        );
+        assert_eq!(summary["compressed_entries"], 1);
+    }
+
+    #[actix_web::test]
+    async fn entries_preview_has_the_requested_length() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response =
+            call_service(&app, TestRequest::get().uri("/api/entries").to_request()).await;
+        let listing: serde_json::Value = read_body_json(response).await;
+        assert!(listing["entries"][0].get("preview").is_none());
+
+        for (requested, expected) in [(8, 8), (1000, MAX_PREVIEW_BYTES as u64)] {
+            let uri = format!("/api/entries?preview={}", requested);
+            let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(response.status(), 200);
+            let listing: serde_json::Value = read_body_json(response).await;
+            for entry in listing["entries"].as_array().unwrap() {
+                let preview = entry["preview"].as_str().unwrap();
+                let size = entry["size"].as_u64().unwrap();
+                assert_eq!(preview.len() as u64, 2 * size.min(expected));
+            }
+        }
+
+        // The PNG entry is the first built entry, after the three header rows
+        let response = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/entries?preview=4")
+                .to_request(),
+        )
+        .await;
+        let listing: serde_json::Value = read_body_json(response).await;
+        assert_eq!(listing["entries"][3]["preview"], "89504e47");
+    }
+
+    #[actix_web::test]
+    async fn base64_extract_decodes_to_the_entry_data() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/api/extract/file_id/{}?encoding=base64", LARGE_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        let extracted: serde_json::Value = read_body_json(response).await;
+        let decompressed = BASE64
+            .decode(extracted["decompressed"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(decompressed, large_entry());
+        let raw = BASE64.decode(extracted["raw"].as_str().unwrap()).unwrap();
+        assert!(raw.len() > 4);
+        assert_ne!(raw, decompressed);
+
+        let uri = format!("/api/extract/file_id/{}?encoding=base64", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        let extracted: serde_json::Value = read_body_json(response).await;
+        assert_eq!(extracted["name"], format!("file_{}.png", PNG_FILE_ID));
+        assert_eq!(
+            BASE64.decode(extracted["raw"].as_str().unwrap()).unwrap(),
+            png_entry()
+        );
+
+        let uri = format!("/api/extract/file_id/{}?encoding=hex", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 400);
+
+        // Without an encoding the HTML view is returned
+        let uri = format!("/api/extract/file_id/{}", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert!(body.contains(&format!("file_{}", PNG_FILE_ID)));
+    }
+
+    #[actix_web::test]
+    async fn base64_extract_needs_force_past_the_size_cap() {
+        // Stored, so the raw and decompressed copies together pass the cap
+        let mut builder = DatBuilder::new();
+        let base_id = builder.add_entry(vec![0xAB; MAX_BASE64_BYTES / 2 + 1], 0);
+        builder.add_file_id(PNG_FILE_ID, base_id);
+        let app = init_service(
+            App::new()
+                .app_data(test_state(vec![(DEFAULT_ARCHIVE, builder.build())]))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/api/extract/file_id/{}?encoding=base64", PNG_FILE_ID);
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 413);
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+        assert!(body.ends_with("Add force=true to encode it anyway."));
+
+        let uri = format!(
+            "/api/extract/file_id/{}?encoding=base64&force=true",
+            PNG_FILE_ID
+        );
+        let response = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        let extracted: serde_json::Value = read_body_json(response).await;
+        let raw = BASE64.decode(extracted["raw"].as_str().unwrap()).unwrap();
+        assert_eq!(raw.len(), MAX_BASE64_BYTES / 2 + 1);
+    }
+
+    #[actix_web::test]
+    async fn summary_reports_file_size_and_modification_time() {
+        let archive = test_archive();
+        let archive_len = archive.len() as u64;
+        let app = init_service(
+            App::new()
+                .app_data(test_state(vec![(DEFAULT_ARCHIVE, archive)]))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response =
+            call_service(&app, TestRequest::get().uri("/api/summary").to_request()).await;
+        let summary: serde_json::Value = read_body_json(response).await;
+        assert_eq!(summary["file_size"], archive_len);
+        // Written just now, so well after the epoch
+        assert!(summary["modified"].as_u64().unwrap() > 1_600_000_000);
+    }
+
+    #[actix_web::test]
+    async fn healthz_answers_while_an_extraction_runs() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let uri = format!("/download/decompressed/file_id/{}", LARGE_FILE_ID);
+        let extraction = std::pin::pin!(call_service(
+            &app,
+            TestRequest::get().uri(&uri).to_request()
+        ));
+        let health = std::pin::pin!(call_service(
+            &app,
+            TestRequest::get().uri("/healthz").to_request()
+        ));
+        // The extraction is polled first; had it run on this worker, it would finish before healthz is polled
+        let extraction = match futures_util::future::select(extraction, health).await {
+            futures_util::future::Either::Right((health, extraction)) => {
+                assert_eq!(health.status(), 200);
+                extraction
+            }
+            futures_util::future::Either::Left(_) => {
+                panic!("the extraction finished before healthz answered")
+            }
+        };
+        let response = extraction.await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(read_body(response).await, large_entry());
+    }
+
+    #[actix_web::test]
+    async fn diff_reports_the_first_differing_offset() {
+        let entry_a: Vec<u8> = (0..100).collect();
+        let mut entry_b = entry_a.clone();
+        entry_b[40] = 0xFF;
+        let mut builder = DatBuilder::new();
+        for (file_id, data) in [(10, &entry_a), (11, &entry_b), (12, &entry_a)] {
+            let base_id = builder.add_entry(compress_entry_data(data), 8);
+            builder.add_file_id(file_id, base_id);
+        }
+        let prefix_base_id = builder.add_entry(entry_a[..60].to_vec(), 0);
+        builder.add_file_id(13, prefix_base_id);
+        let app = init_service(
+            App::new()
+                .app_data(test_state(vec![(DEFAULT_ARCHIVE, builder.build())]))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/api/diff?a=10&b=11").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+        let diff: serde_json::Value = read_body_json(response).await;
+        let context_start = 40 - DIFF_CONTEXT_BYTES;
+        let context_end = 40 + DIFF_CONTEXT_BYTES;
+        assert_eq!(
+            diff,
+            serde_json::json!({
+                "identical": false,
+                "offset": 40,
+                "context_offset": context_start,
+                "a": { "size": 100, "context": hex_bytes(&entry_a[context_start..context_end]) },
+                "b": { "size": 100, "context": hex_bytes(&entry_b[context_start..context_end]) },
+            })
+        );
+
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/api/diff?a=10&b=12").to_request(),
+        )
+        .await;
+        let diff: serde_json::Value = read_body_json(response).await;
+        assert_eq!(diff, serde_json::json!({ "identical": true, "size": 100 }));
+
+        // A prefix differs where it ends
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/api/diff?a=10&b=13").to_request(),
+        )
+        .await;
+        let diff: serde_json::Value = read_body_json(response).await;
+        assert_eq!(diff["offset"], 60);
+        assert_eq!(diff["b"]["size"], 60);
+
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/api/diff?a=10&b=99").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 404);
+        let response =
+            call_service(&app, TestRequest::get().uri("/api/diff?a=10").to_request()).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn tar_export_streams_the_first_members() {
+        let app = init_service(
+            App::new()
+                .app_data(default_state())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/export/tar?limit=2").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header_str(&response, "content-type"), "application/x-tar");
+        let tar = read_body(response).await;
+
+        let mut members = Vec::new();
+        let mut position = 0;
+        while tar[position..position + TAR_BLOCK_SIZE]
+            .iter()
+            .any(|&byte| byte != 0)
+        {
+            let header = &tar[position..position + TAR_BLOCK_SIZE];
+            let name_length = header[..100].iter().position(|&byte| byte == 0).unwrap();
+            let name = String::from_utf8(header[..name_length].to_vec()).unwrap();
+            let size =
+                usize::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap();
+            let checksum =
+                u32::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+            let expected_checksum: u32 = header
+                .iter()
+                .enumerate()
+                .map(|(offset, &byte)| {
+                    if (148..156).contains(&offset) {
+                        b' ' as u32
+                    } else {
+                        byte as u32
+                    }
+                })
+                .sum();
+            assert_eq!(checksum, expected_checksum);
+
+            let contents_start = position + TAR_BLOCK_SIZE;
+            members.push((name, tar[contents_start..contents_start + size].to_vec()));
+            position = contents_start + size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+        }
+        // The archive ends with two zero blocks
+        assert_eq!(tar.len(), position + 2 * TAR_BLOCK_SIZE);
+
+        let texture = texture_file(b"DXT1", 4, 4, &[0x0000_F800, 0]);
+        assert_eq!(
+            members,
+            [
+                (format!("file_{}.png", PNG_FILE_ID), png_entry()),
+                (
+                    format!("file_{}.{}", TEXTURE_FILE_ID, file_extension(&texture)),
+                    texture
+                ),
+            ]
+        );
+    }
+
+    fn args(args: &[&str]) -> std::vec::IntoIter<String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn bind_address_prefers_argument_then_env_then_default() {
+        let default: SocketAddr = DEFAULT_BIND_ADDRESS.parse().unwrap();
+        assert_eq!(resolve_bind_address(args(&[]), None).unwrap(), default);
+        assert_eq!(
+            resolve_bind_address(args(&["--verbose"]), None).unwrap(),
+            default
+        );
+
+        let env_value = Some("0.0.0.0:9000".to_string());
+        assert_eq!(
+            resolve_bind_address(args(&[]), env_value.clone()).unwrap(),
+            "0.0.0.0:9000".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_bind_address(args(&["--bind", "[::1]:8081"]), env_value.clone()).unwrap(),
+            "[::1]:8081".parse().unwrap()
+        );
+        assert_eq!(
+            resolve_bind_address(args(&["--bind=10.0.0.1:80"]), env_value).unwrap(),
+            "10.0.0.1:80".parse().unwrap()
+        );
+        // The last --bind wins
+        assert_eq!(
+            resolve_bind_address(args(&["--bind", "1.2.3.4:1", "--bind=5.6.7.8:2"]), None).unwrap(),
+            "5.6.7.8:2".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn bind_address_rejects_bad_input() {
+        let err = resolve_bind_address(args(&["--bind"]), None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "--bind requires an address, e.g. --bind 0.0.0.0:8080."
+        );
+
+        for (arguments, env_value) in [
+            (args(&["--bind="]), None),
+            (args(&["--bind", "localhost:8080"]), None),
+            (args(&[]), Some("127.0.0.1".to_string())),
+            (args(&[]), Some("not an address".to_string())),
+        ] {
+            let err = resolve_bind_address(arguments, env_value).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(err.to_string().starts_with("Invalid bind address '"));
+        }
+    }
+
+    fn archive_paths(pairs: &[(&str, &str)]) -> Vec<(String, PathBuf)> {
+        pairs
+            .iter()
+            .map(|&(name, path)| (name.to_string(), PathBuf::from(path)))
+            .collect()
+    }
+
+    #[test]
+    fn archive_paths_prefer_arguments_then_env_then_defaults() {
+        assert_eq!(
+            resolve_archive_paths(args(&["--bind", "0.0.0.0:80"]), None).unwrap(),
+            archive_paths(&DEFAULT_ARCHIVE_PATHS)
+        );
+
+        let env_value = Some("Gw2=/env/Gw2.dat; ;Local=/env/Local.dat;".to_string());
+        assert_eq!(
+            resolve_archive_paths(args(&[]), env_value.clone()).unwrap(),
+            archive_paths(&[("Gw2", "/env/Gw2.dat"), ("Local", "/env/Local.dat")])
+        );
+        // Arguments replace the environment entirely, and paths may contain '='
+        assert_eq!(
+            resolve_archive_paths(
+                args(&["--archive", "Gw2=/a b/Gw2.dat", "--archive=Test=/x=y.dat"]),
+                env_value
+            )
+            .unwrap(),
+            archive_paths(&[("Gw2", "/a b/Gw2.dat"), ("Test", "/x=y.dat")])
+        );
+    }
+
+    #[test]
+    fn archive_paths_reject_bad_input() {
+        let err = resolve_archive_paths(args(&["--archive"]), None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "--archive requires a name and path, e.g. --archive Gw2=/games/Gw2.dat."
+        );
+
+        for (arguments, env_value) in [
+            (args(&["--archive=/games/Gw2.dat"]), None),
+            (args(&["--archive", "=/games/Gw2.dat"]), None),
+            (args(&["--archive=Gw2="]), None),
+            (args(&[]), Some("Gw2=/games/Gw2.dat;Local".to_string())),
+        ] {
+            let err = resolve_archive_paths(arguments, env_value).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(err.to_string().starts_with("Invalid archive '"));
+        }
+    }
+}
@@ -1,31 +1,257 @@
 #![allow(dead_code)]
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
 const PF_MAGIC_NUMBER: usize = 2;
 const CHUNK_HEADER_MAGIC_NUMBER: usize = 4;
+/// Expected PF file identifier.
+const PF_MAGIC: [u8; PF_MAGIC_NUMBER] = *b"PF";
+/// Bytes before a chunk's `version` field: the identifier and `chunk_size`.
+const CHUNK_PREFIX_BYTES: u64 = 8;
 
 #[derive(Debug, Default)]
-struct PfHeader {
-    identifier: [u8; PF_MAGIC_NUMBER],
-    version: u16,
-    zero: u16,
-    header_size: u16,
-    chunk_identifier: [u8; CHUNK_HEADER_MAGIC_NUMBER],
+pub struct PfHeader {
+    /// A 2-character ASCII identifier, always "PF".
+    pub identifier: [u8; PF_MAGIC_NUMBER],
+    pub version: u16,
+    pub zero: u16,
+    /// The size of the file header in bytes, typically 12 bytes.
+    pub header_size: u16,
+    /// The type of data in the file, e.g. "MODL" or "AMAT".
+    pub chunk_identifier: [u8; CHUNK_HEADER_MAGIC_NUMBER],
 }
 
 #[derive(Debug, Default)]
-struct PfChunkHeader {
-    identifier: [u8; CHUNK_HEADER_MAGIC_NUMBER],
-    chunk_size: u32,
-    version: u16,
-    header_size: u16,
-    offset_to_offset_table: u32,
+pub struct PfChunkHeader {
+    /// A 4-character ASCII identifier for the chunk.
+    pub identifier: [u8; CHUNK_HEADER_MAGIC_NUMBER],
+    /// Size of the chunk in bytes, counted from the `version` field.
+    pub chunk_size: u32,
+    pub version: u16,
+    /// Size of the chunk header in bytes, counted from the `version` field.
+    pub header_size: u16,
+    /// Offset of the offset table, relative to the start of the chunk payload. Zero if absent.
+    pub offset_to_offset_table: u32,
 }
 
 #[derive(Debug, Default)]
-struct PfChunkData {
-    chunk_header: PfChunkHeader,
-    chunk_data: Vec<u8>,
-    offset_count: u32,
-    offset_data: Vec<u32>,
-    padding: Vec<u8>,
+pub struct PfChunkData {
+    pub chunk_header: PfChunkHeader,
+    /// The chunk payload that follows the chunk header.
+    pub chunk_data: Vec<u8>,
+    /// Number of entries in the offset table.
+    pub offset_count: u32,
+    /// Payload offsets of pointers that need relocating.
+    pub offset_data: Vec<u32>,
+    /// Bytes between the end of the offset table and the end of the chunk.
+    pub padding: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct PfFile {
+    pub pf_header: PfHeader,
+    pub chunks: Vec<PfChunkData>,
+}
+
+impl PfFile {
+    /// Parse a PF container from decompressed entry data.
+    pub fn parse(data: &[u8]) -> std::io::Result<PfFile> {
+        let mut reader = Cursor::new(data);
+        let mut pf_file = PfFile::default();
+
+        reader.read_exact(&mut pf_file.pf_header.identifier)?;
+        if pf_file.pf_header.identifier != PF_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid PF identifier. Expected {:02X?}, found {:02X?}.",
+                    PF_MAGIC, pf_file.pf_header.identifier
+                ),
+            ));
+        }
+        pf_file.pf_header.version = reader.read_u16::<LittleEndian>()?;
+        pf_file.pf_header.zero = reader.read_u16::<LittleEndian>()?;
+        pf_file.pf_header.header_size = reader.read_u16::<LittleEndian>()?;
+        reader.read_exact(&mut pf_file.pf_header.chunk_identifier)?;
+
+        reader.seek(SeekFrom::Start(pf_file.pf_header.header_size as u64))?;
+        while reader.position() + CHUNK_PREFIX_BYTES <= data.len() as u64 {
+            let chunk_start = reader.position();
+            let mut chunk_header = PfChunkHeader::default();
+            reader.read_exact(&mut chunk_header.identifier)?;
+            chunk_header.chunk_size = reader.read_u32::<LittleEndian>()?;
+            chunk_header.version = reader.read_u16::<LittleEndian>()?;
+            chunk_header.header_size = reader.read_u16::<LittleEndian>()?;
+            chunk_header.offset_to_offset_table = reader.read_u32::<LittleEndian>()?;
+
+            let chunk_end = chunk_start + CHUNK_PREFIX_BYTES + chunk_header.chunk_size as u64;
+            let data_start = chunk_start + CHUNK_PREFIX_BYTES + chunk_header.header_size as u64;
+            if chunk_header.header_size as u32 > chunk_header.chunk_size
+                || chunk_end > data.len() as u64
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "PF chunk {} at offset {} extends past the end of the data.",
+                        String::from_utf8_lossy(&chunk_header.identifier),
+                        chunk_start
+                    ),
+                ));
+            }
+
+            let mut chunk = PfChunkData {
+                chunk_header,
+                chunk_data: data[data_start as usize..chunk_end as usize].to_vec(),
+                ..Default::default()
+            };
+            chunk.read_offset_table()?;
+            pf_file.chunks.push(chunk);
+            reader.seek(SeekFrom::Start(chunk_end))?;
+        }
+
+        Ok(pf_file)
+    }
+
+    /// Find the first chunk whose identifier matches `fourcc` exactly.
+    pub fn chunk(&self, fourcc: &[u8; 4]) -> Option<&PfChunkData> {
+        self.chunks_iter()
+            .find(|chunk| &chunk.chunk_header.identifier == fourcc)
+    }
+
+    /// Parse the payload of the chunk `fourcc` as a nested PF container.
+    ///
+    /// Returns `None` if the chunk is absent or its payload is not a valid PF file.
+    pub fn nested(&self, fourcc: &[u8; 4]) -> Option<PfFile> {
+        PfFile::parse(&self.chunk(fourcc)?.chunk_data).ok()
+    }
+
+    /// Iterate over the chunks in file order.
+    pub fn chunks_iter(&self) -> impl Iterator<Item = &PfChunkData> {
+        self.chunks.iter()
+    }
+}
+
+impl PfChunkData {
+    /// Read the offset table and trailing padding from the chunk payload.
+    fn read_offset_table(&mut self) -> std::io::Result<()> {
+        let table_offset = self.chunk_header.offset_to_offset_table as u64;
+        if table_offset == 0 {
+            return Ok(());
+        }
+
+        let payload_len = self.chunk_data.len() as u64;
+        let out_of_bounds = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "PF chunk {} offset table at {} lies outside its {} byte payload.",
+                    String::from_utf8_lossy(&self.chunk_header.identifier),
+                    table_offset,
+                    payload_len
+                ),
+            )
+        };
+        if table_offset + 4 > payload_len {
+            return Err(out_of_bounds());
+        }
+
+        let mut reader = Cursor::new(&self.chunk_data);
+        reader.seek(SeekFrom::Start(table_offset))?;
+        let offset_count = reader.read_u32::<LittleEndian>()?;
+        if reader.position() + offset_count as u64 * 4 > payload_len {
+            return Err(out_of_bounds());
+        }
+
+        let mut offset_data = Vec::with_capacity(offset_count as usize);
+        for _ in 0..offset_count {
+            offset_data.push(reader.read_u32::<LittleEndian>()?);
+        }
+        self.padding = self.chunk_data[reader.position() as usize..].to_vec();
+        self.offset_count = offset_count;
+        self.offset_data = offset_data;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a PF file of `chunks`, each given as its identifier, payload and offset table offset.
+    fn pf_file(chunk_identifier: &[u8; 4], chunks: &[(&[u8; 4], Vec<u8>, u32)]) -> Vec<u8> {
+        let mut data = PF_MAGIC.to_vec();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&12u16.to_le_bytes());
+        data.extend_from_slice(chunk_identifier);
+        for (identifier, payload, offset_to_offset_table) in chunks {
+            data.extend_from_slice(*identifier);
+            data.extend_from_slice(&(8 + payload.len() as u32).to_le_bytes());
+            data.extend_from_slice(&1u16.to_le_bytes());
+            data.extend_from_slice(&8u16.to_le_bytes());
+            data.extend_from_slice(&offset_to_offset_table.to_le_bytes());
+            data.extend_from_slice(payload);
+        }
+        data
+    }
+
+    #[test]
+    fn chunk_finds_payload_by_exact_fourcc() {
+        let data = pf_file(
+            b"MODL",
+            &[(b"GEOM", vec![1, 2, 3], 0), (b"AMAT", vec![9; 5], 0)],
+        );
+        let pf_file = PfFile::parse(&data).unwrap();
+
+        assert_eq!(&pf_file.pf_header.chunk_identifier, b"MODL");
+        let identifiers: Vec<&[u8; 4]> = pf_file
+            .chunks_iter()
+            .map(|chunk| &chunk.chunk_header.identifier)
+            .collect();
+        assert_eq!(identifiers, [b"GEOM", b"AMAT"]);
+        assert_eq!(pf_file.chunk(b"AMAT").unwrap().chunk_data, vec![9; 5]);
+        assert!(pf_file.chunk(b"AMA ").is_none());
+        assert!(pf_file.chunk(b"amat").is_none());
+    }
+
+    #[test]
+    fn offset_table_is_read_from_the_chunk_payload() {
+        let mut payload = vec![0xAA; 4];
+        payload.extend_from_slice(&2u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&8u32.to_le_bytes());
+        payload.extend_from_slice(&[0, 0]);
+        let pf_file = PfFile::parse(&pf_file(b"MODL", &[(b"GEOM", payload, 4)])).unwrap();
+
+        let chunk = pf_file.chunk(b"GEOM").unwrap();
+        assert_eq!(chunk.offset_count, 2);
+        assert_eq!(chunk.offset_data, vec![0, 8]);
+        assert_eq!(chunk.padding, vec![0, 0]);
+    }
+
+    #[test]
+    fn offset_table_past_the_payload_is_rejected() {
+        let mut payload = vec![0xAA; 4];
+        payload.extend_from_slice(&3u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        let err = PfFile::parse(&pf_file(b"MODL", &[(b"GEOM", payload.clone(), 4)])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let err = PfFile::parse(&pf_file(b"MODL", &[(b"GEOM", payload, 100)])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn nested_parses_a_chunk_holding_another_pf_file() {
+        let inner = pf_file(b"GEOM", &[(b"MESH", vec![7; 6], 0)]);
+        let data = pf_file(b"MODL", &[(b"GEOM", inner, 0), (b"AMAT", vec![9; 5], 0)]);
+        let pf_file = PfFile::parse(&data).unwrap();
+
+        let nested = pf_file.nested(b"GEOM").unwrap();
+        assert_eq!(&nested.pf_header.chunk_identifier, b"GEOM");
+        assert_eq!(nested.chunk(b"MESH").unwrap().chunk_data, vec![7; 6]);
+
+        assert!(pf_file.nested(b"AMAT").is_none());
+        assert!(pf_file.nested(b"NONE").is_none());
+    }
 }
@@ -0,0 +1,50 @@
+//! A `log` backend for tests that records every message instead of printing it.
+//!
+//! The logger is global to the test process, so tests see each other's messages and
+//! should look for the ones they expect rather than count them.
+
+use std::sync::{Mutex, Once};
+
+struct CapturingLogger {
+    records: Mutex<Vec<(log::Level, String)>>,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+/// Install the capturing logger at every level, once per process.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+}
+
+/// The level of every logged message containing `text`, in logging order.
+pub(crate) fn levels_of(text: &str) -> Vec<log::Level> {
+    LOGGER
+        .records
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, message)| message.contains(text))
+        .map(|&(level, _)| level)
+        .collect()
+}
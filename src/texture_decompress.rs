@@ -4,70 +4,13 @@
 #![allow(unused_assignments)]
 #![allow(unused_mut)]
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{Cursor, Seek};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use image::RgbaImage;
+use log::{debug, error, warn};
+use std::io::{Cursor, Read, Seek};
 
-const MAX_BITS_HASH: usize = 8;
-const MAX_CODE_BITS_LENGTH: usize = 32;
-const MAX_SYMBOL_VALUE: usize = 285;
-
-const SKIPPED_BYTES_PER_CHUNK: usize = 16384; // 0x4000
-const BYTES_TO_REMOVE: usize = 4; // sizeof(u32)
-
-#[derive(Debug, Default)]
-struct StateData {
-    input_buffer: Cursor<Vec<u8>>,
-    buffer_position: u64,
-    bytes_available: u32,
-    skipped_bytes: u32,
-    head_data: u32,
-    buffer_data: u32,
-    bytes_available_data: u8,
-}
-
-#[derive(Debug)]
-struct HuffmanTree {
-    code_comparison: [u32; MAX_CODE_BITS_LENGTH],
-    symbol_value_offset: [u16; MAX_CODE_BITS_LENGTH],
-    code_bits: [u8; MAX_CODE_BITS_LENGTH],
-    symbol_value: [u16; MAX_SYMBOL_VALUE],
-    symbol_value_hash_exist: [bool; 1 << MAX_BITS_HASH],
-    symbol_value_hash: [u16; 1 << MAX_BITS_HASH],
-    code_bits_hash: [u8; 1 << MAX_BITS_HASH],
-}
-
-impl Default for HuffmanTree {
-    fn default() -> Self {
-        HuffmanTree {
-            code_comparison: [0; MAX_CODE_BITS_LENGTH],
-            symbol_value_offset: [0; MAX_CODE_BITS_LENGTH],
-            code_bits: [0; MAX_CODE_BITS_LENGTH],
-            symbol_value: [0; MAX_SYMBOL_VALUE],
-            symbol_value_hash_exist: [false; 1 << MAX_BITS_HASH],
-            symbol_value_hash: [0; 1 << MAX_BITS_HASH],
-            code_bits_hash: [0; 1 << MAX_BITS_HASH],
-        }
-    }
-}
-
-#[derive(Debug)]
-struct HuffmanTreeBuilder {
-    bits_head_exist: [bool; MAX_CODE_BITS_LENGTH],
-    bits_head: [u16; MAX_CODE_BITS_LENGTH],
-    bits_body_exist: [bool; MAX_SYMBOL_VALUE],
-    bits_body: [u16; MAX_SYMBOL_VALUE],
-}
-
-impl Default for HuffmanTreeBuilder {
-    fn default() -> Self {
-        HuffmanTreeBuilder {
-            bits_head_exist: [false; MAX_CODE_BITS_LENGTH],
-            bits_head: [0; MAX_CODE_BITS_LENGTH],
-            bits_body_exist: [false; MAX_SYMBOL_VALUE],
-            bits_body: [0; MAX_SYMBOL_VALUE],
-        }
-    }
-}
+use crate::bitreader::{StateData, drop_bits, read_bits};
+use crate::huffman::{HuffmanTree, HuffmanTreeBuilder, add_symbol, build_huffmantree, read_code};
 
 #[derive(Debug, Default, Clone, Copy)]
 struct Format {
@@ -86,6 +29,8 @@ struct FullFormat {
     height: u16,
 }
 
+// Named after the flags of the original C++ decoder
+#[allow(clippy::enum_variant_names)]
 enum FormatFlags {
     FfColor = 0x10,
     FfAlpha = 0x20,
@@ -101,186 +46,388 @@ enum CompressionFlags {
     CfDecodePlainColor = 0x08,
 }
 
-fn pull_byte(
-    state_data: &mut StateData,
-    head_data: &mut u32,
-    bytes_available_data: &mut u8,
-) -> std::io::Result<()> {
-    if state_data.bytes_available >= std::mem::size_of::<u32>() as u32 {
-        if state_data.skipped_bytes != 0 {
-            if ((state_data.buffer_position / std::mem::size_of::<u32>() as u64) + 1)
-                % state_data.skipped_bytes as u64
-                == 0
-            {
-                state_data.bytes_available -= std::mem::size_of::<u32>() as u32;
-                state_data.input_buffer.read_u32::<LittleEndian>()?; // Skipping 4 bytes, for CRC probably
-                state_data.buffer_position = state_data.input_buffer.position();
-            }
-        }
-        *head_data = state_data.input_buffer.read_u32::<LittleEndian>()?;
-        state_data.bytes_available -= std::mem::size_of::<u32>() as u32;
-        state_data.buffer_position = state_data.input_buffer.position();
-        *bytes_available_data = (std::mem::size_of::<u32>() as u32 * 8) as u8;
-    } else {
-        *head_data = 0;
-        *bytes_available_data = 0;
-    }
-    Ok(())
+/// Size of the ATEX header: identifier, fourcc, width and height.
+pub(crate) const TEXTURE_HEADER_SIZE: usize = 12;
+
+/// The identifier at the start of a texture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureMagic {
+    /// "ATEX", a generic texture.
+    Atex,
+    /// "ATEC", a texture variant used by some game content.
+    Atec,
+    /// "ATEP", a texture variant used by map content.
+    Atep,
 }
 
-fn read_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<u32> {
-    if state_data.bytes_available_data < bits_number {
-        println!(
-            "Not enough bits available to read the value. in position : {}",
-            state_data.input_buffer.position()
-        );
-    }
-    Ok(state_data.head_data >> (std::mem::size_of::<u32>() as u8 * 8) - bits_number)
+/// A recognized texture header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureKind {
+    pub magic: TextureMagic,
+    /// The compression fourcc following the identifier, e.g. 0x31545844 for "DXT1".
+    /// `None` when the buffer ends before it.
+    pub fourcc: Option<u32>,
+}
+
+/// Check whether `data` starts with a texture identifier.
+pub fn is_texture(data: &[u8]) -> Option<TextureKind> {
+    let magic = match data.get(0..4)? {
+        b"ATEX" => TextureMagic::Atex,
+        b"ATEC" => TextureMagic::Atec,
+        b"ATEP" => TextureMagic::Atep,
+        _ => return None,
+    };
+    let fourcc = data
+        .get(4..8)
+        .map(|fourcc| u32::from_le_bytes([fourcc[0], fourcc[1], fourcc[2], fourcc[3]]));
+
+    Some(TextureKind { magic, fourcc })
 }
 
-fn drop_bits(state_data: &mut StateData, bits_number: u8) -> std::io::Result<()> {
-    if state_data.bytes_available_data < bits_number {
-        println!("Too much bits were asked to be dropped.");
+/// Read the fourcc, width and height from the header of a texture file.
+fn read_texture_header(data: &[u8]) -> std::io::Result<(u32, u16, u16)> {
+    if is_texture(data).is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Data does not start with a texture identifier.",
+        ));
     }
-    #[allow(unused_assignments)]
-    let mut new_bits_available: u8 = 0;
-    new_bits_available = state_data.bytes_available_data.wrapping_sub(bits_number);
-    if new_bits_available >= std::mem::size_of::<u32>() as u8 * 8 {
-        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
-            state_data.head_data = state_data.buffer_data;
-            state_data.buffer_data = 0;
-        } else {
-            state_data.head_data = (state_data.head_data << bits_number)
-                | (state_data.buffer_data >> (std::mem::size_of::<u32>() as u8 * 8) - bits_number);
-            state_data.buffer_data = state_data.buffer_data << bits_number;
-        }
-        state_data.bytes_available_data = new_bits_available;
-    } else {
-        let mut new_value: u32 = 0;
-        let mut pulled_bits: u8 = 0;
-        pull_byte(state_data, &mut new_value, &mut pulled_bits)?;
+    if data.len() < TEXTURE_HEADER_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "Texture data is shorter than its header.",
+        ));
+    }
+    let fourcc_format = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let width = u16::from_le_bytes([data[8], data[9]]);
+    let height = u16::from_le_bytes([data[10], data[11]]);
+    Ok((fourcc_format, width, height))
+}
 
-        if bits_number == std::mem::size_of::<u32>() as u8 * 8 {
-            state_data.head_data = 0;
-        } else {
-            state_data.head_data = state_data.head_data << bits_number;
+/// Read a texture's width, height and format name from its header, without inflating any blocks.
+///
+/// Since nothing is decoded, errors never carry a `DecompressError`: a short header is
+/// `UnexpectedEof` and an unknown fourcc is `Unsupported`.
+pub fn probe_dimensions(data: &[u8]) -> std::io::Result<(u16, u16, &'static str)> {
+    let (fourcc_format, width, height) = read_texture_header(data)?;
+    let format_name = fourcc_name(fourcc_format).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Unsupported texture fourcc {:#010X}.", fourcc_format),
+        )
+    })?;
+    Ok((width, height, format_name))
+}
+
+/// Decode an ATEX texture file into tightly packed RGBA8 pixels.
+///
+/// Returns the width, height and a buffer of `width * height * 4` bytes.
+///
+/// Errors are `io::Error` by design (see `DecompressError`). When inflating the blocks failed,
+/// `DecompressError::from_io` returns the cause; short headers and unsupported formats have none.
+pub fn decode_to_rgba8(data: &[u8]) -> std::io::Result<(u16, u16, Vec<u8>)> {
+    let (fourcc_format, width, height) = read_texture_header(data)?;
+
+    let mut output_data_size: u32 = 0;
+    let mut block_data: Vec<u8> = Vec::new();
+    inflate_texture_file_buffer(data.to_vec(), &mut output_data_size, &mut block_data)?;
+
+    let block_kind = match fourcc_format {
+        // DXT1
+        0x31545844 => DxtBlockKind::Bc1,
+        // DXT2, DXT3
+        0x32545844 | 0x33545844 => DxtBlockKind::Bc2,
+        // DXT4, DXT5
+        0x34545844 | 0x35545844 => DxtBlockKind::Bc3,
+        // 3DCX
+        0x58434433 => DxtBlockKind::Bc5,
+        // DXTA
+        0x41545844 => DxtBlockKind::Bc4,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!(
+                    "Decoding {} textures to RGBA is not supported.",
+                    fourcc_name(fourcc_format).unwrap_or("unknown")
+                ),
+            ));
         }
-        state_data.head_data |= (state_data.buffer_data
-            >> ((std::mem::size_of::<u32>() as u8 * 8) - bits_number))
-            | (new_value >> (new_bits_available));
-        if new_bits_available > 0 {
-            state_data.buffer_data =
-                new_value << (std::mem::size_of::<u32>() as u8 * 8) - new_bits_available;
+    };
+
+    Ok((
+        width,
+        height,
+        decode_dxt_blocks(&block_data, block_kind, width, height),
+    ))
+}
+
+/// Decode an ATEX texture file into an `image::RgbaImage`, ready to resize or encode.
+///
+/// Fails with the same `io::Error`s as `decode_to_rgba8`.
+pub fn decode_to_image(data: &[u8]) -> std::io::Result<RgbaImage> {
+    let (width, height, pixels) = decode_to_rgba8(data)?;
+    RgbaImage::from_raw(width as u32, height as u32, pixels).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Decoded pixel buffer does not match texture dimensions.",
+        )
+    })
+}
+
+/// Size of a DDS file header, including the "DDS " identifier.
+const DDS_HEADER_SIZE: usize = 128;
+
+/// Inflate an ATEX texture file and wrap its compressed blocks in a DDS file.
+///
+/// Only the top mip level is written. 3DCX textures are stored with the "ATI2" fourcc.
+pub fn to_dds(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let (fourcc_format, width, height) = read_texture_header(data)?;
+
+    let (dds_fourcc, block_size) = match fourcc_format {
+        // DXT1
+        0x31545844 => (fourcc_format, 8),
+        // DXT2, DXT3, DXT4, DXT5
+        0x32545844 | 0x33545844 | 0x34545844 | 0x35545844 => (fourcc_format, 16),
+        // 3DCX
+        0x58434433 => (u32::from_le_bytes(*b"ATI2"), 16),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!(
+                    "Writing {} textures as DDS is not supported.",
+                    fourcc_name(fourcc_format).unwrap_or("unknown")
+                ),
+            ));
         }
-        state_data.bytes_available_data = new_bits_available + pulled_bits;
+    };
+
+    let mut output_data_size: u32 = 0;
+    let mut block_data: Vec<u8> = Vec::new();
+    inflate_texture_file_buffer(data.to_vec(), &mut output_data_size, &mut block_data)?;
+
+    let linear_size = (width as usize).div_ceil(4) * (height as usize).div_ceil(4) * block_size;
+    if block_data.len() < linear_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!(
+                "Texture has {} bytes of blocks, expected {}.",
+                block_data.len(),
+                linear_size
+            ),
+        ));
     }
-    Ok(())
+
+    let mut dds_data = Vec::with_capacity(DDS_HEADER_SIZE + linear_size);
+    dds_data.extend_from_slice(b"DDS ");
+    // DDS_HEADER: size, flags (caps, height, width, pixel format, linear size)
+    dds_data.write_u32::<LittleEndian>(124)?;
+    dds_data.write_u32::<LittleEndian>(0x0008_1007)?;
+    dds_data.write_u32::<LittleEndian>(height as u32)?;
+    dds_data.write_u32::<LittleEndian>(width as u32)?;
+    dds_data.write_u32::<LittleEndian>(linear_size as u32)?;
+    // Depth, mip map count and 11 reserved values
+    dds_data.resize(dds_data.len() + 13 * 4, 0);
+    // DDS_PIXELFORMAT: size, flags (fourcc), fourcc, then unused bit count and masks
+    dds_data.write_u32::<LittleEndian>(32)?;
+    dds_data.write_u32::<LittleEndian>(0x4)?;
+    dds_data.write_u32::<LittleEndian>(dds_fourcc)?;
+    dds_data.resize(dds_data.len() + 5 * 4, 0);
+    // Caps (texture), then caps2 to caps4 and a reserved value
+    dds_data.write_u32::<LittleEndian>(0x1000)?;
+    dds_data.resize(DDS_HEADER_SIZE, 0);
+
+    dds_data.extend_from_slice(&block_data[..linear_size]);
+    Ok(dds_data)
 }
 
-fn read_code(
-    huffmantree_data: &mut HuffmanTree,
-    state_data: &mut StateData,
-    symbol_data: &mut u16,
-) -> std::io::Result<()> {
-    let index_num = read_bits(state_data, MAX_BITS_HASH as u8)? as usize;
+/// Largest width or height accepted by `inflate_texture_file_buffer` and `inflate_texture_block_buffer`.
+pub const DEFAULT_MAX_TEXTURE_DIMENSION: u16 = 8192;
 
-    let exist = huffmantree_data.symbol_value_hash_exist[index_num];
+/// Inflate an ATEX/ATEC/ATEP texture file into its compressed blocks.
+///
+/// `input_data` is the decompressed data of a DAT entry, as returned by `DatFile::extract_mft_data`.
+/// The archive's chunk CRCs are stripped before the entry is inflated, so the texture stream carries none.
+pub fn inflate_texture_file_buffer(
+    input_data: Vec<u8>,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    inflate_texture_file_buffer_bounded(
+        input_data,
+        DEFAULT_MAX_TEXTURE_DIMENSION,
+        output_data_size,
+        output_data,
+    )
+}
 
-    if exist {
-        *symbol_data = huffmantree_data.symbol_value_hash
-            [read_bits(state_data, MAX_BITS_HASH as u8)? as usize];
+/// Like `inflate_texture_file_buffer`, but fail before allocating if the width or height exceeds `max_dimension`.
+pub fn inflate_texture_file_buffer_bounded(
+    input_data: Vec<u8>,
+    max_dimension: u16,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let mut state_data = StateData::new(&input_data)?;
 
-        let code_bits_hash =
-            huffmantree_data.code_bits_hash[read_bits(state_data, MAX_BITS_HASH as u8)? as usize];
+    // Skipping the ATEX/ATEC/ATEP identifier
+    drop_bits(&mut state_data, 32)?;
 
-        drop_bits(state_data, code_bits_hash)?;
-    } else {
-        let mut index_data: u16 = 0;
-        while read_bits(state_data, 32)? < huffmantree_data.code_comparison[index_data as usize] {
-            index_data = index_data.wrapping_add(1);
-        }
+    let fourcc_format = read_bits(&mut state_data, 32)?;
+    drop_bits(&mut state_data, 32)?;
 
-        let temp_bits: u8 = huffmantree_data.code_bits[index_data as usize];
+    let width = read_bits(&mut state_data, 16)? as u16;
+    drop_bits(&mut state_data, 16)?;
+    let height = read_bits(&mut state_data, 16)? as u16;
+    drop_bits(&mut state_data, 16)?;
+    check_texture_dimensions(width, height, max_dimension)?;
 
-        // Step 1: Read 32 bits from state_data
-        let read_bits_value = read_bits(state_data, 32)?;
+    if let Some(top_level_size) =
+        uncompressed_payload_size(&input_data, fourcc_format, width, height)
+    {
+        debug!(
+            "Texture payload is uncompressed, copying {} bytes of blocks.",
+            top_level_size
+        );
+        let payload = &input_data[TEXTURE_HEADER_SIZE..TEXTURE_HEADER_SIZE + top_level_size];
+        output_data.clear();
+        output_data.extend_from_slice(payload);
+        *output_data_size = top_level_size as u32;
+        return Ok(());
+    }
 
-        // Step 2: Subtract code_comparison from read_bits_value (with wrapping)
-        let adjusted_bits = read_bits_value
-            .wrapping_sub(huffmantree_data.code_comparison[index_data as usize] as u32);
+    inflate_texture(
+        &mut state_data,
+        output_data_size,
+        output_data,
+        width,
+        height,
+        fourcc_format,
+    )
+}
 
-        // Step 3: Perform the right shift operation (with wrapping)
-        let shifted_bits = adjusted_bits.wrapping_shr((32 - temp_bits as u16) as u32);
+/// Fail with `InvalidData` before anything is allocated if `width` or `height` exceeds `max_dimension`.
+fn check_texture_dimensions(width: u16, height: u16, max_dimension: u16) -> std::io::Result<()> {
+    if width > max_dimension || height > max_dimension {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Texture dimensions {}x{} exceed the limit of {} pixels per side.",
+                width, height, max_dimension
+            ),
+        ));
+    }
+    Ok(())
+}
 
-        // Step 4: Subtract the shifted value from the symbol_value_offset (with wrapping)
-        let symbol_index = huffmantree_data.symbol_value_offset[index_data as usize]
-            .wrapping_sub(shifted_bits as u16) as usize;
+/// Detect a texture whose payload is plain DXT blocks with no huffman layer.
+///
+/// A compressed payload starts with its own byte length, while an uncompressed one is exactly
+/// the block data of the top level, or of the full mip chain. Returns the size of the top level.
+///
+/// Telling the two apart is a heuristic, not a flag in the header: a payload whose first u32
+/// equals its own byte length is always treated as compressed, even if its size also matches
+/// the block data.
+fn uncompressed_payload_size(
+    data: &[u8],
+    fourcc_format: u32,
+    width: u16,
+    height: u16,
+) -> Option<usize> {
+    let (format_index, _) = fourcc_format_index(fourcc_format)?;
+    let block_bytes = texture_formats()[format_index].pixel_size_bits as usize * 16 / 8;
+    let payload = data.get(TEXTURE_HEADER_SIZE..)?;
+    if payload.len() >= 4 {
+        let declared_size = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        if declared_size as usize == payload.len() {
+            return None;
+        }
+    }
 
-        // Step 5: Retrieve the symbol_data using the calculated index
-        *symbol_data = huffmantree_data.symbol_value[symbol_index];
+    let level_size = |w: u32, h: u32| (w.div_ceil(4) * h.div_ceil(4)) as usize * block_bytes;
+    let top_level_size = level_size(width as u32, height as u32);
+    if top_level_size == 0 {
+        return None;
+    }
 
-        drop_bits(state_data, temp_bits)?;
+    let (mut w, mut h) = (width as u32, height as u32);
+    let mut chain_size = top_level_size;
+    while w > 1 || h > 1 {
+        w = (w / 2).max(1);
+        h = (h / 2).max(1);
+        chain_size += level_size(w, h);
     }
-    Ok(())
+
+    (payload.len() == top_level_size || payload.len() == chain_size).then_some(top_level_size)
 }
 
-pub fn inflate_texture_file_buffer(
+/// Inflate a texture block that has no ATEX header; the format and dimensions are given by the caller.
+///
+/// Dimensions above `DEFAULT_MAX_TEXTURE_DIMENSION` are rejected.
+pub fn inflate_texture_block_buffer(
     input_data: Vec<u8>,
     output_data_size: &mut u32,
     output_data: &mut Vec<u8>,
+    width: u16,
+    height: u16,
+    fourcc_format: u32,
+) -> std::io::Result<()> {
+    check_texture_dimensions(width, height, DEFAULT_MAX_TEXTURE_DIMENSION)?;
+    let mut state_data = StateData::new(&input_data)?;
+
+    inflate_texture(
+        &mut state_data,
+        output_data_size,
+        output_data,
+        width,
+        height,
+        fourcc_format,
+    )
+}
+
+fn inflate_texture(
+    state_data: &mut StateData,
+    output_data_size: &mut u32,
+    output_data: &mut Vec<u8>,
+    width: u16,
+    height: u16,
+    fourcc_format: u32,
 ) -> std::io::Result<()> {
     let mut texture_huffmantree_dict = HuffmanTree::default();
     let mut format_data: Vec<Format> = Vec::new();
 
     initialize_static_values(&mut texture_huffmantree_dict, &mut format_data)?;
 
-    let mut state_data = StateData::default();
-    state_data.bytes_available = input_data.len() as u32;
-    state_data.input_buffer = Cursor::new(input_data);
-    state_data.skipped_bytes = 0 as u32;
-    let mut head_data: u32 = 0;
-    let mut bytes_available_data: u8 = 0;
-
-    pull_byte(&mut state_data, &mut head_data, &mut bytes_available_data)?;
-
-    state_data.head_data = head_data;
-    state_data.bytes_available_data = bytes_available_data;
-
-    drop_bits(&mut state_data, 32)?;
-
-    let mut fourcc_format: u32 = 0;
-    fourcc_format = read_bits(&mut state_data, 32)?;
-    drop_bits(&mut state_data, 32)?;
-
-    let mut full_format_data = FullFormat::default();
-    full_format_data.format = deduce_format(fourcc_format, format_data)?;
-
-    full_format_data.width = read_bits(&mut state_data, 16)? as u16;
-    drop_bits(&mut state_data, 16)?;
-    full_format_data.height = read_bits(&mut state_data, 16)? as u16;
-    drop_bits(&mut state_data, 16)?;
+    let (format, _) = deduce_format(fourcc_format, format_data)?;
+    let plain_flags =
+        FormatFlags::FfPlaincomp as u16 | FormatFlags::FfColor as u16 | FormatFlags::FfAlpha as u16;
+    let mut full_format_data = FullFormat {
+        format,
+        width,
+        height,
+        two_component: (format.flag_data & plain_flags) == plain_flags
+            || (format.flag_data & FormatFlags::FfBicolorcomp as u16) != 0,
+        ..Default::default()
+    };
 
     full_format_data.pixel_blocks =
-        ((full_format_data.width as u32 + 3) / 4) * ((full_format_data.height as u32 + 3) / 4);
+        (full_format_data.width as u32).div_ceil(4) * (full_format_data.height as u32).div_ceil(4);
     full_format_data.bytes_pixel_blocks =
         (full_format_data.format.pixel_size_bits as u32 * 4 * 4) / 8;
     full_format_data.bytes_component =
         full_format_data.bytes_pixel_blocks / if full_format_data.two_component { 2 } else { 1 };
 
-    let mut texture_output_size: u32 = 0;
-    texture_output_size = full_format_data.bytes_pixel_blocks * full_format_data.pixel_blocks;
+    let texture_output_size = full_format_data.bytes_pixel_blocks * full_format_data.pixel_blocks;
 
-    if (*output_data_size != 0 && *output_data_size < texture_output_size) {
-        println!("Output buffer is too small.");
+    if *output_data_size != 0 && *output_data_size < texture_output_size {
+        warn!("Output buffer is too small.");
     }
     *output_data_size = texture_output_size;
 
     output_data.resize(*output_data_size as usize, 0);
 
     inflate_texture_data(
-        &mut state_data,
+        state_data,
         &full_format_data,
-        &mut texture_output_size,
         output_data,
         &mut texture_huffmantree_dict,
     )?;
@@ -291,8 +438,7 @@ pub fn inflate_texture_file_buffer(
 fn inflate_texture_data(
     state_data: &mut StateData,
     fullformat_data: &FullFormat,
-    texture_output_data_size: &mut u32,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
     texture_huffmantree_dict: &mut HuffmanTree,
 ) -> std::io::Result<()> {
     let mut color_bitmap_data: Vec<bool> = Vec::new();
@@ -303,24 +449,19 @@ fn inflate_texture_data(
     let mut data_size: u32 = 0;
     data_size = read_bits(state_data, 32)?;
     drop_bits(state_data, 32)?;
-    println!("Data size : {}", data_size);
     let mut compression_flag_data: u32 = 0;
     compression_flag_data = read_bits(state_data, 32)?;
     drop_bits(state_data, 32)?;
-    println!("Compression flags : {}", compression_flag_data);
 
-    println!(
-        "full_format_data.pixel_blocks : {}",
-        fullformat_data.pixel_blocks
+    // Silent unless debug logging is enabled, since this runs once per decoded texture
+    debug!(
+        "Texture data size {}, compression flags {:#x}, {} pixel blocks.",
+        data_size, compression_flag_data, fullformat_data.pixel_blocks
     );
     color_bitmap_data.resize(fullformat_data.pixel_blocks as usize, false);
     alpha_bitmap_data.resize(fullformat_data.pixel_blocks as usize, false);
 
     if (compression_flag_data & CompressionFlags::CfDecodeWhiteColor as u32) != 0 {
-        println!(
-            "Checking CfDecodeWhiteColor: {}",
-            12 & CompressionFlags::CfDecodeWhiteColor as i32
-        );
         decode_white_color(
             state_data,
             texture_huffmantree_dict,
@@ -332,10 +473,6 @@ fn inflate_texture_data(
     }
 
     if (compression_flag_data & CompressionFlags::CfDecodeConstantAlphaFrom4bits as u32) != 0 {
-        println!(
-            "Checking CfDecodeConstantAlphaFrom4bits: {}",
-            12 & CompressionFlags::CfDecodeConstantAlphaFrom4bits as i32
-        );
         decode_constant_alpha_from_4_bits(
             state_data,
             texture_huffmantree_dict,
@@ -346,10 +483,6 @@ fn inflate_texture_data(
     }
 
     if (compression_flag_data & CompressionFlags::CfDecodeConstantAlphaFrom8bits as u32) != 0 {
-        println!(
-            "Checking CfDecodeConstantAlphaFrom8bits: {}",
-            12 & CompressionFlags::CfDecodeConstantAlphaFrom8bits as i32
-        );
         decode_constant_alpha_from_8_bits(
             state_data,
             texture_huffmantree_dict,
@@ -360,10 +493,6 @@ fn inflate_texture_data(
     }
 
     if (compression_flag_data & CompressionFlags::CfDecodePlainColor as u32) != 0 {
-        println!(
-            "Checking CfDecodePlainColor: {}",
-            12 & CompressionFlags::CfDecodePlainColor as i32
-        );
         decode_plain_color(
             state_data,
             texture_huffmantree_dict,
@@ -373,90 +502,143 @@ fn inflate_texture_data(
         )?;
     }
 
-    let mut loop_index_data: u32 = 0;
+    // Whatever was not covered by the bitmaps is stored as raw 32-bit words
     if state_data.bytes_available_data >= 32 {
-        state_data
-            .input_buffer
-            .seek(std::io::SeekFrom::Current(-1))?;
-        state_data.buffer_position = state_data.input_buffer.position();
+        state_data.input_buffer.seek(std::io::SeekFrom::Current(
+            -(std::mem::size_of::<u32>() as i64),
+        ))?;
+        state_data.buffer_position_bytes = state_data.input_buffer.position();
+    }
+
+    let flag_data = fullformat_data.format.flag_data;
+    let bytes_pixel_blocks = fullformat_data.bytes_pixel_blocks as usize;
+    let bytes_component = fullformat_data.bytes_component as usize;
+    let color_offset = if fullformat_data.two_component {
+        bytes_component
+    } else {
+        0
+    };
+
+    if ((flag_data & FormatFlags::FfAlpha as u16) != 0
+        && (flag_data & FormatFlags::FfDeducedalphacomp as u16) == 0)
+        || (flag_data & FormatFlags::FfBicolorcomp as u16) != 0
+    {
+        for (loop_index_data, _) in alpha_bitmap_data
+            .iter()
+            .enumerate()
+            .filter(|(_, alpha)| !**alpha)
+        {
+            let offset = bytes_pixel_blocks * loop_index_data;
+            copy_raw_word(state_data, &mut output_data[offset..offset + 4])?;
+            if bytes_component > 4 {
+                copy_raw_word(state_data, &mut output_data[offset + 4..offset + 8])?;
+            }
+        }
+    }
+
+    if (flag_data & FormatFlags::FfColor as u16) != 0
+        || (flag_data & FormatFlags::FfBicolorcomp as u16) != 0
+    {
+        for (loop_index_data, _) in color_bitmap_data
+            .iter()
+            .enumerate()
+            .filter(|(_, color)| !**color)
+        {
+            let offset = bytes_pixel_blocks * loop_index_data + color_offset;
+            copy_raw_word(state_data, &mut output_data[offset..offset + 4])?;
+        }
+
+        if bytes_component > 4 {
+            for (loop_index_data, _) in color_bitmap_data
+                .iter()
+                .enumerate()
+                .filter(|(_, color)| !**color)
+            {
+                let offset = bytes_pixel_blocks * loop_index_data + 4 + color_offset;
+                copy_raw_word(state_data, &mut output_data[offset..offset + 4])?;
+            }
+        }
     }
 
     Ok(())
 }
-pub fn inflate_texture_block_buffer(
-    input_data: Vec<u8>,
-    output_data_size: &mut u32,
-    output_data: &mut Vec<u8>,
-    width: u16,
-    height: u16,
-    fourcc_format: u32,
-) -> std::io::Result<()> {
+
+/// Copy the next raw 32-bit word of the input into `destination`.
+fn copy_raw_word(state_data: &mut StateData, destination: &mut [u8]) -> std::io::Result<()> {
+    state_data.input_buffer.read_exact(destination)?;
+    state_data.buffer_position_bytes = state_data.input_buffer.position();
     Ok(())
 }
 
+/// Block layout of each supported fourcc, in the order used by `fourcc_format_index`.
+fn texture_formats() -> Vec<Format> {
+    vec![
+        // Number 1 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16
+                | FormatFlags::FfAlpha as u16
+                | FormatFlags::FfDeducedalphacomp as u16,
+            pixel_size_bits: 4,
+        },
+        // Number 2 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16
+                | FormatFlags::FfAlpha as u16
+                | FormatFlags::FfPlaincomp as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 3 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16
+                | FormatFlags::FfAlpha as u16
+                | FormatFlags::FfPlaincomp as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 4 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16
+                | FormatFlags::FfAlpha as u16
+                | FormatFlags::FfPlaincomp as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 5 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16
+                | FormatFlags::FfAlpha as u16
+                | FormatFlags::FfPlaincomp as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 6 format data
+        Format {
+            flag_data: FormatFlags::FfAlpha as u16 | FormatFlags::FfPlaincomp as u16,
+            pixel_size_bits: 4,
+        },
+        // Number 7 format data
+        Format {
+            flag_data: FormatFlags::FfColor as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 8 format data
+        Format {
+            flag_data: FormatFlags::FfBicolorcomp as u16,
+            pixel_size_bits: 8,
+        },
+        // Number 9 format data
+        Format {
+            flag_data: FormatFlags::FfBicolorcomp as u16,
+            pixel_size_bits: 8,
+        },
+    ]
+}
+
 fn initialize_static_values(
     texture_huffmantree_dict: &mut HuffmanTree,
     format_data: &mut Vec<Format>,
 ) -> std::io::Result<()> {
-    // Number 1 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16
-            | FormatFlags::FfAlpha as u16
-            | FormatFlags::FfDeducedalphacomp as u16,
-        pixel_size_bits: 4,
-    });
-    // Number 2 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16
-            | FormatFlags::FfAlpha as u16
-            | FormatFlags::FfPlaincomp as u16,
-        pixel_size_bits: 8,
-    });
-
-    // Number 3 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16
-            | FormatFlags::FfAlpha as u16
-            | FormatFlags::FfPlaincomp as u16,
-        pixel_size_bits: 8,
-    });
-    // Number 4 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16
-            | FormatFlags::FfAlpha as u16
-            | FormatFlags::FfPlaincomp as u16,
-        pixel_size_bits: 8,
-    });
-    // Number 5 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16
-            | FormatFlags::FfAlpha as u16
-            | FormatFlags::FfPlaincomp as u16,
-        pixel_size_bits: 8,
-    });
-    // Number 6 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfAlpha as u16 | FormatFlags::FfPlaincomp as u16,
-        pixel_size_bits: 4,
-    });
-    // Number 7 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfColor as u16,
-        pixel_size_bits: 8,
-    });
-    // Number 8 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfBicolorcomp as u16,
-        pixel_size_bits: 8,
-    });
-    // Number 9 format data
-    format_data.push(Format {
-        flag_data: FormatFlags::FfBicolorcomp as u16,
-        pixel_size_bits: 8,
-    });
+    format_data.extend(texture_formats());
 
     if !initialize_huffmantree_dict(texture_huffmantree_dict)? {
-        println!("Failed to initialize huffmantree dict!");
+        error!("Failed to initialize huffmantree dict!");
     }
 
     Ok(())
@@ -465,10 +647,10 @@ fn initialize_static_values(
 fn decode_white_color(
     state_data: &mut StateData,
     texture_huffmantree_dict: &mut HuffmanTree,
-    alpha_bitmap: &mut Vec<bool>,
-    color_bitmap: &mut Vec<bool>,
+    alpha_bitmap: &mut [bool],
+    color_bitmap: &mut [bool],
     fullformat_data: &FullFormat,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
 ) -> std::io::Result<()> {
     let mut pixel_block_position: u32 = 0;
     while pixel_block_position < fullformat_data.pixel_blocks {
@@ -477,12 +659,13 @@ fn decode_white_color(
         let mut value_data = 0;
         value_data = read_bits(state_data, 1)?;
         drop_bits(state_data, 1)?;
-        while temp_code > 0 {
+        while temp_code > 0 && pixel_block_position < fullformat_data.pixel_blocks {
             if !color_bitmap[pixel_block_position as usize] {
                 if value_data != 0 {
-                    output_data
-                        [(fullformat_data.bytes_pixel_blocks * pixel_block_position) as usize] =
-                        std::u64::MAX as u8;
+                    let offset =
+                        (fullformat_data.bytes_pixel_blocks * pixel_block_position) as usize;
+                    output_data[offset..offset + 8]
+                        .copy_from_slice(&0xFFFFFFFFFFFFFFFE_u64.to_le_bytes());
                     alpha_bitmap[pixel_block_position as usize] = true;
                     color_bitmap[pixel_block_position as usize] = true;
                 }
@@ -490,12 +673,12 @@ fn decode_white_color(
             }
             pixel_block_position = pixel_block_position.wrapping_add(1);
         }
-    }
 
-    while pixel_block_position < fullformat_data.pixel_blocks
-        && color_bitmap[pixel_block_position as usize]
-    {
-        pixel_block_position = pixel_block_position.wrapping_add(1);
+        while pixel_block_position < fullformat_data.pixel_blocks
+            && color_bitmap[pixel_block_position as usize]
+        {
+            pixel_block_position = pixel_block_position.wrapping_add(1);
+        }
     }
     Ok(())
 }
@@ -503,9 +686,9 @@ fn decode_white_color(
 fn decode_constant_alpha_from_4_bits(
     state_data: &mut StateData,
     texture_huffmantree_dict: &mut HuffmanTree,
-    alpha_bitmap: &mut Vec<bool>,
+    alpha_bitmap: &mut [bool],
     fullformat_data: &FullFormat,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
 ) -> std::io::Result<()> {
     let mut alpha_value_byte: u8 = 0;
     alpha_value_byte = read_bits(state_data, 4)? as u8;
@@ -530,7 +713,7 @@ fn decode_constant_alpha_from_4_bits(
             drop_bits(state_data, 1)?;
         }
 
-        while temp_code > 0 {
+        while temp_code > 0 && pixel_block_position < fullformat_data.pixel_blocks {
             if !alpha_bitmap[pixel_block_position as usize] {
                 if value_data != 0 {
                     let destination = &mut output_data[fullformat_data.bytes_pixel_blocks
@@ -561,16 +744,16 @@ fn decode_constant_alpha_from_4_bits(
 fn decode_constant_alpha_from_8_bits(
     state_data: &mut StateData,
     texture_huffmantree_dict: &mut HuffmanTree,
-    alpha_bitmap: &mut Vec<bool>,
+    alpha_bitmap: &mut [bool],
     fullformat_data: &FullFormat,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
 ) -> std::io::Result<()> {
     let mut alpha_value_byte: u8 = 0;
     alpha_value_byte = read_bits(state_data, 8)? as u8;
     drop_bits(state_data, 8)?;
     let mut pixel_block_position: u32 = 0;
 
-    let mut alpha_value: u64 = (alpha_value_byte | (alpha_value_byte.wrapping_shl(8))) as u64;
+    let mut alpha_value: u64 = alpha_value_byte as u64 | ((alpha_value_byte as u64) << 8);
     let mut zero_data: u64 = 0;
 
     while pixel_block_position < fullformat_data.pixel_blocks {
@@ -586,7 +769,7 @@ fn decode_constant_alpha_from_8_bits(
             drop_bits(state_data, 1)?;
         }
 
-        while temp_code > 0 {
+        while temp_code > 0 && pixel_block_position < fullformat_data.pixel_blocks {
             if !alpha_bitmap[pixel_block_position as usize] {
                 if value_data != 0 {
                     let destination = &mut output_data[fullformat_data.bytes_pixel_blocks
@@ -616,9 +799,9 @@ fn decode_constant_alpha_from_8_bits(
 fn decode_plain_color(
     state_data: &mut StateData,
     texture_huffmantree_dict: &mut HuffmanTree,
-    color_bitmap: &mut Vec<bool>,
+    color_bitmap: &mut [bool],
     fullformat_data: &FullFormat,
-    output_data: &mut Vec<u8>,
+    output_data: &mut [u8],
 ) -> std::io::Result<()> {
     let mut blue_data: u16 = 0;
     blue_data = read_bits(state_data, 8)? as u16;
@@ -650,10 +833,24 @@ fn decode_plain_color(
     let mut comparison_red: u32 = 0;
     let mut comparison_blue: u32 = 0;
     let mut comparison_green: u32 = 0;
-    unimplemented!();
-    // comparison_red = 12 * (red_data - temp_red_data_2) / (8 - ((temp_red_data_1 & 0x11) == 0x11 ? 1 : 0));
-    // comparison_blue = 12 * (blue_data - temp_blue_data_2) / (8 - ((temp_blue_data_1 & 0x11) == 0x11 ? 1 : 0));
-    // comparison_green = 12 * (green_data - temp_green_data_2) / (8 - ((temp_green_data_1 & 0x1111) == 0x1111 ? 1 : 0));
+    comparison_red = (12 * (red_data as i32 - temp_red_data_2 as i32)
+        / (8 - if (temp_red_data_1 & 0x11) == 0x11 {
+            1
+        } else {
+            0
+        })) as u32;
+    comparison_blue = (12 * (blue_data as i32 - temp_blue_data_2 as i32)
+        / (8 - if (temp_blue_data_1 & 0x11) == 0x11 {
+            1
+        } else {
+            0
+        })) as u32;
+    comparison_green = (12 * (green_data as i32 - temp_green_data_2 as i32)
+        / (8 - if (temp_green_data_1 & 0x1111) == 0x1111 {
+            1
+        } else {
+            0
+        })) as u32;
 
     let mut value_red_1: u32 = 0;
     let mut value_red_2: u32 = 0;
@@ -742,8 +939,8 @@ fn decode_plain_color(
         temp_value_2 += 1;
     }
 
-    if (temp_value_2 > 0) {
-        temp_value_1 = (temp_value_1 + (temp_value_2 / 2)) / temp_value_2;
+    if let Some(average) = (temp_value_1 + (temp_value_2 / 2)).checked_div(temp_value_2) {
+        temp_value_1 = average;
     }
 
     let mut special_case_dxt1 = false;
@@ -767,7 +964,7 @@ fn decode_plain_color(
         value_color_1 = value_color_2;
         value_color_2 = swap_temp;
 
-        temp_value_1 = temp_value_1.wrapping_sub(1);
+        temp_value_1 = 12u32.wrapping_sub(temp_value_1);
     }
     let mut color_selected: u32 = 0;
 
@@ -796,7 +993,7 @@ fn decode_plain_color(
     let mut final_value: u64 = 0;
     final_value = value_color_1 as u64
         | (value_color_2.wrapping_shl(16) as u64)
-        | (temp_value.wrapping_shl(32) as u64);
+        | temp_value.wrapping_shl(32);
     let mut pixel_block_position: u32 = 0;
 
     while pixel_block_position < fullformat_data.pixel_blocks {
@@ -806,11 +1003,20 @@ fn decode_plain_color(
         value_data = read_bits(state_data, 1)?;
         drop_bits(state_data, 1)?;
 
-        while temp_code > 0 {
+        while temp_code > 0 && pixel_block_position < fullformat_data.pixel_blocks {
             if !color_bitmap[pixel_block_position as usize] {
                 if value_data != 0 {
+                    let offset = (fullformat_data.bytes_pixel_blocks * pixel_block_position)
+                        as usize
+                        + if fullformat_data.two_component {
+                            fullformat_data.bytes_component as usize
+                        } else {
+                            0
+                        };
+                    let component = (fullformat_data.bytes_component as usize).min(8);
+                    output_data[offset..offset + component]
+                        .copy_from_slice(&final_value.to_le_bytes()[..component]);
                     color_bitmap[pixel_block_position as usize] = true;
-                    unimplemented!()
                 }
                 temp_code = temp_code.wrapping_sub(1);
             }
@@ -826,30 +1032,42 @@ fn decode_plain_color(
     Ok(())
 }
 
-fn deduce_format(fourcc_data: u32, format_data: Vec<Format>) -> std::io::Result<Format> {
-    let mut format_texture = Format::default();
+/// Map a fourcc to its index in the static format table and its name.
+fn fourcc_format_index(fourcc_data: u32) -> Option<(usize, &'static str)> {
     match fourcc_data {
-        // DXT1
-        0x31545844 => format_texture = format_data[0].clone(),
-        // DXT2
-        0x32545844 => format_texture = format_data[1].clone(),
-        // DXT3
-        0x33545844 => format_texture = format_data[2].clone(),
-        // DXT4
-        0x34545844 => format_texture = format_data[3].clone(),
-        // DXT5
-        0x35545844 => format_texture = format_data[4].clone(),
-        // DXTA
-        0x41545844 => format_texture = format_data[5].clone(),
-        // DXTL
-        0x4C545844 => format_texture = format_data[6].clone(),
-        // DXTN
-        0x4E545844 => format_texture = format_data[7].clone(),
-        // 3DCX
-        0x58434433 => format_texture = format_data[8].clone(),
-        _ => println!("Format not found!"),
+        0x31545844 => Some((0, "DXT1")),
+        0x32545844 => Some((1, "DXT2")),
+        0x33545844 => Some((2, "DXT3")),
+        0x34545844 => Some((3, "DXT4")),
+        0x35545844 => Some((4, "DXT5")),
+        0x41545844 => Some((5, "DXTA")),
+        0x4C545844 => Some((6, "DXTL")),
+        0x4E545844 => Some((7, "DXTN")),
+        0x58434433 => Some((8, "3DCX")),
+        _ => None,
+    }
+}
+
+/// The human readable name of a texture fourcc, e.g. "DXT1", or `None` if it is not supported.
+pub fn fourcc_name(fourcc_data: u32) -> Option<&'static str> {
+    fourcc_format_index(fourcc_data).map(|(_, format_name)| format_name)
+}
+
+/// Look up the block format for a texture fourcc.
+///
+/// An unknown fourcc is an `Unsupported` `io::Error`, not a `DecompressError`: it is not a
+/// decoding failure, and the texture entry points all return `io::Result` (see `DecompressError`).
+fn deduce_format(
+    fourcc_data: u32,
+    format_data: Vec<Format>,
+) -> std::io::Result<(Format, &'static str)> {
+    match fourcc_format_index(fourcc_data) {
+        Some((format_index, format_name)) => Ok((format_data[format_index], format_name)),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Unsupported texture fourcc {:#010X}.", fourcc_data),
+        )),
     }
-    Ok(format_texture)
 }
 
 fn initialize_huffmantree_dict(huffmantree_data: &mut HuffmanTree) -> std::io::Result<bool> {
@@ -875,122 +1093,535 @@ fn initialize_huffmantree_dict(huffmantree_data: &mut HuffmanTree) -> std::io::R
     add_symbol(&mut huffmantree_builder, 0x03, 6)?;
     add_symbol(&mut huffmantree_builder, 0x02, 6)?;
 
-    if !build_huffmantree(huffmantree_data, &mut huffmantree_builder)? {
-        return Ok(false);
-    } else {
-        Ok(true)
+    build_huffmantree(huffmantree_data, &mut huffmantree_builder)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DxtBlockKind {
+    /// DXT1: 8 bytes per block, color with 1-bit alpha.
+    Bc1,
+    /// DXT2/DXT3: 16 bytes per block, explicit 4-bit alpha followed by color.
+    Bc2,
+    /// DXT4/DXT5: 16 bytes per block, interpolated alpha followed by color.
+    Bc3,
+    /// DXTA: 8 bytes per block, one interpolated channel. Written as opaque grayscale.
+    Bc4,
+    /// 3DCX: 16 bytes per block, two interpolated channels holding a normal's X and Y.
+    /// Z is reconstructed into the blue channel.
+    Bc5,
+}
+
+/// Convert DXT compressed blocks into RGBA8 pixels, clipping blocks at the image edges.
+fn decode_dxt_blocks(
+    block_data: &[u8],
+    block_kind: DxtBlockKind,
+    width: u16,
+    height: u16,
+) -> Vec<u8> {
+    let width = width as usize;
+    let height = height as usize;
+    let block_size = match block_kind {
+        DxtBlockKind::Bc1 | DxtBlockKind::Bc4 => 8,
+        DxtBlockKind::Bc2 | DxtBlockKind::Bc3 | DxtBlockKind::Bc5 => 16,
+    };
+    let blocks_wide = width.div_ceil(4);
+    let mut pixels = vec![0; width * height * 4];
+
+    for (block_index, block) in block_data.chunks_exact(block_size).enumerate() {
+        let block_x = (block_index % blocks_wide) * 4;
+        let block_y = (block_index / blocks_wide) * 4;
+        if block_y >= height {
+            break;
+        }
+
+        let mut texels = [[0u8; 4]; 16];
+        match block_kind {
+            DxtBlockKind::Bc1 => decode_color_block(&block[0..8], &mut texels, true),
+            DxtBlockKind::Bc2 => {
+                decode_color_block(&block[8..16], &mut texels, false);
+                decode_explicit_alpha_block(&block[0..8], &mut texels);
+            }
+            DxtBlockKind::Bc3 => {
+                decode_color_block(&block[8..16], &mut texels, false);
+                let mut alpha = [0u8; 16];
+                decode_interpolated_block(&block[0..8], &mut alpha);
+                for (texel, alpha) in texels.iter_mut().zip(alpha) {
+                    texel[3] = alpha;
+                }
+            }
+            DxtBlockKind::Bc4 => {
+                let mut gray = [0u8; 16];
+                decode_interpolated_block(&block[0..8], &mut gray);
+                for (texel, gray) in texels.iter_mut().zip(gray) {
+                    *texel = [gray, gray, gray, 0xFF];
+                }
+            }
+            DxtBlockKind::Bc5 => {
+                let mut red = [0u8; 16];
+                let mut green = [0u8; 16];
+                decode_interpolated_block(&block[0..8], &mut red);
+                decode_interpolated_block(&block[8..16], &mut green);
+                for (texel_index, texel) in texels.iter_mut().enumerate() {
+                    let (x, y) = (red[texel_index], green[texel_index]);
+                    *texel = [x, y, reconstruct_normal_z(x, y), 0xFF];
+                }
+            }
+        }
+
+        for (texel_index, texel) in texels.iter().enumerate() {
+            let x = block_x + texel_index % 4;
+            let y = block_y + texel_index / 4;
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                pixels[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
     }
+
+    pixels
 }
 
-fn add_symbol(
-    huffmantree_builder: &mut HuffmanTreeBuilder,
-    symbol_data: u16,
-    bit_data: u8,
-) -> std::io::Result<()> {
-    if huffmantree_builder.bits_head_exist[bit_data as usize] {
-        huffmantree_builder.bits_body[symbol_data as usize] =
-            huffmantree_builder.bits_head[bit_data as usize];
+/// Rebuild the Z component of a unit normal from its stored X and Y, all encoded as `[-1, 1]` mapped to `0..=255`.
+fn reconstruct_normal_z(x: u8, y: u8) -> u8 {
+    let x = x as f32 / 127.5 - 1.0;
+    let y = y as f32 / 127.5 - 1.0;
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    ((z + 1.0) * 127.5).round() as u8
+}
 
-        huffmantree_builder.bits_body_exist[symbol_data as usize] = true;
+/// Expand an RGB565 color to RGB888.
+fn rgb565_to_rgb888(color: u16) -> [u8; 3] {
+    let red = ((color >> 11) & 0x1F) as u8;
+    let green = ((color >> 5) & 0x3F) as u8;
+    let blue = (color & 0x1F) as u8;
+    [
+        (red << 3) | (red >> 2),
+        (green << 2) | (green >> 4),
+        (blue << 3) | (blue >> 2),
+    ]
+}
 
-        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
+/// Decode an 8-byte DXT color block. `one_bit_alpha` enables the DXT1 three-color mode.
+fn decode_color_block(block: &[u8], texels: &mut [[u8; 4]; 16], one_bit_alpha: bool) {
+    let color_0 = u16::from_le_bytes([block[0], block[1]]);
+    let color_1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let rgb_0 = rgb565_to_rgb888(color_0);
+    let rgb_1 = rgb565_to_rgb888(color_1);
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [rgb_0[0], rgb_0[1], rgb_0[2], 0xFF];
+    palette[1] = [rgb_1[0], rgb_1[1], rgb_1[2], 0xFF];
+
+    if color_0 > color_1 || !one_bit_alpha {
+        for channel in 0..3 {
+            let (value_0, value_1) = (rgb_0[channel] as u16, rgb_1[channel] as u16);
+            palette[2][channel] = ((2 * value_0 + value_1) / 3) as u8;
+            palette[3][channel] = ((value_0 + 2 * value_1) / 3) as u8;
+        }
+        palette[2][3] = 0xFF;
+        palette[3][3] = 0xFF;
     } else {
-        huffmantree_builder.bits_head[bit_data as usize] = symbol_data;
+        for channel in 0..3 {
+            palette[2][channel] = ((rgb_0[channel] as u16 + rgb_1[channel] as u16) / 2) as u8;
+        }
+        palette[2][3] = 0xFF;
+        palette[3] = [0, 0, 0, 0];
+    }
 
-        huffmantree_builder.bits_head_exist[bit_data as usize] = true;
+    for (texel_index, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (texel_index * 2)) & 0x03) as usize];
     }
-    Ok(())
 }
 
-fn check_bits_head(huffmantree_builder: &mut HuffmanTreeBuilder) -> std::io::Result<bool> {
-    for head in huffmantree_builder.bits_head_exist {
-        if head == true {
-            return Ok(false);
+/// Decode an 8-byte DXT3 alpha block holding one 4-bit alpha value per texel.
+fn decode_explicit_alpha_block(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let alpha_bits = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    for (texel_index, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha_bits >> (texel_index * 4)) & 0x0F) as u8 * 0x11;
+    }
+}
+
+/// Decode an 8-byte DXT5 style interpolated block into one 8-bit value per texel.
+fn decode_interpolated_block(block: &[u8], values: &mut [u8; 16]) {
+    let value_0 = block[0] as u16;
+    let value_1 = block[1] as u16;
+    let mut palette = [0u8; 8];
+    palette[0] = value_0 as u8;
+    palette[1] = value_1 as u8;
+    if value_0 > value_1 {
+        for step in 1..7 {
+            palette[step + 1] = (((7 - step as u16) * value_0 + step as u16 * value_1) / 7) as u8;
+        }
+    } else {
+        for step in 1..5 {
+            palette[step + 1] = (((5 - step as u16) * value_0 + step as u16 * value_1) / 5) as u8;
         }
+        palette[6] = 0;
+        palette[7] = 0xFF;
     }
 
-    Ok(true)
+    let mut index_bytes = [0u8; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+    for (texel_index, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (texel_index * 3)) & 0x07) as usize];
+    }
 }
 
-fn build_huffmantree(
-    huffmantree_data: &mut HuffmanTree,
-    huffmantree_builder: &mut HuffmanTreeBuilder,
-) -> std::io::Result<bool> {
-    if check_bits_head(huffmantree_builder)? {
-        return Ok(false);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE_ONE_BLOCK: (u32, u8) = (0b1, 1);
+    const SET_BLOCKS: (u32, u8) = (0b1, 1);
+
+    /// Build an ATEX texture: `bits` are packed most significant first into the words after
+    /// the compression header, followed by `raw_words` for the blocks they leave unset.
+    fn texture_file(
+        fourcc: &[u8; 4],
+        width: u16,
+        height: u16,
+        compression_flags: u32,
+        bits: &[(u32, u8)],
+        raw_words: &[u32],
+    ) -> Vec<u8> {
+        let mut words = Vec::new();
+        let mut current_word: u64 = 0;
+        let mut current_bits = 0;
+        for &(value, bit_count) in bits {
+            current_word = (current_word << bit_count) | value as u64;
+            current_bits += bit_count;
+            if current_bits >= 32 {
+                current_bits -= 32;
+                words.push((current_word >> current_bits) as u32);
+            }
+        }
+        if current_bits > 0 {
+            words.push((current_word << (32 - current_bits)) as u32);
+        }
+        words.extend_from_slice(raw_words);
+
+        let mut data = b"ATEX".to_vec();
+        data.extend_from_slice(fourcc);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&((words.len() * 4 + 8) as u32).to_le_bytes());
+        data.extend_from_slice(&compression_flags.to_le_bytes());
+        for word in words {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data
     }
-    *huffmantree_data = HuffmanTree::default();
-    let mut temp_code: u32 = 0;
-    let mut temp_bits: u8 = 0;
 
-    // First part, filling hashTable for codes that are of less than 8 bits
-    while temp_bits <= MAX_BITS_HASH as u8 {
-        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
+    fn inflate(data: Vec<u8>) -> Vec<u8> {
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        inflate_texture_file_buffer(data, &mut output_data_size, &mut output_data).unwrap();
+        assert_eq!(output_data_size as usize, output_data.len());
+        output_data
+    }
 
-        if data_exist {
-            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
+    #[test]
+    fn white_color_fills_the_whole_block() {
+        let data = texture_file(
+            b"DXT1",
+            4,
+            4,
+            CompressionFlags::CfDecodeWhiteColor as u32,
+            &[CODE_ONE_BLOCK, SET_BLOCKS],
+            &[],
+        );
+        assert_eq!(
+            inflate(data),
+            [0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
 
-            while data_exist {
-                // Processing hash values
-                let mut hash_value: u16 = (temp_code << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
-                let next_hash_value: u16 =
-                    ((temp_code.wrapping_add(1)) << (MAX_BITS_HASH as u8 - temp_bits)) as u16;
+    #[test]
+    fn plain_color_writes_the_color_component() {
+        // The alpha component is not covered by a bitmap, so it follows as two raw words
+        let data = texture_file(
+            b"DXT5",
+            4,
+            4,
+            CompressionFlags::CfDecodePlainColor as u32,
+            &[(10, 8), (100, 8), (200, 8), CODE_ONE_BLOCK, SET_BLOCKS],
+            &[0x0403_0201, 0x0807_0605],
+        );
+        assert_eq!(
+            inflate(data),
+            [
+                1, 2, 3, 4, 5, 6, 7, 8, 0x3A, 0x13, 0x18, 0x0B, 0x55, 0x55, 0x55, 0x55
+            ]
+        );
+    }
 
-                while hash_value < next_hash_value {
-                    huffmantree_data.symbol_value_hash_exist[hash_value as usize] = true;
-                    huffmantree_data.symbol_value_hash[hash_value as usize] = current_symbol;
-                    huffmantree_data.code_bits_hash[hash_value as usize] = temp_bits;
-                    hash_value = hash_value.wrapping_add(1);
-                }
+    #[test]
+    fn plain_color_near_white_selects_the_swapped_endpoint() {
+        // Rounding up every channel reaches 0xFFFF, so the endpoints swap and index 0 is picked
+        let data = texture_file(
+            b"DXT5",
+            4,
+            4,
+            CompressionFlags::CfDecodePlainColor as u32,
+            &[(249, 8), (253, 8), (249, 8), CODE_ONE_BLOCK, SET_BLOCKS],
+            &[0, 0],
+        );
+        assert_eq!(
+            inflate(data)[8..],
+            [0xFF, 0xFF, 0xDD, 0xF7, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
 
-                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
-                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
-                temp_code = temp_code.wrapping_sub(1);
-            }
+    #[test]
+    fn raw_words_fill_alpha_then_color_components_in_block_order() {
+        let data = texture_file(
+            b"DXT5",
+            8,
+            4,
+            0,
+            &[],
+            &[0xA0, 0xA1, 0xB0, 0xB1, 0xC0, 0xD0, 0xC1, 0xD1],
+        );
+        let words: Vec<u32> = inflate(data)
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        assert_eq!(words, [0xA0, 0xA1, 0xC0, 0xC1, 0xB0, 0xB1, 0xD0, 0xD1]);
+    }
+
+    #[test]
+    fn raw_words_follow_the_last_partly_read_word() {
+        // Block 0 is white; block 1 takes its color from the raw word after the bitmap bits
+        let data = texture_file(
+            b"DXT1",
+            8,
+            4,
+            CompressionFlags::CfDecodeWhiteColor as u32,
+            &[CODE_ONE_BLOCK, SET_BLOCKS, CODE_ONE_BLOCK, (0, 1)],
+            &[0x0000_F800, 0x5555_5555],
+        );
+        assert_eq!(
+            inflate(data),
+            [
+                0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0xF8, 0x00, 0x00, 0x55, 0x55,
+                0x55, 0x55
+            ]
+        );
+    }
+
+    #[test]
+    fn is_texture_recognizes_each_magic() {
+        for (magic, expected) in [
+            (b"ATEX", TextureMagic::Atex),
+            (b"ATEC", TextureMagic::Atec),
+            (b"ATEP", TextureMagic::Atep),
+        ] {
+            let mut data = magic.to_vec();
+            data.extend_from_slice(b"DXT5");
+            assert_eq!(
+                is_texture(&data),
+                Some(TextureKind {
+                    magic: expected,
+                    fourcc: Some(0x35545844),
+                })
+            );
         }
+    }
 
-        temp_code = (temp_code << 1) + 1;
-        temp_bits = temp_bits.wrapping_add(1);
+    #[test]
+    fn is_texture_handles_short_and_foreign_buffers() {
+        assert_eq!(
+            is_texture(b"ATEC\x01"),
+            Some(TextureKind {
+                magic: TextureMagic::Atec,
+                fourcc: None,
+            })
+        );
+        assert_eq!(is_texture(b"ATE"), None);
+        assert_eq!(is_texture(b""), None);
+        assert_eq!(is_texture(b"\x89PNG\r\n\x1a\n"), None);
     }
 
-    let mut temp_code_comparison_index: u16 = 0;
-    let mut symbol_offset: u16 = 0;
+    #[test]
+    fn deduce_format_names_known_fourccs() {
+        let (format, format_name) = deduce_format(0x58434433, texture_formats()).unwrap();
+        assert_eq!(format_name, "3DCX");
+        assert_eq!(format.flag_data, FormatFlags::FfBicolorcomp as u16);
+        assert_eq!(format.pixel_size_bits, 8);
 
-    // Second part, filling classical structure for other codes
-    while temp_bits < MAX_CODE_BITS_LENGTH as u8 {
-        let mut data_exist: bool = huffmantree_builder.bits_head_exist[temp_bits as usize];
+        let (_, format_name) =
+            deduce_format(u32::from_le_bytes(*b"DXT1"), texture_formats()).unwrap();
+        assert_eq!(format_name, "DXT1");
+    }
 
-        if data_exist {
-            let mut current_symbol: u16 = huffmantree_builder.bits_head[temp_bits as usize];
+    #[test]
+    fn deduce_format_rejects_unknown_fourccs() {
+        let err = deduce_format(u32::from_le_bytes(*b"ABCD"), texture_formats()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("0x44434241"));
+    }
 
-            while data_exist {
-                // Registering the code
-                huffmantree_data.symbol_value[symbol_offset as usize] = current_symbol;
+    #[test]
+    fn decode_to_rgba8_fills_a_solid_dxt1_texture() {
+        // Four blocks whose color 0 is pure green, with every texel using it
+        let data = texture_file(
+            b"DXT1",
+            8,
+            8,
+            0,
+            &[],
+            &[0x07E0, 0x07E0, 0x07E0, 0x07E0, 0, 0, 0, 0],
+        );
+        let (width, height, pixels) = decode_to_rgba8(&data).unwrap();
 
-                symbol_offset = symbol_offset.wrapping_add(1);
-                data_exist = huffmantree_builder.bits_body_exist[current_symbol as usize];
-                current_symbol = huffmantree_builder.bits_body[current_symbol as usize];
+        assert_eq!((width, height), (8, 8));
+        assert_eq!(pixels.len(), 8 * 8 * 4);
+        assert_eq!(pixels[0..4], [0, 255, 0, 255]);
+        assert_eq!(pixels[pixels.len() - 4..], [0, 255, 0, 255]);
+    }
 
-                temp_code = temp_code.wrapping_sub(1);
-            }
+    #[test]
+    fn bc5_blocks_reconstruct_the_normal_z() {
+        // Both channels at the midpoint: a flat normal pointing straight out
+        let mut block = [0u8; 16];
+        block[0..2].copy_from_slice(&[128, 128]);
+        block[8..10].copy_from_slice(&[128, 128]);
+        let pixels = decode_dxt_blocks(&block, DxtBlockKind::Bc5, 4, 4);
+        assert_eq!(pixels.len(), 4 * 4 * 4);
+        for texel in pixels.chunks_exact(4) {
+            assert_eq!(texel, [128, 128, 255, 255]);
+        }
 
-            // Minimum code value for temp_bits bits
-            huffmantree_data.code_comparison[temp_code_comparison_index as usize] =
-                temp_code.wrapping_add(1) << (32 - temp_bits);
+        // A normal lying along X has no Z left
+        assert_eq!(reconstruct_normal_z(255, 128), 128);
+        assert_eq!(reconstruct_normal_z(128, 0), 128);
+    }
 
-            // Number of bits for l_codeCompIndex index
-            huffmantree_data.code_bits[temp_code_comparison_index as usize] = temp_bits;
+    #[test]
+    fn probe_dimensions_reads_the_header_only() {
+        // Only the 12-byte header; there are no blocks to decode
+        let mut header = b"ATEXDXT5".to_vec();
+        header.extend_from_slice(&512u16.to_le_bytes());
+        header.extend_from_slice(&256u16.to_le_bytes());
+        assert_eq!(probe_dimensions(&header).unwrap(), (512, 256, "DXT5"));
 
-            // Offset in symbol_value table to reach the value
-            huffmantree_data.symbol_value_offset[temp_code_comparison_index as usize] =
-                symbol_offset.wrapping_sub(1);
+        header[4..8].copy_from_slice(b"ABCD");
+        let err = probe_dimensions(&header).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
 
-            temp_code_comparison_index = temp_code_comparison_index.wrapping_add(1);
-        }
+        assert!(probe_dimensions(&header[..10]).is_err());
+    }
+
+    #[test]
+    fn oversized_dimensions_are_rejected_before_allocating() {
+        let data = texture_file(b"DXT1", 65535, 65535, 0, &[CODE_ONE_BLOCK], &[]);
+        let mut output_data_size = 0;
+        let mut output_data = Vec::new();
+        let err =
+            inflate_texture_file_buffer(data, &mut output_data_size, &mut output_data).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("65535x65535"));
+        assert_eq!(output_data.capacity(), 0);
+
+        let data = texture_file(b"DXT1", 8, 8, 0, &[], &[0; 8]);
+        let err =
+            inflate_texture_file_buffer_bounded(data, 4, &mut output_data_size, &mut output_data)
+                .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // Uncompressed payloads get the same check: 8x8 DXT1 is four 8 byte blocks
+        let mut data = b"ATEXDXT1".to_vec();
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(&[0; 32]);
+        let err =
+            inflate_texture_file_buffer_bounded(data, 4, &mut output_data_size, &mut output_data)
+                .unwrap_err();
+        assert!(err.to_string().contains("8x8"));
+
+        let err = inflate_texture_block_buffer(
+            vec![0; 8],
+            &mut output_data_size,
+            &mut output_data,
+            65535,
+            4,
+            u32::from_le_bytes(*b"DXT1"),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(output_data.capacity(), 0);
+    }
 
-        temp_code = (temp_code << 1) + 1;
-        temp_bits = temp_bits.wrapping_add(1);
+    #[test]
+    fn decode_to_image_matches_the_header_dimensions() {
+        // 3x2 blocks of solid blue
+        let mut raw_words = vec![0x001F; 6];
+        raw_words.extend_from_slice(&[0; 6]);
+        let data = texture_file(b"DXT1", 12, 8, 0, &[], &raw_words);
+        let image = decode_to_image(&data).unwrap();
+
+        assert_eq!(image.dimensions(), (12, 8));
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(11, 7).0, [0, 0, 255, 255]);
     }
 
-    Ok(true)
+    #[test]
+    fn dxta_blocks_expand_to_grayscale() {
+        // Endpoints 255 and 0; texels 0, 1 and 2 use palette entries 0, 1 and 2
+        let block = [0xFF, 0x00, 0b1000_1000, 0, 0, 0, 0, 0];
+        let pixels = decode_dxt_blocks(&block, DxtBlockKind::Bc4, 4, 4);
+
+        assert_eq!(pixels[0..4], [255, 255, 255, 255]);
+        assert_eq!(pixels[4..8], [0, 0, 0, 255]);
+        assert_eq!(pixels[8..12], [218, 218, 218, 255]);
+        assert_eq!(pixels[60..64], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn decode_diagnostics_are_debug_log_messages() {
+        crate::test_logger::install();
+
+        // A 12x4 texture, so its block count is unique among the tests; every block is stored raw
+        let data = texture_file(b"DXT1", 12, 4, 0, &[], &[0; 6]);
+        inflate(data);
+
+        // Debug messages are dropped at the default log level, so nothing is printed
+        let levels = crate::test_logger::levels_of(", 3 pixel blocks.");
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|&level| level == log::Level::Debug));
+    }
+
+    #[test]
+    fn uncompressed_payloads_are_copied_as_blocks() {
+        // An 8x4 DXT1 texture: a red block, then a blue one, every texel using color 0
+        let mut blocks = Vec::new();
+        for color in [0xF800u16, 0x001F] {
+            blocks.extend_from_slice(&color.to_le_bytes());
+            blocks.extend_from_slice(&[0; 6]);
+        }
+        let mut top_level = b"ATEXDXT1".to_vec();
+        top_level.extend_from_slice(&8u16.to_le_bytes());
+        top_level.extend_from_slice(&4u16.to_le_bytes());
+        top_level.extend_from_slice(&blocks);
+        // The 4x2, 2x1 and 1x1 levels take one block each
+        let mut mip_chain = top_level.clone();
+        mip_chain.extend_from_slice(&[0xAA; 24]);
+
+        for data in [top_level, mip_chain] {
+            assert_eq!(inflate(data.clone()), blocks);
+
+            let (width, height, pixels) = decode_to_rgba8(&data).unwrap();
+            assert_eq!((width, height), (8, 4));
+            for (texel, pixel) in pixels.chunks_exact(4).enumerate() {
+                let expected = if texel % 8 < 4 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                };
+                assert_eq!(pixel, expected, "texel {}", texel);
+            }
+        }
+    }
 }